@@ -0,0 +1,143 @@
+use std::{fmt, ops::{Bound, Deref, RangeBounds}, path::Path, str::FromStr};
+
+use crate::{CommandError, ParseArgument};
+
+/// A compiled `*.rs`-style glob, validated when the argument is parsed.
+#[derive(Clone, Debug)]
+pub struct GlobPattern(globset::GlobMatcher);
+
+impl GlobPattern {
+    pub fn as_str(&self) -> &str {
+        self.0.glob().glob()
+    }
+
+    pub fn is_match(&self, path: impl AsRef<Path>) -> bool {
+        self.0.is_match(path)
+    }
+}
+
+impl fmt::Display for GlobPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl<'a> ParseArgument<'a> for GlobPattern {
+    fn parse(s: &str) -> Result<Self, CommandError> {
+        globset::Glob::new(s)
+            .map(|glob| GlobPattern(glob.compile_matcher()))
+            .map_err(|e| CommandError::InvalidArguments(format!("Invalid glob pattern '{}': {}", s, e.kind())))
+    }
+}
+
+/// A `start..end` range such as `2..10`, `..5`, `3..` or `1..=4`, with either side optional.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArgRange<T> {
+    pub start: Bound<T>,
+    pub end: Bound<T>,
+}
+
+impl<T: PartialOrd> ArgRange<T> {
+    pub fn contains(&self, value: &T) -> bool {
+        RangeBounds::contains(self, value)
+    }
+}
+
+impl<T> RangeBounds<T> for ArgRange<T> {
+    fn start_bound(&self) -> Bound<&T> {
+        self.start.as_ref()
+    }
+
+    fn end_bound(&self) -> Bound<&T> {
+        self.end.as_ref()
+    }
+}
+
+impl<'a, T: ParseArgument<'a> + PartialOrd> ParseArgument<'a> for ArgRange<T> {
+    fn parse(s: &'a str) -> Result<Self, CommandError> {
+        let Some((start, end)) = s.split_once("..") else {
+            return Err(CommandError::InvalidArguments(format!("Invalid range '{}', expected START..END", s)));
+        };
+
+        let start = match start {
+            "" => Bound::Unbounded,
+            start => Bound::Included(T::parse(start)?),
+        };
+        let end = match end.strip_prefix('=') {
+            Some("") => return Err(CommandError::InvalidArguments(format!("Invalid range '{}', '..=' needs an end", s))),
+            Some(end) => Bound::Included(T::parse(end)?),
+            None if end.is_empty() => Bound::Unbounded,
+            None => Bound::Excluded(T::parse(end)?),
+        };
+
+        if let (Bound::Included(start), Bound::Included(end) | Bound::Excluded(end)) = (&start, &end) {
+            if start > end {
+                return Err(CommandError::InvalidArguments(format!("Invalid range '{}', start is greater than end", s)));
+            }
+        }
+
+        Ok(ArgRange { start, end })
+    }
+}
+
+/// An integer argument limited to `MIN..=MAX`, e.g. `Bounded<u8, 1, 10>`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Bounded<T, const MIN: i128, const MAX: i128>(pub T);
+
+impl<T, const MIN: i128, const MAX: i128> Bounded<T, MIN, MAX> {
+    pub fn get(self) -> T {
+        self.0
+    }
+}
+
+impl<T, const MIN: i128, const MAX: i128> Deref for Bounded<T, MIN, MAX> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<'a, T, const MIN: i128, const MAX: i128> ParseArgument<'a> for Bounded<T, MIN, MAX>
+where
+    T: ParseArgument<'a> + Copy + TryInto<i128>,
+{
+    fn parse(s: &'a str) -> Result<Self, CommandError> {
+        let value = T::parse(s)?;
+        let wide: Result<i128, _> = value.try_into();
+        match wide {
+            Ok(wide) if (MIN..=MAX).contains(&wide) => Ok(Bounded(value)),
+            _ => Err(CommandError::InvalidArguments(format!("Value '{}' is out of range, expected {}..={}", s, MIN, MAX))),
+        }
+    }
+}
+
+/// Adapts any [`FromStr`] type into a command argument, e.g. `FromStrArg<std::net::Ipv6Addr>`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FromStrArg<T>(pub T);
+
+impl<T> FromStrArg<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for FromStrArg<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<'a, T> ParseArgument<'a> for FromStrArg<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    fn parse(s: &str) -> Result<Self, CommandError> {
+        s.parse()
+            .map(FromStrArg)
+            .map_err(|e| CommandError::InvalidArguments(format!("Invalid value '{}': {}", s, e)))
+    }
+}
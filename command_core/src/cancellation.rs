@@ -0,0 +1,87 @@
+use std::{cell::{Cell, RefCell}, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}};
+
+use crate::CommandError;
+
+thread_local! {
+    /// The token of the invocation running on this thread, so commands run in the background, e.g.
+    /// by a remote client or a scheduled job, have their own.
+    static CURRENT: RefCell<Option<CancellationToken>> = const { RefCell::new(None) };
+
+    /// Whether this thread runs what the user types, see [`CancellationToken::foreground`].
+    static IN_FOREGROUND: Cell<bool> = const { Cell::new(false) };
+}
+
+/// The token of the invocation running on the foreground thread, the one Ctrl-C interrupts.
+static FOREGROUND: Mutex<Option<CancellationToken>> = Mutex::new(None);
+
+/// Cooperative cancellation flag for a single command invocation.
+///
+/// A fresh token is installed by the registry for every invocation; long-running builtins
+/// should call [`CancellationToken::check`] between units of work.
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Returns `CommandError::Interrupted` once the token has been tripped.
+    pub fn check(&self) -> Result<(), CommandError> {
+        if self.is_cancelled() {
+            Err(CommandError::Interrupted)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The token of the invocation running on this thread, or a token that is never tripped.
+    pub fn current() -> Self {
+        CURRENT.with(|current| current.borrow().clone()).unwrap_or_default()
+    }
+
+    /// Runs `f` with the current thread as the foreground one, whose commands Ctrl-C interrupts
+    /// and which may ask the user questions. [`crate::Shell::run`] does so for its loop; embedders
+    /// evaluating typed lines some other way wrap that in it.
+    pub fn foreground<R>(f: impl FnOnce() -> R) -> R {
+        struct Restore(bool);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                IN_FOREGROUND.with(|foreground| foreground.set(self.0));
+            }
+        }
+
+        let _restore = Restore(IN_FOREGROUND.with(|foreground| foreground.replace(true)));
+        f()
+    }
+
+    /// Whether the current thread is the foreground one, and not e.g. running a scheduled job.
+    pub fn is_foreground() -> bool {
+        IN_FOREGROUND.with(Cell::get)
+    }
+
+    /// Trips the token of the invocation running on the foreground thread, if any. Intended for Ctrl-C
+    /// handlers, which shouldn't interrupt commands running in the background instead.
+    pub fn cancel_current() {
+        if let Some(token) = FOREGROUND.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            token.cancel();
+        }
+    }
+
+    /// Installs `token` as the current one of this thread, returning the token it replaced.
+    pub(crate) fn replace_current(token: Option<CancellationToken>) -> Option<CancellationToken> {
+        if Self::is_foreground() {
+            FOREGROUND.lock().unwrap_or_else(|e| e.into_inner()).clone_from(&token);
+        }
+        CURRENT.with(|current| std::mem::replace(&mut *current.borrow_mut(), token))
+    }
+}
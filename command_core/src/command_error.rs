@@ -1,16 +1,71 @@
-use std::{error::Error, io::Error as IoError, path::PathBuf};
+use std::{error::Error, ffi::OsStr, io::Error as IoError, path::PathBuf, time::Duration};
 
 use crate::command_info::CommandInfo;
 
+#[derive(Debug)]
 pub enum CommandError {
     TooFewArguments(usize, &'static CommandInfo),
     TooManyArguments(usize, &'static CommandInfo),
-    CommandNotFound(String),
+    CommandNotFound(String, Vec<String>),
+    AmbiguousCommand(String, Vec<String>),
     CommandFailed(String),
     InvalidArguments(String),
+    InvalidArgumentValue {
+        argument: &'static str,
+        position: usize,
+        value: String,
+        expected: &'static str,
+        source: Box<CommandError>,
+    },
     CannotAccessCurrentDirectory(IoError),
     DirectoryReadError(PathBuf, IoError),
     FileReadError(PathBuf, IoError),
+    Interrupted,
+    TimedOut(Duration),
+    NotSupported(String),
+    /// A command handler panicked; the panic was caught so the session keeps running.
+    Panicked {
+        command: String,
+        message: String,
+    },
+}
+
+impl CommandError {
+    /// `CommandNotFound` for `name`, with the closest known commands attached as suggestions.
+    pub fn not_found(name: &str) -> Self {
+        CommandError::CommandNotFound(name.to_string(), crate::suggest::suggestions(name))
+    }
+
+    /// Wraps a failed `ParseArgument::parse` with the argument's name, its 1-based position and the offending token.
+    pub fn invalid_argument(argument: &'static str, position: usize, value: &OsStr, expected: &'static str, source: CommandError) -> Self {
+        CommandError::InvalidArgumentValue {
+            argument,
+            position,
+            value: value.to_string_lossy().into_owned(),
+            expected,
+            source: Box::new(source),
+        }
+    }
+
+    /// Stable process exit code for this failure, following shell conventions where one exists.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CommandError::CommandFailed(_) => 1,
+            CommandError::TooFewArguments(..)
+            | CommandError::TooManyArguments(..)
+            | CommandError::InvalidArguments(_)
+            | CommandError::InvalidArgumentValue { .. }
+            | CommandError::AmbiguousCommand(..) => 2,
+            CommandError::NotSupported(_) => 69,
+            CommandError::CannotAccessCurrentDirectory(_)
+            | CommandError::DirectoryReadError(..)
+            | CommandError::FileReadError(..) => 74,
+            CommandError::Panicked { .. } => 101,
+            CommandError::TimedOut(_) => 124,
+            CommandError::CommandNotFound(..) => 127,
+            CommandError::Interrupted => 130,
+        }
+    }
 }
 
 impl std::fmt::Display for CommandError {
@@ -22,8 +77,17 @@ impl std::fmt::Display for CommandError {
             CommandError::TooManyArguments(args_passed, info) => {
                 write!(f, "Too many arguments passed '{}' when calling command '{}', the maximum required is '{}'", args_passed, info.name, info.max)
             },
-            CommandError::CommandNotFound(cmd) => {
-                write!(f, "Command '{}' not found", cmd)
+            CommandError::CommandNotFound(cmd, suggestions) => {
+                write!(f, "Command '{}' not found", cmd)?;
+                if !suggestions.is_empty() {
+                    let quoted: Vec<String> = suggestions.iter().map(|s| format!("'{}'", s)).collect();
+                    write!(f, ". Did you mean {}?", quoted.join(" or "))?;
+                }
+                Ok(())
+            },
+            CommandError::AmbiguousCommand(cmd, candidates) => {
+                let quoted: Vec<String> = candidates.iter().map(|s| format!("'{}'", s)).collect();
+                write!(f, "Command '{}' is ambiguous, it could be {}", cmd, quoted.join(", "))
             },
             CommandError::CommandFailed(e) => {
                 write!(f, "{}", e)
@@ -31,6 +95,14 @@ impl std::fmt::Display for CommandError {
             CommandError::InvalidArguments(e) => {
                 write!(f, "{}", e)
             }
+            CommandError::InvalidArgumentValue { argument, position, value, expected, source } => {
+                write!(f, "invalid value '{}' for argument '{}' at position {} (expected {})", value, argument, position, expected)?;
+                // Type mismatches are fully described above; validation failures carry a more specific reason.
+                if let CommandError::InvalidArguments(reason) = source.as_ref() {
+                    write!(f, ": {}", reason)?;
+                }
+                Ok(())
+            }
             CommandError::CannotAccessCurrentDirectory(e) => {
                 write!(f, "Could not access the current directory: {}", e)
             },
@@ -40,6 +112,34 @@ impl std::fmt::Display for CommandError {
             CommandError::FileReadError(path, e) => {
                 write!(f, "Could not read file '{}': {}", path.display(), e)
             },
+            CommandError::Interrupted => {
+                write!(f, "Interrupted")
+            },
+            CommandError::TimedOut(after) => {
+                write!(f, "Timed out after {:?}", after)
+            },
+            CommandError::NotSupported(what) => {
+                write!(f, "Not supported: {}", what)
+            },
+            CommandError::Panicked { command, message } => {
+                write!(f, "Command '{}' panicked: {}", command, message)?;
+                if std::env::var_os("RUST_BACKTRACE").is_none_or(|value| value == "0") {
+                    write!(f, " (run with RUST_BACKTRACE=1 to see a backtrace)")?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+impl Error for CommandError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CommandError::CannotAccessCurrentDirectory(e)
+            | CommandError::DirectoryReadError(_, e)
+            | CommandError::FileReadError(_, e) => Some(e),
+            CommandError::InvalidArgumentValue { source, .. } => Some(source.as_ref()),
+            _ => None,
         }
     }
 }
@@ -1,6 +1,18 @@
+use std::ffi::OsStr;
+
 use crate::command_error::CommandError;
 
 pub trait CommandHandler: Sync + Send {
-    fn call(&self, args: &[&str]) -> Result<(), CommandError>;
+    /// Runs the command with raw OS arguments, so paths need not be valid UTF-8.
+    fn call_os(&self, args: &[&OsStr]) -> Result<(), CommandError>;
+
+    fn call(&self, args: &[&str]) -> Result<(), CommandError> {
+        let args: Vec<&OsStr> = args.iter().map(OsStr::new).collect();
+        self.call_os(&args)
+    }
+
+    /// Checks and parses the arguments like [`call_os`](Self::call_os) without running the command.
+    fn bind_os(&self, args: &[&OsStr]) -> Result<(), CommandError>;
+
     fn command_info(&self) -> &'static crate::CommandInfo;
-}
\ No newline at end of file
+}
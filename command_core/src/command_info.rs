@@ -4,27 +4,35 @@ pub struct CommandInfo {
     pub name: &'static str,
     pub description: &'static str,
     pub aliases: &'static [&'static str],
+    pub usage: &'static str,
+    pub examples: &'static [&'static str],
+    /// Whether the command takes `--dry-run`, which `set dry-run on` then passes to it.
+    pub dry_run: bool,
+    /// Whether the command writes its output through [`crate::output::emit`], so `--json` applies.
+    pub json: bool,
     pub min: usize,
     pub max: usize,
     pub handler: &'static dyn CommandHandler,
+    /// Path of the function implementing the command, e.g. `shell::file_commands::cmd_rm`.
+    pub source: &'static str,
 }
 
-impl CommandInfo {
-    pub const fn new(
-        name: &'static str,
-        description: &'static str,
-        aliases: &'static [&'static str],
-        min: usize,
-        max: usize,
-        handler: &'static dyn CommandHandler,
-    ) -> Self {
-        Self {
-            name,
-            description,
-            aliases,
-            min,
-            max,
-            handler,
-        }
+/// Binds `args` to the parameters of `info`'s command, reporting what running it would fail with
+/// before the command itself starts. Never panics, so it can be fuzzed.
+pub fn bind_args(info: &CommandInfo, args: &[&str]) -> Result<(), crate::CommandError> {
+    let args: Vec<&std::ffi::OsStr> = args.iter().map(std::ffi::OsStr::new).collect();
+    info.handler.bind_os(&args)
+}
+
+impl std::fmt::Debug for CommandInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CommandInfo")
+            .field("name", &self.name)
+            .field("aliases", &self.aliases)
+            .field("usage", &self.usage)
+            .field("min", &self.min)
+            .field("max", &self.max)
+            .field("source", &self.source)
+            .finish_non_exhaustive()
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,164 @@
+use std::{collections::{BTreeMap, BTreeSet}, env, ffi::OsString, fs, path::{Path, PathBuf}, sync::RwLock};
+
+/// Extensions that make a file executable on Windows, from `PATHEXT`, lowercased and without the dot.
+/// Empty on other platforms, where the execute permission decides.
+pub fn executable_extensions() -> Vec<String> {
+    if !cfg!(windows) {
+        return Vec::new();
+    }
+
+    env::var("PATHEXT")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| ".COM;.EXE;.BAT;.CMD".to_string())
+        .split(';')
+        .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect()
+}
+
+fn has_executable_extension(path: &Path, extensions: &[String]) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| extensions.contains(&ext))
+}
+
+#[cfg(unix)]
+fn is_executable_file(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable_file(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Batch files, which Windows can only run through `cmd /C`.
+pub fn is_batch_file(path: &Path) -> bool {
+    cfg!(windows) && has_executable_extension(path, &["bat".to_string(), "cmd".to_string()])
+}
+
+/// Executables on `PATH` by command name, built for the `PATH` value it was scanned with.
+struct ExecutableIndex {
+    path: Option<OsString>,
+    executables: BTreeMap<String, PathBuf>,
+}
+
+static INDEX: RwLock<Option<ExecutableIndex>> = RwLock::new(None);
+
+/// The command name `path` is run as: the file name, or the lowercased stem on Windows.
+fn command_name(path: &Path) -> Option<String> {
+    if cfg!(windows) {
+        path.file_stem().map(|s| s.to_string_lossy().to_lowercase())
+    } else {
+        path.file_name().map(|s| s.to_string_lossy().into_owned())
+    }
+}
+
+fn scan(path: Option<&OsString>) -> BTreeMap<String, PathBuf> {
+    let Some(path) = path else {
+        return BTreeMap::new();
+    };
+    let extensions = executable_extensions();
+    let mut executables = BTreeMap::new();
+
+    for dir in env::split_paths(path) {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+
+        let mut found: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().map(|t| !t.is_dir()).unwrap_or(false))
+            .map(|entry| entry.path())
+            .filter(|path| if cfg!(windows) {
+                has_executable_extension(path, &extensions)
+            } else {
+                is_executable_file(path)
+            })
+            .collect();
+
+        // Within one directory, `PATHEXT` order decides between `build.cmd` and `build.exe`.
+        found.sort_by_key(|path| {
+            path.extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .and_then(|ext| extensions.iter().position(|e| *e == ext))
+        });
+
+        for path in found {
+            if let Some(name) = command_name(&path) {
+                // Earlier `PATH` directories win.
+                executables.entry(name).or_insert(path);
+            }
+        }
+    }
+
+    executables
+}
+
+/// Rebuilds the index of executables on `PATH`, returning how many were found.
+pub fn rehash() -> usize {
+    let path = env::var_os("PATH");
+    let executables = scan(path.as_ref());
+    let count = executables.len();
+
+    *INDEX.write().unwrap_or_else(|e| e.into_inner()) = Some(ExecutableIndex { path, executables });
+    count
+}
+
+/// Runs `f` on the index, rebuilding it first if it was never built or `PATH` changed since.
+fn with_index<R>(f: impl FnOnce(&BTreeMap<String, PathBuf>) -> R) -> R {
+    {
+        let index = INDEX.read().unwrap_or_else(|e| e.into_inner());
+        if let Some(index) = index.as_ref().filter(|index| index.path == env::var_os("PATH")) {
+            return f(&index.executables);
+        }
+    }
+
+    rehash();
+    let index = INDEX.read().unwrap_or_else(|e| e.into_inner());
+    f(index.as_ref().map(|index| &index.executables).unwrap_or(&BTreeMap::new()))
+}
+
+/// Names of executables found on `PATH`, without their extension on Windows.
+pub fn path_executables() -> BTreeSet<String> {
+    with_index(|executables| executables.keys().cloned().collect())
+}
+
+/// `candidate` itself if it is executable, or else `candidate` with one of the `PATHEXT` extensions appended.
+fn resolve_candidate(candidate: &Path, extensions: &[String]) -> Option<PathBuf> {
+    if (extensions.is_empty() || has_executable_extension(candidate, extensions)) && is_executable_file(candidate) {
+        return Some(candidate.to_path_buf());
+    }
+
+    extensions.iter()
+        .map(|ext| {
+            let mut name = OsString::from(candidate.as_os_str());
+            name.push(".");
+            name.push(ext);
+            PathBuf::from(name)
+        })
+        .find(|candidate| is_executable_file(candidate))
+}
+
+/// Full path of the executable `name` refers to: a path relative to the current directory when it
+/// contains a separator, otherwise the first match on `PATH`, trying the `PATHEXT` extensions on Windows.
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    let extensions = executable_extensions();
+
+    if Path::new(name).components().count() > 1 {
+        return resolve_candidate(Path::new(name), &extensions);
+    }
+
+    let key = if cfg!(windows) { name.to_lowercase() } else { name.to_string() };
+    let indexed = with_index(|executables| executables.get(&key).cloned());
+    if let Some(path) = indexed.filter(|path| is_executable_file(path)) {
+        return Some(path);
+    }
+
+    // Not indexed, or removed since: search `PATH` directly, e.g. for a tool installed after the last rehash.
+    let path = env::var_os("PATH")?;
+    env::split_paths(&path).find_map(|dir| resolve_candidate(&dir.join(name), &extensions))
+}
@@ -0,0 +1,39 @@
+use std::{
+    cell::RefCell,
+    io::{self, Cursor, Read},
+};
+
+thread_local! {
+    /// Inputs given to the commands running on this thread, innermost last, e.g. a here-document.
+    static PROVIDED: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Runs `f` with `data` as the standard input of the commands it runs on the current thread:
+/// builtins read it through [`stdin`], external programs are given it by whoever spawns them.
+pub fn with_stdin<R>(data: Vec<u8>, f: impl FnOnce() -> R) -> R {
+    /// Pops the input when dropped, so a builtin that panics doesn't leave it to later commands.
+    struct Provided;
+
+    impl Drop for Provided {
+        fn drop(&mut self) {
+            PROVIDED.with(|provided| provided.borrow_mut().pop());
+        }
+    }
+
+    PROVIDED.with(|provided| provided.borrow_mut().push(data));
+    let _provided = Provided;
+    f()
+}
+
+/// The input [`with_stdin`] provides, if any.
+pub fn provided() -> Option<Vec<u8>> {
+    PROVIDED.with(|provided| provided.borrow().last().cloned())
+}
+
+/// Standard input for a builtin: what [`with_stdin`] provides, or else the process's.
+pub fn stdin() -> Box<dyn Read> {
+    match provided() {
+        Some(data) => Box::new(Cursor::new(data)),
+        None => Box::new(io::stdin()),
+    }
+}
@@ -1,11 +1,27 @@
+pub mod arg_types;
+pub mod cancellation;
 pub mod command_error;
 pub mod command_info;
 pub mod command_handler;
+pub mod executables;
+pub mod input;
+pub mod middleware;
+pub mod output;
 pub mod parse_argument;
 pub mod registry;
+pub mod shell;
+pub mod suggest;
+pub mod table;
+pub mod vfs;
 
+pub use arg_types::{ArgRange, Bounded, FromStrArg, GlobPattern};
+pub use cancellation::CancellationToken;
 pub use command_error::CommandError;
-pub use command_info::CommandInfo;
+pub use command_info::{bind_args, CommandInfo};
 pub use command_handler::CommandHandler;
+pub use middleware::{CommandMiddleware, Invocation, Next};
+pub use output::{CapturedOutput, CommandOutput, OutputFormat};
 pub use parse_argument::ParseArgument;
-pub use registry::{COMMANDS, CommandRegistry};
\ No newline at end of file
+pub use registry::{COMMANDS, Allowed, Collision, CommandOverride, CommandRegistry, LookupOptions, Precedence};
+pub use shell::{find_heredoc, parse_line, CommandReport, Heredoc, LastCommand, ParsedLine, ScriptReport, Shell, ShellBuilder};
+pub use table::{Align, Table};
\ No newline at end of file
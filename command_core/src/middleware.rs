@@ -0,0 +1,44 @@
+use std::ffi::OsStr;
+
+use crate::{command_info::CommandInfo, CommandError};
+
+/// A single command invocation as seen by the middleware chain.
+pub struct Invocation<'a> {
+    pub name: &'a str,
+    pub args: &'a [&'a OsStr],
+    /// The registered builtin, or `None` when the invocation resolves to an external program.
+    pub info: Option<&'static CommandInfo>,
+}
+
+impl<'a> Invocation<'a> {
+    pub fn is_builtin(&self) -> bool {
+        self.info.is_some()
+    }
+}
+
+/// Wraps command execution. Implementations decide whether, and how, to call into the rest of the chain.
+pub trait CommandMiddleware: Sync + Send {
+    fn handle(&self, invocation: &Invocation, next: Next) -> Result<(), CommandError>;
+}
+
+/// The remainder of the middleware chain, ending with the command itself.
+pub struct Next<'a> {
+    chain: &'a [Box<dyn CommandMiddleware>],
+    exec: &'a dyn Fn(&Invocation) -> Result<(), CommandError>,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(
+        chain: &'a [Box<dyn CommandMiddleware>],
+        exec: &'a dyn Fn(&Invocation) -> Result<(), CommandError>,
+    ) -> Self {
+        Self { chain, exec }
+    }
+
+    pub fn run(self, invocation: &Invocation) -> Result<(), CommandError> {
+        match self.chain.split_first() {
+            Some((middleware, rest)) => middleware.handle(invocation, Next::new(rest, self.exec)),
+            None => (self.exec)(invocation),
+        }
+    }
+}
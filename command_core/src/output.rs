@@ -0,0 +1,255 @@
+use std::{borrow::Cow, cell::RefCell, ffi::OsStr, io::{self, Write}, sync::RwLock};
+
+use serde::Serialize;
+
+use crate::{middleware::{CommandMiddleware, Invocation, Next}, CommandError};
+
+/// How structured command output is written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    /// One JSON value per line; arrays are written one element per line.
+    Json,
+}
+
+static FORMAT: RwLock<OutputFormat> = RwLock::new(OutputFormat::Text);
+
+/// What a command wrote while running under [`capture`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CapturedOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl CapturedOutput {
+    pub fn stdout_text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.stdout)
+    }
+
+    pub fn stderr_text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.stderr)
+    }
+}
+
+thread_local! {
+    /// Outputs being captured on this thread, innermost last.
+    static CAPTURES: RefCell<Vec<CapturedOutput>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Pops the innermost capture when dropped, so a panic while capturing doesn't leave it collecting
+/// everything the thread writes afterwards.
+struct CaptureFrame;
+
+impl CaptureFrame {
+    fn finish(self) -> CapturedOutput {
+        std::mem::forget(self);
+        CAPTURES.with(|captures| captures.borrow_mut().pop()).unwrap_or_default()
+    }
+}
+
+impl Drop for CaptureFrame {
+    fn drop(&mut self) {
+        CAPTURES.with(|captures| captures.borrow_mut().pop());
+    }
+}
+
+/// Runs `f`, collecting what it writes through this module on the current thread instead of
+/// printing it. Output from other threads, e.g. a command's workers, is printed as usual.
+pub fn capture<R>(f: impl FnOnce() -> R) -> (R, CapturedOutput) {
+    CAPTURES.with(|captures| captures.borrow_mut().push(CapturedOutput::default()));
+    let frame = CaptureFrame;
+    let result = f();
+    (result, frame.finish())
+}
+
+pub fn is_capturing() -> bool {
+    CAPTURES.with(|captures| !captures.borrow().is_empty())
+}
+
+/// Appends `data` to the innermost capture, returning whether there was one.
+fn capture_write(data: &[u8], stderr: bool) -> bool {
+    CAPTURES.with(|captures| match captures.borrow_mut().last_mut() {
+        Some(captured) if stderr => {
+            captured.stderr.extend_from_slice(data);
+            true
+        }
+        Some(captured) => {
+            captured.stdout.extend_from_slice(data);
+            true
+        }
+        None => false,
+    })
+}
+
+/// Runs `f` with a writer to stdout, or to the capture when there is one.
+fn with_stdout<R>(f: impl FnOnce(&mut dyn Write) -> Result<R, CommandError>) -> Result<R, CommandError> {
+    if is_capturing() {
+        let mut buffer = Vec::new();
+        let result = f(&mut buffer);
+        capture_write(&buffer, false);
+        return result;
+    }
+
+    let mut stdout = Tee(io::stdout().lock());
+    let result = f(&mut stdout);
+    stdout.flush()?;
+    result
+}
+
+/// A tee installed with [`set_tee`], under its name.
+type InstalledTee = (&'static str, fn(&[u8]));
+
+static TEES: RwLock<Vec<InstalledTee>> = RwLock::new(Vec::new());
+
+/// Also hands everything printed outside a capture to `tee`, e.g. to record a session, or stops with `None`.
+/// Each `name`, such as `"record"`, has its own tee, so several can be installed at once.
+pub fn set_tee(name: &'static str, tee: Option<fn(&[u8])>) {
+    let mut tees = TEES.write().unwrap_or_else(|e| e.into_inner());
+    tees.retain(|(installed, _)| *installed != name);
+    tees.extend(tee.map(|tee| (name, tee)));
+}
+
+fn tee(data: &[u8]) {
+    // Not holding the lock while the tees run, so they may call `set_tee` themselves.
+    let tees = TEES.read().unwrap_or_else(|e| e.into_inner()).clone();
+    for (_, tee) in tees {
+        tee(data);
+    }
+}
+
+/// Passes what was written to the inner writer on to the tee.
+struct Tee<W: Write>(W);
+
+impl<W: Write> Write for Tee<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let written = self.0.write(data)?;
+        tee(&data[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+static TEXT_SINK: RwLock<fn(&str) -> io::Result<()>> = RwLock::new(print_text as fn(&str) -> io::Result<()>);
+
+fn print_text(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(text.as_bytes())?;
+    stdout.flush()
+}
+
+pub fn set_format(format: OutputFormat) {
+    *FORMAT.write().unwrap_or_else(|e| e.into_inner()) = format;
+}
+
+pub fn format() -> OutputFormat {
+    *FORMAT.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Replaces the function text output is written through, e.g. with a pager.
+pub fn set_text_sink(sink: fn(&str) -> io::Result<()>) {
+    *TEXT_SINK.write().unwrap_or_else(|e| e.into_inner()) = sink;
+}
+
+/// Writes plain text produced by a command through the text sink, in either format.
+pub fn print(text: &str) -> Result<(), CommandError> {
+    if capture_write(text.as_bytes(), false) {
+        return Ok(());
+    }
+    let sink = *TEXT_SINK.read().unwrap_or_else(|e| e.into_inner());
+    sink(text)?;
+    tee(text.as_bytes());
+    Ok(())
+}
+
+/// Writes raw command data such as file contents to stdout, unchanged.
+pub fn write_data(data: &[u8]) -> Result<(), CommandError> {
+    with_stdout(|out| out.write_all(data).map_err(CommandError::from))
+}
+
+/// Writes diagnostics such as log records to stderr, or to the capture when there is one.
+pub fn print_error(text: &str) -> Result<(), CommandError> {
+    if capture_write(text.as_bytes(), true) {
+        return Ok(());
+    }
+    let mut stderr = io::stderr().lock();
+    stderr.write_all(text.as_bytes())?;
+    stderr.flush()?;
+    tee(text.as_bytes());
+    Ok(())
+}
+
+/// Runs `f` with stdout locked behind a buffer that is flushed once at the end, for commands
+/// writing data in many small pieces.
+pub fn with_data_writer<R>(f: impl FnOnce(&mut dyn Write) -> Result<R, CommandError>) -> Result<R, CommandError> {
+    if is_capturing() {
+        return with_stdout(f);
+    }
+
+    let mut writer = io::BufWriter::with_capacity(64 * 1024, Tee(io::stdout().lock()));
+    let result = f(&mut writer);
+    let flushed = writer.flush();
+
+    let value = result?;
+    flushed?;
+    Ok(value)
+}
+
+/// Structured result of a command, with a human-readable rendering for text mode.
+pub trait CommandOutput: Serialize {
+    fn to_text(&self) -> String;
+}
+
+/// Writes `output` in the current [`OutputFormat`].
+pub fn emit<T: CommandOutput + ?Sized>(output: &T) -> Result<(), CommandError> {
+    match format() {
+        OutputFormat::Text => print(&output.to_text()),
+        OutputFormat::Json => {
+            let value = serde_json::to_value(output)
+                .map_err(|e| CommandError::CommandFailed(format!("Could not serialize output: {}", e)))?;
+
+            with_stdout(|stdout| {
+                match value {
+                    serde_json::Value::Array(items) => {
+                        for item in items {
+                            writeln!(stdout, "{}", item)?;
+                        }
+                    }
+                    value => writeln!(stdout, "{}", value)?,
+                }
+                Ok(())
+            })
+        }
+    }
+}
+
+/// Switches a single invocation of a builtin declared with `#[command(json)]` to JSON output when
+/// `--json` is among its options. Arguments after `--` are left alone, so `--json` can be passed on.
+pub struct JsonFlagMiddleware;
+
+impl CommandMiddleware for JsonFlagMiddleware {
+    fn handle(&self, invocation: &Invocation, next: Next) -> Result<(), CommandError> {
+        let options = invocation.args.iter().position(|arg| *arg == "--").unwrap_or(invocation.args.len());
+        let (flags, rest) = invocation.args.split_at(options);
+        let is_flag = |arg: &&OsStr| *arg == "--json";
+        if !invocation.info.is_some_and(|info| info.json) || !flags.iter().any(is_flag) {
+            return next.run(invocation);
+        }
+
+        let args: Vec<&OsStr> = flags.iter()
+            .copied()
+            .filter(|arg| !is_flag(arg))
+            .chain(rest.iter().copied())
+            .collect();
+
+        let previous = format();
+        set_format(OutputFormat::Json);
+        let result = next.run(&Invocation { args: &args, ..*invocation });
+        set_format(previous);
+
+        result
+    }
+}
@@ -1,7 +1,24 @@
+use std::{
+    ffi::{OsStr, OsString},
+    num::{
+        NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize,
+        NonZeroU128, NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize,
+    },
+};
+
 use crate::CommandError;
 
 pub trait ParseArgument<'a>: Sized {
     fn parse(s: &'a str) -> Result<Self, CommandError>;
+
+    /// Parses raw OS input. The default requires valid UTF-8; path-like types override it
+    /// so they can carry any name the OS allows.
+    fn parse_os(s: &'a OsStr) -> Result<Self, CommandError> {
+        match s.to_str() {
+            Some(s) => Self::parse(s),
+            None => Err(CommandError::InvalidArguments(format!("Argument '{}' is not valid UTF-8", s.to_string_lossy()))),
+        }
+    }
 }
 
 impl<'a> ParseArgument<'a> for &'a str {
@@ -37,6 +54,41 @@ impl<'a> ParseArgument<'a> for bool {
     }
 }
 
+/// Splits a `0x`, `0o` or `0b` prefix off `s`, returning the radix and the remaining digits.
+fn split_radix(s: &str) -> (u32, &str) {
+    let radix = match s.get(..2) {
+        Some("0x" | "0X") => 16,
+        Some("0o" | "0O") => 8,
+        Some("0b" | "0B") => 2,
+        _ => return (10, s),
+    };
+    (radix, &s[2..])
+}
+
+macro_rules! impl_parse_integer {
+    ($($t:ty),*) => {
+        $(
+            impl<'a> ParseArgument<'a> for $t {
+                /// Accepts decimal as well as `0x1F`, `0o755` and `0b1010`, optionally signed.
+                fn parse(s: &str) -> Result<Self, CommandError> {
+                    let invalid = || CommandError::CommandFailed(format!("Invalid {}: '{}'", stringify!($t), s));
+
+                    let (sign, unsigned) = match s.strip_prefix('-') {
+                        Some(rest) => ("-", rest),
+                        None => ("", s.strip_prefix('+').unwrap_or(s)),
+                    };
+
+                    match split_radix(unsigned) {
+                        (10, _) => s.parse().map_err(|_| invalid()),
+                        (_, digits) if digits.starts_with(['+', '-']) => Err(invalid()),
+                        (radix, digits) => <$t>::from_str_radix(&format!("{}{}", sign, digits), radix).map_err(|_| invalid()),
+                    }
+                }
+            }
+        )*
+    };
+}
+
 macro_rules! impl_parse_number {
     ($($t:ty),*) => {
         $(
@@ -49,13 +101,35 @@ macro_rules! impl_parse_number {
     };
 }
 
-impl_parse_number!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+macro_rules! impl_parse_non_zero {
+    ($($t:ty => $inner:ty),*) => {
+        $(
+            impl<'a> ParseArgument<'a> for $t {
+                fn parse(s: &str) -> Result<Self, CommandError> {
+                    <$t>::new(<$inner as ParseArgument>::parse(s)?)
+                        .ok_or_else(|| CommandError::CommandFailed(format!("Invalid {}: '{}' must not be zero", stringify!($t), s)))
+                }
+            }
+        )*
+    };
+}
+
+impl_parse_integer!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
 impl_parse_number!(f32, f64);
 
+impl_parse_non_zero!(
+    NonZeroI8 => i8, NonZeroI16 => i16, NonZeroI32 => i32, NonZeroI64 => i64, NonZeroI128 => i128, NonZeroIsize => isize,
+    NonZeroU8 => u8, NonZeroU16 => u16, NonZeroU32 => u32, NonZeroU64 => u64, NonZeroU128 => u128, NonZeroUsize => usize
+);
+
 impl<'a, T: ParseArgument<'a>> ParseArgument<'a> for Option<T> {
     fn parse(s: &'a str) -> Result<Self, CommandError> {
         Ok(Some(T::parse(s)?))
     }
+
+    fn parse_os(s: &'a OsStr) -> Result<Self, CommandError> {
+        Ok(Some(T::parse_os(s)?))
+    }
 }
 
 impl<'a, T: ParseArgument<'a>> ParseArgument<'a> for Vec<T> {
@@ -65,10 +139,43 @@ impl<'a, T: ParseArgument<'a>> ParseArgument<'a> for Vec<T> {
     }
 }
 
+/// Accepts plain seconds (`30`) or unit-suffixed segments such as `500ms`, `1.5s`, `5m` and `1h30m`.
 impl<'a> ParseArgument<'a> for std::time::Duration {
     fn parse(s: &str) -> Result<Self, CommandError> {
-        let secs: u64 = s.parse().map_err(|_| CommandError::CommandFailed(format!("Invalid duration: '{}'", s)))?;
-        Ok(std::time::Duration::from_secs(secs))
+        let invalid = || CommandError::CommandFailed(format!("Invalid duration: '{}'", s));
+        if s.is_empty() {
+            return Err(invalid());
+        }
+
+        if let Ok(secs) = s.parse::<u64>() {
+            return Ok(std::time::Duration::from_secs(secs));
+        }
+
+        let mut total = 0f64;
+        let mut rest = s;
+        while !rest.is_empty() {
+            let number_len = rest.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(rest.len());
+            let (number, tail) = rest.split_at(number_len);
+            let unit_len = tail.find(|c: char| c.is_ascii_digit() || c == '.').unwrap_or(tail.len());
+            let (unit, tail) = tail.split_at(unit_len);
+
+            let value: f64 = number.parse().map_err(|_| invalid())?;
+            let scale = match unit {
+                "ns" => 1e-9,
+                "us" | "µs" => 1e-6,
+                "ms" => 1e-3,
+                "s" => 1.0,
+                "m" => 60.0,
+                "h" => 3600.0,
+                "d" => 86400.0,
+                _ => return Err(invalid()),
+            };
+
+            total += value * scale;
+            rest = tail;
+        }
+
+        std::time::Duration::try_from_secs_f64(total).map_err(|_| invalid())
     }
 }
 
@@ -76,12 +183,40 @@ impl<'a> ParseArgument<'a> for std::path::PathBuf {
     fn parse(s: &str) -> Result<Self, CommandError> {
         Ok(std::path::PathBuf::from(s))
     }
+
+    fn parse_os(s: &OsStr) -> Result<Self, CommandError> {
+        Ok(std::path::PathBuf::from(s))
+    }
 }
 
 impl<'a> ParseArgument<'a> for &'a std::path::Path {
     fn parse(s: &'a str) -> Result<Self, CommandError> {
         Ok(std::path::Path::new(s))
     }
+
+    fn parse_os(s: &'a OsStr) -> Result<Self, CommandError> {
+        Ok(std::path::Path::new(s))
+    }
+}
+
+impl<'a> ParseArgument<'a> for OsString {
+    fn parse(s: &str) -> Result<Self, CommandError> {
+        Ok(OsString::from(s))
+    }
+
+    fn parse_os(s: &OsStr) -> Result<Self, CommandError> {
+        Ok(s.to_os_string())
+    }
+}
+
+impl<'a> ParseArgument<'a> for &'a OsStr {
+    fn parse(s: &'a str) -> Result<Self, CommandError> {
+        Ok(OsStr::new(s))
+    }
+
+    fn parse_os(s: &'a OsStr) -> Result<Self, CommandError> {
+        Ok(s)
+    }
 }
 
 impl<'a> ParseArgument<'a> for std::net::IpAddr {
@@ -94,4 +229,29 @@ impl<'a> ParseArgument<'a> for std::net::SocketAddr {
     fn parse(s: &str) -> Result<Self, CommandError> {
         s.parse().map_err(|_| CommandError::CommandFailed(format!("Invalid SocketAddr: '{}'", s)))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(feature = "regex")]
+impl<'a> ParseArgument<'a> for regex::Regex {
+    fn parse(s: &str) -> Result<Self, CommandError> {
+        regex::Regex::new(s).map_err(|e| {
+            let detail = match regex_syntax::Parser::new().parse(s) {
+                Err(regex_syntax::Error::Parse(e)) => Some((e.span().start.column, e.kind().to_string())),
+                Err(regex_syntax::Error::Translate(e)) => Some((e.span().start.column, e.kind().to_string())),
+                _ => None,
+            };
+
+            match detail {
+                Some((column, kind)) => CommandError::InvalidArguments(format!("Invalid regex '{}' at position {}: {}", s, column, kind)),
+                None => CommandError::InvalidArguments(format!("Invalid regex '{}': {}", s, e)),
+            }
+        })
+    }
+}
+
+#[cfg(feature = "url")]
+impl<'a> ParseArgument<'a> for url::Url {
+    fn parse(s: &str) -> Result<Self, CommandError> {
+        url::Url::parse(s).map_err(|e| CommandError::InvalidArguments(format!("Invalid URL '{}': {}", s, e)))
+    }
+}
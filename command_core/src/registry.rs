@@ -1,32 +1,332 @@
+use std::{any::Any, cell::RefCell, collections::{BTreeMap, HashMap, HashSet}, ffi::OsStr, panic::{self, AssertUnwindSafe}, sync::{Arc, OnceLock, RwLock}};
+
 use linkme::distributed_slice;
-use crate::{command_info::CommandInfo, CommandError};
+use crate::{cancellation::CancellationToken, command_info::CommandInfo, executables::find_executable, middleware::{CommandMiddleware, Invocation, Next}, output::{self, CapturedOutput}, CommandError};
 
 #[distributed_slice]
 pub static COMMANDS: [&'static CommandInfo] = [..];
 
+static SNAPSHOT: OnceLock<Snapshot> = OnceLock::new();
+
+/// What a [`Shell`](crate::Shell) limited to some builtins lets run: those builtins, and external
+/// programs only if it runs them at all.
+#[derive(Clone, Debug)]
+pub struct Allowed {
+    pub commands: Arc<HashSet<String>>,
+    pub external: bool,
+}
+
+impl Allowed {
+    /// What both `self` and `other` allow.
+    fn intersect(&self, other: &Allowed) -> Allowed {
+        Allowed {
+            commands: Arc::new(self.commands.intersection(&other.commands).cloned().collect()),
+            external: self.external && other.external,
+        }
+    }
+}
+
+thread_local! {
+    /// The limit in effect on this thread, so builtins running others, such as `bench`, keep to it.
+    static ALLOWED: RefCell<Option<Allowed>> = const { RefCell::new(None) };
+}
+
+/// Puts the previous limit back when dropped, also when the command panics.
+struct RestoreAllowed(Option<Allowed>);
+
+impl Drop for RestoreAllowed {
+    fn drop(&mut self) {
+        ALLOWED.with(|allowed| *allowed.borrow_mut() = self.0.take());
+    }
+}
+
+/// The lookup table and the collisions found while building it.
+struct Snapshot {
+    index: HashMap<&'static str, &'static CommandInfo>,
+    collisions: Vec<Collision>,
+}
+
+/// Two registrations claiming the same name or alias. The one registered first keeps it.
+#[derive(Clone, Copy, Debug)]
+pub struct Collision {
+    pub key: &'static str,
+    pub kept: &'static CommandInfo,
+    pub shadowed: &'static CommandInfo,
+}
+
+impl Collision {
+    /// `name` or `alias`, for how `info` uses the key.
+    pub fn role_of(&self, info: &CommandInfo) -> &'static str {
+        if info.name == self.key { "name" } else { "alias" }
+    }
+}
+
+static LOOKUP: RwLock<LookupOptions> = RwLock::new(LookupOptions {
+    case_insensitive: false,
+    prefix_abbreviation: false,
+});
+
+static OVERRIDES: RwLock<BTreeMap<&'static str, CommandOverride>> = RwLock::new(BTreeMap::new());
+
+static PRECEDENCE: RwLock<Precedence> = RwLock::new(Precedence::Builtin);
+
+static MIDDLEWARE: RwLock<Vec<Box<dyn CommandMiddleware>>> = RwLock::new(Vec::new());
+
+/// How command names typed by the user are matched against registered names and aliases.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LookupOptions {
+    pub case_insensitive: bool,
+    /// Accept any unambiguous prefix of a name or alias, e.g. `his` for `history`.
+    pub prefix_abbreviation: bool,
+}
+
+/// Whether a builtin or an executable of the same name on `PATH` runs when both exist.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Precedence {
+    #[default]
+    Builtin,
+    External,
+}
+
+/// Per-builtin override, consulted before the registered commands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommandOverride {
+    /// Never run the builtin; the name falls through to external executables.
+    Disabled,
+    /// Always run the builtin, regardless of [`Precedence`].
+    Enabled,
+}
+
+/// The text a panic was raised with, for the common `panic!` payloads.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload.downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic payload".to_string())
+}
+
 pub struct CommandRegistry;
 
 impl CommandRegistry {
-    pub fn find(name: &str) -> Option<&'static CommandInfo> {
-        COMMANDS.iter()
-            .find_map(|&info| {
-                if info.name == name || info.aliases.iter().any(|a| a == &name) {
-                    Some(info)
-                } else {
-                    None
+    fn snapshot() -> &'static Snapshot {
+        SNAPSHOT.get_or_init(|| {
+            let mut index: HashMap<&'static str, &'static CommandInfo> = HashMap::with_capacity(COMMANDS.len());
+            let mut collisions = Vec::new();
+            for &info in COMMANDS.iter() {
+                for &key in std::iter::once(&info.name).chain(info.aliases.iter()) {
+                    match index.get(key) {
+                        Some(&kept) => collisions.push(Collision { key, kept, shadowed: info }),
+                        None => _ = index.insert(key, info),
+                    }
                 }
-            })
+            }
+            Snapshot { index, collisions }
+        })
+    }
+
+    /// Name and alias lookup table, built on first use from the registered commands.
+    /// Earlier registrations win when two commands claim the same name.
+    pub fn index() -> &'static HashMap<&'static str, &'static CommandInfo> {
+        &Self::snapshot().index
+    }
+
+    /// Names and aliases claimed more than once, including a command listing the same alias twice.
+    pub fn collisions() -> &'static [Collision] {
+        &Self::snapshot().collisions
+    }
+
+    pub fn set_lookup_options(options: LookupOptions) {
+        *LOOKUP.write().unwrap_or_else(|e| e.into_inner()) = options;
+    }
+
+    pub fn lookup_options() -> LookupOptions {
+        *LOOKUP.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn find(name: &str) -> Option<&'static CommandInfo> {
+        Self::resolve(name).ok().flatten()
+    }
+
+    pub fn set_precedence(precedence: Precedence) {
+        *PRECEDENCE.write().unwrap_or_else(|e| e.into_inner()) = precedence;
+    }
+
+    pub fn precedence() -> Precedence {
+        *PRECEDENCE.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Sets or clears (`None`) the override for the builtin called, or aliased, `name`.
+    pub fn set_override(name: &str, value: Option<CommandOverride>) -> Result<&'static CommandInfo, CommandError> {
+        let info = Self::index()
+            .get(name)
+            .copied()
+            .ok_or_else(|| CommandError::not_found(name))?;
+
+        let mut overrides = OVERRIDES.write().unwrap_or_else(|e| e.into_inner());
+        match value {
+            Some(value) => overrides.insert(info.name, value),
+            None => overrides.remove(info.name),
+        };
+
+        Ok(info)
+    }
+
+    pub fn overrides() -> Vec<(&'static str, CommandOverride)> {
+        OVERRIDES.read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(&name, &value)| (name, value))
+            .collect()
+    }
+
+    /// Whether `info`, typed as `name`, should run as a builtin given the overrides and precedence.
+    fn is_active(info: &CommandInfo, name: &str) -> bool {
+        let overridden = OVERRIDES.read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(info.name)
+            .copied();
+
+        match overridden {
+            Some(CommandOverride::Disabled) => false,
+            Some(CommandOverride::Enabled) => true,
+            None => Self::precedence() == Precedence::Builtin || find_executable(name).is_none(),
+        }
+    }
+
+    /// Resolves `name` using the current [`LookupOptions`], overrides and [`Precedence`].
+    /// Returns `Ok(None)` when no active builtin matches and `CommandError::AmbiguousCommand`
+    /// when a prefix matches more than one command.
+    pub fn resolve(name: &str) -> Result<Option<&'static CommandInfo>, CommandError> {
+        Ok(Self::resolve_registered(name)?.filter(|info| Self::is_active(info, name)))
+    }
+
+    fn resolve_registered(name: &str) -> Result<Option<&'static CommandInfo>, CommandError> {
+        let index = Self::index();
+        if let Some(&info) = index.get(name) {
+            return Ok(Some(info));
+        }
+
+        let options = Self::lookup_options();
+        let matches_exactly = |key: &str| options.case_insensitive && key.eq_ignore_ascii_case(name);
+        if let Some((_, &info)) = index.iter().find(|(key, _)| matches_exactly(key)) {
+            return Ok(Some(info));
+        }
+
+        if !options.prefix_abbreviation || name.is_empty() {
+            return Ok(None);
+        }
+
+        let is_prefix = |key: &str| match key.get(..name.len()) {
+            Some(head) if options.case_insensitive => head.eq_ignore_ascii_case(name),
+            Some(head) => head == name,
+            None => false,
+        };
+
+        let mut candidates: Vec<&'static CommandInfo> = index.iter()
+            .filter(|(key, _)| is_prefix(key))
+            .map(|(_, &info)| info)
+            .collect();
+        candidates.sort_by_key(|info| info.name);
+        candidates.dedup_by_key(|info| info.name);
+
+        match candidates.as_slice() {
+            [] => Ok(None),
+            [info] => Ok(Some(*info)),
+            _ => Err(CommandError::AmbiguousCommand(
+                name.to_string(),
+                candidates.iter().map(|info| info.name.to_string()).collect(),
+            )),
+        }
+    }
+
+    /// The limit on what may run on this thread, see [`with_allowed`](Self::with_allowed).
+    pub fn allowed() -> Option<Allowed> {
+        ALLOWED.with(|allowed| allowed.borrow().clone())
+    }
+
+    /// Runs `f` allowing only what `allowed` does on this thread, on top of any limit already in
+    /// effect. Everything executed meanwhile is checked, also commands a builtin runs itself.
+    pub fn with_allowed<R>(allowed: Option<Allowed>, f: impl FnOnce() -> R) -> R {
+        let Some(allowed) = allowed else {
+            return f();
+        };
+        let previous = ALLOWED.with(|current| {
+            let mut current = current.borrow_mut();
+            let limited = match current.as_ref() {
+                Some(outer) => outer.intersect(&allowed),
+                None => allowed,
+            };
+            current.replace(limited)
+        });
+        let _restore = RestoreAllowed(previous);
+        f()
+    }
+
+    /// Whether the limit on this thread lets `info`, or an external program for `None`, run.
+    fn is_allowed(info: Option<&CommandInfo>) -> bool {
+        ALLOWED.with(|allowed| match (allowed.borrow().as_ref(), info) {
+            (None, _) => true,
+            (Some(allowed), Some(info)) => allowed.commands.contains(info.name),
+            (Some(allowed), None) => allowed.external,
+        })
+    }
+
+    /// Appends a middleware to the chain. Middleware runs in the order it was added.
+    pub fn add_middleware(middleware: Box<dyn CommandMiddleware>) {
+        MIDDLEWARE.write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(middleware);
     }
 
     pub fn execute_command(name: &str, args: &[&str]) -> Result<(), CommandError> {
-        match CommandRegistry::find(name) {
-            Some(info) => info.handler.call(&args),
-            None => Err(CommandError::CommandNotFound(name.to_string()))
+        let args: Vec<&OsStr> = args.iter().map(OsStr::new).collect();
+        Self::execute_or_else(name, &args, |name, _| Err(CommandError::not_found(name)))
+    }
+
+    /// Executes a builtin, falling back to `external` when no builtin matches `name`.
+    /// Both paths go through the middleware chain and run under a fresh [`CancellationToken`].
+    pub fn execute_or_else<F>(name: &str, args: &[&OsStr], external: F) -> Result<(), CommandError>
+    where
+        F: Fn(&str, &[&OsStr]) -> Result<(), CommandError>,
+    {
+        let invocation = Invocation {
+            name,
+            args,
+            info: CommandRegistry::resolve(name)?,
+        };
+        if !Self::is_allowed(invocation.info) {
+            return Err(CommandError::not_found(name));
         }
+
+        let exec: &dyn Fn(&Invocation) -> Result<(), CommandError> = &|invocation| match invocation.info {
+            Some(info) => panic::catch_unwind(AssertUnwindSafe(|| info.handler.call_os(invocation.args)))
+                .unwrap_or_else(|payload| Err(CommandError::Panicked {
+                    command: info.name.to_string(),
+                    message: panic_message(payload.as_ref()),
+                })),
+            None => external(invocation.name, invocation.args),
+        };
+
+        let chain = MIDDLEWARE.read()
+            .unwrap_or_else(|e| e.into_inner());
+
+        let previous = CancellationToken::replace_current(Some(CancellationToken::new()));
+        let result = Next::new(chain.as_slice(), exec).run(&invocation);
+        CancellationToken::replace_current(previous);
+
+        result
+    }
+
+    /// Executes a builtin like [`execute_command`](Self::execute_command), returning what it wrote
+    /// instead of printing it, e.g. for command substitution or tests.
+    pub fn execute_captured(name: &str, args: &[&OsStr]) -> Result<CapturedOutput, CommandError> {
+        let (result, captured) = output::capture(|| {
+            Self::execute_or_else(name, args, |name, _| Err(CommandError::not_found(name)))
+        });
+        result.map(|()| captured)
     }
 
     pub fn all() -> impl Iterator<Item = &'static CommandInfo> {
-        COMMANDS.iter()
-            .map(|&info| info)
+        COMMANDS.iter().copied()
     }
-}
\ No newline at end of file
+}
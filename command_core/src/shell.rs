@@ -0,0 +1,502 @@
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    ffi::OsStr,
+    sync::Arc,
+    io::{self, BufRead, Write},
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::{output, Allowed, CancellationToken, CommandError, CommandRegistry};
+
+/// Result of the previously executed command, e.g. for the next prompt.
+#[derive(Clone, Copy, Debug)]
+pub struct LastCommand {
+    pub duration: Duration,
+    pub exit_code: i32,
+}
+
+/// A word of the input line with its byte range in the line. It borrows from the line unless it had to be converted.
+pub struct Token<'a> {
+    pub text: Cow<'a, OsStr>,
+    pub span: Range<usize>,
+}
+
+/// Splits a raw input line on ASCII whitespace without requiring it to be valid UTF-8.
+pub fn tokenize(line: &[u8]) -> impl Iterator<Item = Token<'_>> {
+    let mut at = 0;
+    std::iter::from_fn(move || {
+        let start = at + line[at..].iter().position(|byte| !byte.is_ascii_whitespace())?;
+        let end = line[start..].iter().position(u8::is_ascii_whitespace).map_or(line.len(), |len| start + len);
+        at = end;
+        Some(Token { text: bytes_to_os_str(&line[start..end]), span: start..end })
+    })
+}
+
+/// A command line split into the command name and its arguments.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParsedLine {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+/// Splits `line` the way the shell does before running it, or `None` for a blank line. Never
+/// panics, whatever the input, so it can be fuzzed.
+pub fn parse_line(line: &str) -> Option<ParsedLine> {
+    let mut words = tokenize(line.as_bytes()).map(|token| token.text.to_string_lossy().into_owned());
+    let name = words.next()?;
+    Some(ParsedLine { name, args: words.collect() })
+}
+
+/// A `<<WORD` or `<<-WORD` operator: the lines after the command, up to one that is just `WORD`,
+/// are its standard input. With `<<-`, leading tabs are stripped from them and from that line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Heredoc {
+    pub delimiter: String,
+    pub strip_tabs: bool,
+    /// Bytes of the command line the operator and the delimiter take up.
+    pub span: Range<usize>,
+}
+
+impl Heredoc {
+    fn strip<'a>(&self, line: &'a str) -> &'a str {
+        if self.strip_tabs { line.trim_start_matches('\t') } else { line }
+    }
+
+    /// Whether `line` ends the document.
+    pub fn is_end(&self, line: &str) -> bool {
+        self.strip(line.trim_end_matches(['\r', '\n'])) == self.delimiter
+    }
+
+    /// The document at the start of `rest`, the text after the command line. Without a delimiter
+    /// line it runs to the end.
+    pub fn body(&self, rest: &str) -> String {
+        let mut body = String::new();
+        for line in rest.lines().take_while(|line| !self.is_end(line)) {
+            body.push_str(self.strip(line));
+            body.push('\n');
+        }
+        body
+    }
+}
+
+/// The here-document operator in a command line, if it has one. Quotes around the delimiter are
+/// dropped, as the document is taken literally either way.
+pub fn find_heredoc(line: &[u8]) -> Option<Heredoc> {
+    let mut tokens = tokenize(line);
+    while let Some(token) = tokens.next() {
+        let text = token.text.to_string_lossy();
+        let Some(rest) = text.strip_prefix("<<") else {
+            continue;
+        };
+        let (strip_tabs, rest) = match rest.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let (word, end) = if rest.is_empty() {
+            let next = tokens.next()?;
+            (next.text.to_string_lossy().into_owned(), next.span.end)
+        } else {
+            (rest.to_string(), token.span.end)
+        };
+
+        let delimiter = ['\'', '"'].iter()
+            .find_map(|quote| word.strip_prefix(*quote).and_then(|word| word.strip_suffix(*quote)))
+            .unwrap_or(&word)
+            .to_string();
+        return (!delimiter.is_empty()).then_some(Heredoc { delimiter, strip_tabs, span: token.span.start..end });
+    }
+    None
+}
+
+#[cfg(unix)]
+fn bytes_to_os_str(bytes: &[u8]) -> Cow<'_, OsStr> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn bytes_to_os_str(bytes: &[u8]) -> Cow<'_, OsStr> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Cow::Borrowed(OsStr::new(text)),
+        Err(_) => Cow::Owned(std::ffi::OsString::from(String::from_utf8_lossy(bytes).into_owned())),
+    }
+}
+
+/// How one line of a script went, see [`Shell::run_script`].
+#[derive(Clone, Debug, Serialize)]
+pub struct CommandReport {
+    /// 1-based line number in the script.
+    pub line: usize,
+    pub command: String,
+    pub exit_code: i32,
+    pub error: Option<String>,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration_ms: f64,
+}
+
+/// Result of [`Shell::run_script`], for tooling to inspect or write out as JSON.
+#[derive(Clone, Debug, Serialize)]
+pub struct ScriptReport {
+    pub commands: Vec<CommandReport>,
+    pub duration_ms: f64,
+}
+
+impl ScriptReport {
+    pub fn succeeded(&self) -> bool {
+        self.commands.iter().all(|command| command.exit_code == 0)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &CommandReport> {
+        self.commands.iter().filter(|command| command.exit_code != 0)
+    }
+}
+
+type External = Box<dyn Fn(&str, &[&OsStr]) -> Result<(), CommandError>>;
+type Expander = Box<dyn FnMut(&str) -> Result<Option<String>, CommandError>>;
+type NotFoundHandler = Box<dyn Fn(&str, &[&OsStr]) -> Option<Result<(), CommandError>>>;
+type Prompt = Box<dyn FnMut(Option<&LastCommand>) -> String>;
+type InputHook = Box<dyn FnMut(&str, &str)>;
+type BeforeCommand = Box<dyn FnMut(&str)>;
+type AfterCommand = Box<dyn FnMut(&str, &LastCommand)>;
+type ErrorHandler = Box<dyn FnMut(&CommandError)>;
+type LineReader = Box<dyn FnMut(&str) -> io::Result<Option<String>>>;
+
+/// A read-eval-print loop over the registered commands, for embedding the command shell in other
+/// applications such as a game or admin console. Built with [`Shell::builder`].
+pub struct Shell {
+    prompt: Prompt,
+    read_line: Option<LineReader>,
+    on_input: Option<InputHook>,
+    expanders: Vec<Expander>,
+    before_command: Option<BeforeCommand>,
+    after_command: Option<AfterCommand>,
+    on_error: ErrorHandler,
+    external: Option<External>,
+    not_found: Vec<NotFoundHandler>,
+    commands: Option<Arc<HashSet<String>>>,
+    last_command: Option<LastCommand>,
+    // Reused for every line, so reading one only allocates when it is longer than any before.
+    input: Vec<u8>,
+}
+
+impl Shell {
+    pub fn builder() -> ShellBuilder {
+        ShellBuilder::default()
+    }
+
+    /// Result of the last command that ran, if any.
+    pub fn last_command(&self) -> Option<&LastCommand> {
+        self.last_command.as_ref()
+    }
+
+    /// Reads, runs and reports lines from standard input until it ends, as the foreground thread
+    /// Ctrl-C interrupts.
+    pub fn run(&mut self) {
+        CancellationToken::foreground(|| self.read_loop());
+    }
+
+    fn read_loop(&mut self) {
+        loop {
+            let prompt_text = (self.prompt)(self.last_command.as_ref());
+            let mut input = std::mem::take(&mut self.input);
+            input.clear();
+            match self.read_input(&prompt_text, &mut input) {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(_) => {
+                    self.input = input;
+                    continue;
+                }
+            }
+
+            let result = match self.expand(String::from_utf8_lossy(&input).trim_end()) {
+                Ok(Some(expanded)) => {
+                    // Shown, as what runs is no longer what was typed.
+                    if let Err(e) = output::print(&format!("{}\n", expanded)) {
+                        (self.on_error)(&e);
+                    }
+                    if let Some(on_input) = self.on_input.as_mut() {
+                        on_input(&prompt_text, &expanded);
+                    }
+                    let mut line = expanded.into_bytes();
+                    line.push(b'\n');
+                    read_heredoc(&mut line);
+                    self.eval_bytes(&line)
+                }
+                Ok(None) => {
+                    if let Some(on_input) = self.on_input.as_mut() {
+                        on_input(&prompt_text, String::from_utf8_lossy(&input).trim_end());
+                    }
+                    read_heredoc(&mut input);
+                    self.eval_bytes(&input)
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                (self.on_error)(&e);
+            }
+            self.input = input;
+        }
+    }
+
+    /// Shows `prompt` and reads a line into `input`, returning `false` at the end of the input.
+    fn read_input(&mut self, prompt: &str, input: &mut Vec<u8>) -> io::Result<bool> {
+        if let Some(read_line) = self.read_line.as_mut() {
+            let Some(line) = read_line(prompt)? else {
+                return Ok(false);
+            };
+            input.extend_from_slice(line.as_bytes());
+            input.push(b'\n');
+            return Ok(true);
+        }
+
+        print!("{}", prompt);
+        io::stdout().flush()?;
+        Ok(io::stdin().lock().read_until(b'\n', input)? > 0)
+    }
+
+    /// Passes a typed line through the expanders in order, returning it if any of them changed it.
+    fn expand(&mut self, line: &str) -> Result<Option<String>, CommandError> {
+        let mut expanded: Option<String> = None;
+        for expander in &mut self.expanders {
+            if let Some(line) = expander(expanded.as_deref().unwrap_or(line))? {
+                expanded = Some(line);
+            }
+        }
+        Ok(expanded)
+    }
+
+    /// Runs one line as if it had been typed, returning what the command returned. Blank lines do nothing.
+    pub fn eval(&mut self, line: &str) -> Result<(), CommandError> {
+        self.eval_bytes(line.as_bytes())
+    }
+
+    /// Runs every line of `source` without a terminal, capturing what each command wrote. A failed
+    /// command doesn't stop the script; blank lines and lines starting with `#` are skipped.
+    pub fn run_script(&mut self, source: &str) -> ScriptReport {
+        let started = Instant::now();
+        let mut commands = Vec::new();
+        let mut lines = source.lines().enumerate();
+        while let Some((number, line)) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            // A here-document's lines belong to its command, untrimmed.
+            let mut text = line.to_string();
+            if let Some(heredoc) = find_heredoc(line.as_bytes()) {
+                for (_, body_line) in lines.by_ref() {
+                    text.push('\n');
+                    text.push_str(body_line);
+                    if heredoc.is_end(body_line) {
+                        break;
+                    }
+                }
+            }
+
+            let command_started = Instant::now();
+            let (result, captured) = output::capture(|| self.eval(&text));
+            commands.push(CommandReport {
+                line: number + 1,
+                command: line.to_string(),
+                exit_code: result.as_ref().map_or_else(CommandError::exit_code, |_| 0),
+                error: result.err().map(|e| e.to_string()),
+                stdout: captured.stdout_text().into_owned(),
+                stderr: captured.stderr_text().into_owned(),
+                duration_ms: command_started.elapsed().as_secs_f64() * 1000.0,
+            });
+        }
+
+        ScriptReport { commands, duration_ms: started.elapsed().as_secs_f64() * 1000.0 }
+    }
+
+    /// Runs a command line, and with a here-document the lines after it up to its delimiter are the
+    /// command's standard input.
+    fn eval_bytes(&mut self, input: &[u8]) -> Result<(), CommandError> {
+        let first_line = input.iter().position(|&byte| byte == b'\n').unwrap_or(input.len());
+        let Some(heredoc) = find_heredoc(&input[..first_line]) else {
+            return self.eval_command(input);
+        };
+
+        let mut command = input[..first_line].to_vec();
+        command.drain(heredoc.span.clone());
+        let body = heredoc.body(&String::from_utf8_lossy(input.get(first_line + 1..).unwrap_or_default()));
+        crate::input::with_stdin(body.into_bytes(), || self.eval_command(&command))
+    }
+
+    fn eval_command(&mut self, input: &[u8]) -> Result<(), CommandError> {
+        let tokens: Vec<Token> = tokenize(input).collect();
+        let [first, rest @ ..] = tokens.as_slice() else {
+            return Ok(());
+        };
+
+        let name = first.text.to_string_lossy();
+        let args: Vec<&OsStr> = rest.iter().map(|token| token.text.as_ref()).collect();
+        let line = String::from_utf8_lossy(&input[first.span.start..rest.last().unwrap_or(first).span.end]);
+
+        if let Some(before_command) = self.before_command.as_mut() {
+            before_command(&name);
+        }
+        let started = Instant::now();
+        let result = self.execute(&name, &args);
+        let finished = LastCommand {
+            duration: started.elapsed(),
+            exit_code: result.as_ref().map_or_else(CommandError::exit_code, |_| 0),
+        };
+        if let Some(after_command) = self.after_command.as_mut() {
+            after_command(&line, &finished);
+        }
+        self.last_command = Some(finished);
+
+        result
+    }
+
+    fn execute(&self, name: &str, args: &[&OsStr]) -> Result<(), CommandError> {
+        // Checked by the registry, so it holds for the commands a builtin such as `bench` runs too.
+        let allowed = self.commands.as_ref().map(|commands| Allowed {
+            commands: Arc::clone(commands),
+            external: self.external.is_some(),
+        });
+        let result = CommandRegistry::with_allowed(allowed, || match &self.external {
+            Some(external) => CommandRegistry::execute_or_else(name, args, external),
+            None => CommandRegistry::execute_or_else(name, args, |name, _| Err(CommandError::not_found(name))),
+        });
+        match result {
+            Err(CommandError::CommandNotFound(..)) => self.not_found.iter()
+                .find_map(|handler| handler(name, args))
+                .unwrap_or(result),
+            _ => result,
+        }
+    }
+}
+
+/// Reads the lines of a here-document the typed `line` starts, if it does, up to its delimiter and
+/// appends them. `line` ends with a newline.
+fn read_heredoc(line: &mut Vec<u8>) {
+    let first_line = line.iter().position(|&byte| byte == b'\n').unwrap_or(line.len());
+    let Some(heredoc) = find_heredoc(&line[..first_line]) else {
+        return;
+    };
+    loop {
+        print!("> ");
+        _ = io::stdout().flush();
+        let start = line.len();
+        match io::stdin().lock().read_until(b'\n', line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        if heredoc.is_end(&String::from_utf8_lossy(&line[start..])) {
+            return;
+        }
+    }
+}
+
+/// Configures a [`Shell`]. Everything is optional: by default the prompt is `$ `, all registered
+/// commands are available, nothing else runs and errors are printed to stderr.
+#[derive(Default)]
+pub struct ShellBuilder {
+    prompt: Option<Prompt>,
+    read_line: Option<LineReader>,
+    on_input: Option<InputHook>,
+    expanders: Vec<Expander>,
+    before_command: Option<BeforeCommand>,
+    after_command: Option<AfterCommand>,
+    on_error: Option<ErrorHandler>,
+    external: Option<External>,
+    not_found: Vec<NotFoundHandler>,
+    commands: Option<Arc<HashSet<String>>>,
+}
+
+impl ShellBuilder {
+    /// Renders the prompt before each line, given the result of the previous command.
+    pub fn prompt(mut self, prompt: impl FnMut(Option<&LastCommand>) -> String + 'static) -> Self {
+        self.prompt = Some(Box::new(prompt));
+        self
+    }
+
+    /// Shows the prompt and reads a line, without its newline, e.g. with a line editor; `None` ends
+    /// the input. By default the prompt is printed and a line read from standard input.
+    pub fn read_line(mut self, read_line: impl FnMut(&str) -> io::Result<Option<String>> + 'static) -> Self {
+        self.read_line = Some(Box::new(read_line));
+        self
+    }
+
+    /// Limits the builtins to these names; others are reported as not found, also when a builtin
+    /// such as `bench` or `macro play` runs them.
+    pub fn commands<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.commands = Some(Arc::new(names.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    /// Runs names that aren't builtins, e.g. as programs on `PATH`.
+    pub fn external(mut self, external: impl Fn(&str, &[&OsStr]) -> Result<(), CommandError> + 'static) -> Self {
+        self.external = Some(Box::new(external));
+        self
+    }
+
+    /// Handles a command that isn't a builtin and that the external runner couldn't find either, e.g. by
+    /// suggesting a package or running it with another tool. Handlers are asked in the order they were
+    /// added; the first to return `Some` decides the result, otherwise the not-found error stands.
+    pub fn on_not_found(mut self, handler: impl Fn(&str, &[&OsStr]) -> Option<Result<(), CommandError>> + 'static) -> Self {
+        self.not_found.push(Box::new(handler));
+        self
+    }
+
+    /// Called with the prompt and the line, as expanded, after a line was read and before it runs.
+    pub fn on_input(mut self, on_input: impl FnMut(&str, &str) + 'static) -> Self {
+        self.on_input = Some(Box::new(on_input));
+        self
+    }
+
+    /// Rewrites typed lines before they run, e.g. to expand history references, returning `None` to
+    /// keep a line as it is. Expanders run in the order they were added; a changed line is echoed.
+    pub fn expand(mut self, expander: impl FnMut(&str) -> Result<Option<String>, CommandError> + 'static) -> Self {
+        self.expanders.push(Box::new(expander));
+        self
+    }
+
+    /// Called with the command name right before it runs.
+    pub fn before_command(mut self, before_command: impl FnMut(&str) + 'static) -> Self {
+        self.before_command = Some(Box::new(before_command));
+        self
+    }
+
+    /// Called with the line and its result after a command ran.
+    pub fn after_command(mut self, after_command: impl FnMut(&str, &LastCommand) + 'static) -> Self {
+        self.after_command = Some(Box::new(after_command));
+        self
+    }
+
+    /// Reports the errors of commands run by [`Shell::run`].
+    pub fn on_error(mut self, on_error: impl FnMut(&CommandError) + 'static) -> Self {
+        self.on_error = Some(Box::new(on_error));
+        self
+    }
+
+    pub fn build(self) -> Shell {
+        Shell {
+            prompt: self.prompt.unwrap_or_else(|| Box::new(|_| "$ ".to_string())),
+            read_line: self.read_line,
+            on_input: self.on_input,
+            expanders: self.expanders,
+            before_command: self.before_command,
+            after_command: self.after_command,
+            on_error: self.on_error.unwrap_or_else(|| Box::new(|e| eprintln!("{}", e))),
+            external: self.external,
+            not_found: self.not_found,
+            commands: self.commands,
+            last_command: None,
+            input: Vec::new(),
+        }
+    }
+}
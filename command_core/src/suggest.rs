@@ -0,0 +1,63 @@
+use std::collections::BTreeSet;
+
+use crate::{executables::path_executables, CommandRegistry};
+
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Edit distance between two strings, counted in chars, where swapping two adjacent chars counts
+/// as one edit, so `gerp` is as close to `grep` as `gep` is.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut distance = (rows[i - 1][j - 1] + cost)
+                .min(rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+
+    rows[a.len()][b.len()]
+}
+
+/// Registered names, aliases and `PATH` executables closest to `name`, best match first.
+pub fn suggestions(name: &str) -> Vec<String> {
+    let mut candidates: BTreeSet<String> = CommandRegistry::all()
+        .flat_map(|info| std::iter::once(info.name).chain(info.aliases.iter().copied()))
+        .map(str::to_string)
+        .collect();
+    candidates.extend(path_executables());
+
+    closest(name, candidates)
+}
+
+/// The `candidates` within a few typos of `name`, best match first.
+pub fn closest(name: &str, candidates: impl IntoIterator<Item = String>) -> Vec<String> {
+    let threshold = (name.chars().count() / 3).max(1);
+
+    let mut scored: Vec<(usize, String)> = candidates
+        .into_iter()
+        .filter(|candidate| candidate != name)
+        .map(|candidate| (edit_distance(name, &candidate), candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .collect();
+
+    scored.sort();
+    scored.into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
@@ -0,0 +1,218 @@
+use std::fmt;
+
+use unicode_width::UnicodeWidthStr;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    Left,
+    Right,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Column {
+    align: Align,
+    max_width: Option<usize>,
+    /// SGR parameters such as `1;34`, applied to every body cell of the column.
+    color: Option<String>,
+}
+
+/// Aligned text table, e.g. for `ls -l`.
+///
+/// Cells may already contain ANSI color sequences; widths are measured on the visible text.
+#[derive(Clone, Debug)]
+pub struct Table {
+    headers: Option<Vec<String>>,
+    columns: Vec<Column>,
+    rows: Vec<Vec<String>>,
+    separator: String,
+}
+
+impl Default for Table {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Table {
+    pub fn new() -> Self {
+        Self {
+            headers: None,
+            columns: Vec::new(),
+            rows: Vec::new(),
+            separator: "  ".to_string(),
+        }
+    }
+
+    pub fn headers<I, S>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.headers = Some(headers.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn align(mut self, column: usize, align: Align) -> Self {
+        self.column_mut(column).align = align;
+        self
+    }
+
+    /// Truncates cells of `column` wider than `width`, marking them with `…`.
+    pub fn max_width(mut self, column: usize, width: usize) -> Self {
+        self.column_mut(column).max_width = Some(width);
+        self
+    }
+
+    pub fn color(mut self, column: usize, sgr: impl Into<String>) -> Self {
+        self.column_mut(column).color = Some(sgr.into());
+        self
+    }
+
+    pub fn separator(mut self, separator: impl Into<String>) -> Self {
+        self.separator = separator.into();
+        self
+    }
+
+    pub fn push_row<I, S>(&mut self, cells: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.rows.push(cells.into_iter().map(Into::into).collect());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    fn column_mut(&mut self, column: usize) -> &mut Column {
+        if self.columns.len() <= column {
+            self.columns.resize(column + 1, Column::default());
+        }
+        &mut self.columns[column]
+    }
+
+    pub fn render(&self) -> String {
+        let column_count = self.rows.iter()
+            .chain(self.headers.iter())
+            .map(Vec::len)
+            .max()
+            .unwrap_or(0);
+        let default_column = Column::default();
+        let column = |i: usize| self.columns.get(i).unwrap_or(&default_column);
+
+        let fit = |i: usize, cell: &str| -> String {
+            match column(i).max_width {
+                Some(max) if visible_width(cell) > max => truncate(cell, max),
+                _ => cell.to_string(),
+            }
+        };
+
+        let header: Option<Vec<String>> = self.headers.as_ref()
+            .map(|headers| headers.iter().enumerate().map(|(i, cell)| fit(i, cell)).collect());
+        let body: Vec<Vec<String>> = self.rows.iter()
+            .map(|row| row.iter().enumerate().map(|(i, cell)| fit(i, cell)).collect())
+            .collect();
+
+        let mut widths = vec![0; column_count];
+        for row in header.iter().chain(body.iter()) {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(visible_width(cell));
+            }
+        }
+
+        let mut output = String::new();
+        let mut write_row = |row: &[String], colored: bool| {
+            for (i, cell) in row.iter().enumerate() {
+                let last = i + 1 == row.len();
+                let padding = " ".repeat(widths[i] - visible_width(cell));
+                let text = match &column(i).color {
+                    Some(sgr) if colored => format!("\x1b[{}m{}\x1b[0m", sgr, cell),
+                    _ => cell.clone(),
+                };
+
+                match column(i).align {
+                    Align::Left if last => output.push_str(&text),
+                    Align::Left => {
+                        output.push_str(&text);
+                        output.push_str(&padding);
+                    }
+                    Align::Right => {
+                        output.push_str(&padding);
+                        output.push_str(&text);
+                    }
+                }
+
+                if !last {
+                    output.push_str(&self.separator);
+                }
+            }
+            output.push('\n');
+        };
+
+        if let Some(header) = &header {
+            write_row(header, false);
+        }
+        for row in &body {
+            write_row(row, true);
+        }
+
+        output
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render())
+    }
+}
+
+/// `s` without ANSI escape sequences.
+pub fn strip_ansi(s: &str) -> String {
+    let mut plain = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip a CSI sequence: ESC '[' parameters final-byte.
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+/// Terminal columns `s` occupies, ignoring ANSI escape sequences.
+pub fn visible_width(s: &str) -> usize {
+    if s.contains('\x1b') {
+        strip_ansi(s).width()
+    } else {
+        s.width()
+    }
+}
+
+fn truncate(s: &str, max: usize) -> String {
+    if max == 0 {
+        return String::new();
+    }
+
+    let mut truncated = String::new();
+    let mut width = 0;
+    for c in strip_ansi(s).chars() {
+        let w = unicode_width::UnicodeWidthChar::width(c).unwrap_or(0);
+        if width + w > max - 1 {
+            break;
+        }
+        width += w;
+        truncated.push(c);
+    }
+    truncated.push('…');
+    truncated
+}
@@ -0,0 +1,302 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Cursor, Read, Write},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::SystemTime,
+};
+
+/// What an entry is; links are never followed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileKind {
+    File,
+    Dir,
+    Symlink,
+}
+
+#[derive(Clone, Debug)]
+pub struct FsMetadata {
+    pub kind: FileKind,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl FsMetadata {
+    pub fn is_dir(&self) -> bool {
+        self.kind == FileKind::Dir
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.kind == FileKind::File
+    }
+}
+
+/// The filesystem operations file commands go through, so they can run against memory in tests
+/// and so a sandbox can check every path they touch in one place. `rm`, `rmdir`, `cat` and `iconv`
+/// use it; the other file commands still go to the disk directly and rely on the middleware instead.
+pub trait Fs: Send + Sync {
+    /// Paths of the entries in `path`, in no particular order.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+    fn metadata(&self, path: &Path) -> io::Result<FsMetadata>;
+    /// Removes a file, a link or an empty directory.
+    fn remove(&self, path: &Path) -> io::Result<()>;
+    fn create_dir(&self, path: &Path) -> io::Result<()>;
+    /// Creates or truncates the file at `path` for writing.
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + Send>>;
+    /// Opens the file at `path` for writing at its end, creating it if needed.
+    fn append(&self, path: &Path) -> io::Result<Box<dyn Write + Send>>;
+    /// Moves `from` to `to`, replacing a file there.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>>;
+}
+
+/// The disk, through `std::fs`.
+pub struct RealFs;
+
+impl Fs for RealFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(path)?.map(|entry| entry.map(|entry| entry.path())).collect()
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FsMetadata> {
+        let metadata = fs::symlink_metadata(path)?;
+        let kind = if metadata.is_symlink() {
+            FileKind::Symlink
+        } else if metadata.is_dir() {
+            FileKind::Dir
+        } else {
+            FileKind::File
+        };
+        Ok(FsMetadata { kind, len: metadata.len(), modified: metadata.modified().ok() })
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        if fs::symlink_metadata(path)?.is_dir() {
+            fs::remove_dir(path)
+        } else {
+            // Windows directory links are removed like directories.
+            fs::remove_file(path).or_else(|e| if cfg!(windows) { fs::remove_dir(path) } else { Err(e) })
+        }
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir(path)
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + Send>> {
+        Ok(Box::new(fs::File::create(path)?))
+    }
+
+    fn append(&self, path: &Path) -> io::Result<Box<dyn Write + Send>> {
+        Ok(Box::new(fs::OpenOptions::new().create(true).append(true).open(path)?))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(fs::File::open(path)?))
+    }
+}
+
+enum Node {
+    Dir,
+    File(Arc<Mutex<Vec<u8>>>, SystemTime),
+}
+
+/// A filesystem held in memory, starting out with an empty root. Relative paths are resolved
+/// against the root, so tests don't depend on the working directory.
+#[derive(Default)]
+pub struct MemoryFs {
+    nodes: Mutex<BTreeMap<PathBuf, Node>>,
+}
+
+/// Appends to a file of a [`MemoryFs`].
+struct MemoryFile(Arc<Mutex<Vec<u8>>>);
+
+impl Write for MemoryFile {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("'{}' does not exist", path.display()))
+}
+
+impl MemoryFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `path` from the root with `.` and `..` resolved; the root itself is the empty path.
+    fn key(path: &Path) -> PathBuf {
+        let mut key = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => key.push(name),
+                Component::ParentDir => _ = key.pop(),
+                _ => {}
+            }
+        }
+        key
+    }
+
+    /// Checks that the directory `path` would be created in exists.
+    fn check_parent(nodes: &BTreeMap<PathBuf, Node>, key: &Path) -> io::Result<()> {
+        match key.parent() {
+            None => Err(io::Error::new(io::ErrorKind::AlreadyExists, "the root always exists")),
+            Some(parent) if parent.as_os_str().is_empty() || matches!(nodes.get(parent), Some(Node::Dir)) => Ok(()),
+            Some(parent) => Err(not_found(parent)),
+        }
+    }
+
+    /// Adds a file with `contents`, creating its parent directories, e.g. to set up a test.
+    pub fn add_file(&self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) {
+        let key = Self::key(path.as_ref());
+        let mut nodes = self.nodes.lock().unwrap_or_else(|e| e.into_inner());
+        for parent in key.ancestors().skip(1).filter(|parent| !parent.as_os_str().is_empty()) {
+            nodes.entry(parent.to_path_buf()).or_insert(Node::Dir);
+        }
+        nodes.insert(key, Node::File(Arc::new(Mutex::new(contents.into())), SystemTime::now()));
+    }
+
+    /// Contents of the file at `path`, if there is one.
+    pub fn contents(&self, path: impl AsRef<Path>) -> Option<Vec<u8>> {
+        match self.nodes.lock().unwrap_or_else(|e| e.into_inner()).get(&Self::key(path.as_ref())) {
+            Some(Node::File(data, _)) => Some(data.lock().unwrap_or_else(|e| e.into_inner()).clone()),
+            _ => None,
+        }
+    }
+}
+
+impl Fs for MemoryFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let key = Self::key(path);
+        let nodes = self.nodes.lock().unwrap_or_else(|e| e.into_inner());
+        if !key.as_os_str().is_empty() && !matches!(nodes.get(&key), Some(Node::Dir)) {
+            return Err(not_found(path));
+        }
+        Ok(nodes.keys()
+            .filter(|entry| entry.parent() == Some(key.as_path()))
+            .map(|entry| path.join(entry.file_name().unwrap_or_default()))
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FsMetadata> {
+        let key = Self::key(path);
+        if key.as_os_str().is_empty() {
+            return Ok(FsMetadata { kind: FileKind::Dir, len: 0, modified: None });
+        }
+        match self.nodes.lock().unwrap_or_else(|e| e.into_inner()).get(&key) {
+            Some(Node::Dir) => Ok(FsMetadata { kind: FileKind::Dir, len: 0, modified: None }),
+            Some(Node::File(data, modified)) => Ok(FsMetadata {
+                kind: FileKind::File,
+                len: data.lock().unwrap_or_else(|e| e.into_inner()).len() as u64,
+                modified: Some(*modified),
+            }),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        let key = Self::key(path);
+        let mut nodes = self.nodes.lock().unwrap_or_else(|e| e.into_inner());
+        if nodes.keys().any(|entry| entry.parent() == Some(key.as_path())) {
+            return Err(io::Error::new(io::ErrorKind::DirectoryNotEmpty, format!("'{}' is not empty", path.display())));
+        }
+        nodes.remove(&key).map(|_| ()).ok_or_else(|| not_found(path))
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        let key = Self::key(path);
+        let mut nodes = self.nodes.lock().unwrap_or_else(|e| e.into_inner());
+        Self::check_parent(&nodes, &key)?;
+        if nodes.contains_key(&key) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("'{}' exists", path.display())));
+        }
+        nodes.insert(key, Node::Dir);
+        Ok(())
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + Send>> {
+        let key = Self::key(path);
+        let mut nodes = self.nodes.lock().unwrap_or_else(|e| e.into_inner());
+        Self::check_parent(&nodes, &key)?;
+        if matches!(nodes.get(&key), Some(Node::Dir)) {
+            return Err(io::Error::new(io::ErrorKind::IsADirectory, format!("'{}' is a directory", path.display())));
+        }
+        let data = Arc::new(Mutex::new(Vec::new()));
+        nodes.insert(key, Node::File(Arc::clone(&data), SystemTime::now()));
+        Ok(Box::new(MemoryFile(data)))
+    }
+
+    fn append(&self, path: &Path) -> io::Result<Box<dyn Write + Send>> {
+        let existing = match self.nodes.lock().unwrap_or_else(|e| e.into_inner()).get(&Self::key(path)) {
+            Some(Node::File(data, _)) => Some(Arc::clone(data)),
+            _ => None,
+        };
+        match existing {
+            Some(data) => Ok(Box::new(MemoryFile(data))),
+            None => self.create(path),
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let (from_key, to_key) = (Self::key(from), Self::key(to));
+        let mut nodes = self.nodes.lock().unwrap_or_else(|e| e.into_inner());
+        if !nodes.contains_key(&from_key) {
+            return Err(not_found(from));
+        }
+        Self::check_parent(&nodes, &to_key)?;
+        if matches!(nodes.get(&to_key), Some(Node::Dir)) {
+            return Err(io::Error::new(io::ErrorKind::IsADirectory, format!("'{}' is a directory", to.display())));
+        }
+
+        // A directory takes everything below it along.
+        let moved: Vec<PathBuf> = nodes.keys().filter(|key| key.starts_with(&from_key)).cloned().collect();
+        for key in moved {
+            if let Some(node) = nodes.remove(&key) {
+                let relative = key.strip_prefix(&from_key).unwrap_or(Path::new(""));
+                let new_key = if relative.as_os_str().is_empty() { to_key.clone() } else { to_key.join(relative) };
+                nodes.insert(new_key, node);
+            }
+        }
+        Ok(())
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        match self.nodes.lock().unwrap_or_else(|e| e.into_inner()).get(&Self::key(path)) {
+            Some(Node::File(data, _)) => Ok(Box::new(Cursor::new(data.lock().unwrap_or_else(|e| e.into_inner()).clone()))),
+            Some(Node::Dir) => Err(io::Error::new(io::ErrorKind::IsADirectory, format!("'{}' is a directory", path.display()))),
+            None => Err(not_found(path)),
+        }
+    }
+}
+
+static CURRENT: RwLock<Option<Arc<dyn Fs>>> = RwLock::new(None);
+
+/// The filesystem commands should use: the disk unless [`set`] installed another one.
+pub fn current() -> Arc<dyn Fs> {
+    CURRENT.read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| Arc::new(RealFs))
+}
+
+/// Installs `fs` for all commands, returning the one it replaced.
+pub fn set(fs: Arc<dyn Fs>) -> Option<Arc<dyn Fs>> {
+    CURRENT.write().unwrap_or_else(|e| e.into_inner()).replace(fs)
+}
+
+/// Goes back to the disk.
+pub fn reset() {
+    *CURRENT.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
@@ -14,6 +14,10 @@ struct CommandArgs {
     name: Option<String>,
     description: Option<String>,
     aliases: Vec<String>,
+    usage: Option<String>,
+    examples: Vec<String>,
+    dry_run: bool,
+    json: bool,
 }
 
 impl Parse for CommandArgs {
@@ -22,16 +26,35 @@ impl Parse for CommandArgs {
             name: None,
             description: None,
             aliases: vec![],
+            usage: None,
+            examples: vec![],
+            dry_run: false,
+            json: false,
         };
 
         while !input.is_empty() {
             let ident: Ident = input.parse()?;
+
+            // Capabilities are bare words, e.g. `dry_run` or `json`.
+            if !input.peek(Token![=]) {
+                match ident.to_string().as_str() {
+                    "dry_run" => args.dry_run = true,
+                    "json" => args.json = true,
+                    _ => return Err(syn::Error::new_spanned(ident, "unknown capability")),
+                }
+                if input.peek(Token![,]) {
+                    input.parse::<Token![,]>()?;
+                }
+                continue;
+            }
             input.parse::<Token![=]>()?;
 
             match ident.to_string().as_str() {
                 "name" => args.name = Some(parse_lit_string(input)?),
                 "description" => args.description = Some(parse_lit_string(input)?),
-                "aliases" => args.aliases = parse_aliases_array(input)?,
+                "aliases" => args.aliases = parse_string_array(input, "aliases")?,
+                "usage" => args.usage = Some(parse_lit_string(input)?),
+                "examples" => args.examples = parse_string_array(input, "examples")?,
                 _ => return Err(syn::Error::new_spanned(ident, "unknown argument")),
             }
 
@@ -51,16 +74,16 @@ fn parse_lit_string(input: ParseStream) -> syn::Result<String> {
     Ok(s.value())
 }
 
-fn parse_aliases_array(input: ParseStream) -> syn::Result<Vec<String>> {
+fn parse_string_array(input: ParseStream, what: &str) -> syn::Result<Vec<String>> {
     let Expr::Array(ExprArray { elems, .. }) = input.parse()? else {
-        return Err(input.error("aliases must be an array literal"));
+        return Err(input.error(format!("{} must be an array literal", what)));
     };
 
     elems
         .into_iter()
         .map(|elem| match elem {
             Expr::Lit(syn::ExprLit { lit: Lit::Str(s), .. }) => Ok(s.value()),
-            _ => Err(syn::Error::new_spanned(elem, "aliases must be string literals")),
+            _ => Err(syn::Error::new_spanned(elem, format!("{} must be string literals", what))),
         })
         .collect()
 }
@@ -104,40 +127,79 @@ fn min_required_args(args: &[(Ident, &Type)]) -> usize {
         .count()
 }
 
+/// Builds a usage line such as `cp <from> <to> [flags]...` from the handler signature.
+fn generate_usage(name: &str, args: &[(Ident, &Type)]) -> String {
+    let mut usage = name.to_string();
+    for (ident, ty) in args {
+        let arg = ident.to_string();
+        let arg = arg.trim_start_matches('_');
+        let part = if extract_option(ty).and_then(extract_vec).is_some() {
+            format!("[{}]...", arg)
+        } else if extract_vec(ty).is_some() {
+            format!("<{}>...", arg)
+        } else if extract_option(ty).is_some() {
+            format!("[{}]", arg)
+        } else {
+            format!("<{}>", arg)
+        };
+        usage.push(' ');
+        usage.push_str(&part);
+    }
+    usage
+}
+
+/// Readable type name for error messages, e.g. `&str` rather than `& str`.
+fn type_name(ty: &Type) -> String {
+    quote!(#ty).to_string().replace(' ', "")
+}
+
 fn generate_parse_code(fn_args: &[(Ident, &Type)]) -> Vec<TokenStream2> {
     fn_args
         .iter()
         .enumerate()
         .map(|(i, (ident, ty))| {
+            let arg_name = ident.to_string();
+            let arg_name = arg_name.trim_start_matches('_');
+
             if let Some(inner_vec) = extract_option(ty).and_then(extract_vec) {
+                let expected = type_name(inner_vec);
                 quote! {
                     let #ident: Option<Vec<#inner_vec>> = if args.len() > #i {
                         Some(args[#i..].iter()
-                            .map(|a| <#inner_vec as crate::ParseArgument>::parse(a))
+                            .enumerate()
+                            .map(|(offset, a)| <#inner_vec as crate::ParseArgument>::parse_os(a)
+                                .map_err(|e| crate::CommandError::invalid_argument(#arg_name, #i + offset + 1, a, #expected, e)))
                             .collect::<Result<Vec<_>, _>>()?)
                     } else { None };
                 }
             } else if let Some(inner_vec) = extract_vec(ty) {
+                let expected = type_name(inner_vec);
                 quote! {
                     if args.len() <= #i {
                         return Err(crate::CommandError::TooFewArguments(args.len(), self.command_info()));
                     }
                     let #ident: Vec<#inner_vec> = args[#i..].iter()
-                        .map(|a| <#inner_vec as crate::ParseArgument>::parse(a))
+                        .enumerate()
+                        .map(|(offset, a)| <#inner_vec as crate::ParseArgument>::parse_os(a)
+                            .map_err(|e| crate::CommandError::invalid_argument(#arg_name, #i + offset + 1, a, #expected, e)))
                         .collect::<Result<Vec<_>, _>>()?;
                 }
             } else if let Some(inner) = extract_option(ty) {
+                let expected = type_name(inner);
                 quote! {
                     let #ident: Option<#inner> = if args.len() > #i {
-                        Some(<#inner as crate::ParseArgument>::parse(args[#i])?)
+                        Some(<#inner as crate::ParseArgument>::parse_os(args[#i])
+                            .map_err(|e| crate::CommandError::invalid_argument(#arg_name, #i + 1, args[#i], #expected, e))?)
                     } else { None };
                 }
             } else {
+                let expected = type_name(ty);
                 quote! {
                     if args.len() <= #i {
                         return Err(crate::CommandError::TooFewArguments(args.len(), self.command_info()));
                     }
-                    let #ident: #ty = <#ty as crate::ParseArgument>::parse(args[#i])?;
+                    let #ident: #ty = <#ty as crate::ParseArgument>::parse_os(args[#i])
+                        .map_err(|e| crate::CommandError::invalid_argument(#arg_name, #i + 1, args[#i], #expected, e))?;
                 }
             }
         })
@@ -161,6 +223,9 @@ pub fn command(args: TokenStream, input: TokenStream) -> TokenStream {
     };
     let description = parsed_args.description.unwrap_or_default();
     let alias_literals = parsed_args.aliases.iter().map(|s| quote! { #s });
+    let example_literals = parsed_args.examples.iter().map(|s| quote! { #s });
+    let dry_run = parsed_args.dry_run;
+    let json = parsed_args.json;
 
     let fn_args: Vec<(Ident, &Type)> = func
         .sig
@@ -182,8 +247,10 @@ pub fn command(args: TokenStream, input: TokenStream) -> TokenStream {
         fn_args.len()
     };
 
+    let usage = parsed_args.usage.unwrap_or_else(|| generate_usage(&name, &fn_args));
     let parse_code = generate_parse_code(&fn_args);
     let call_args = fn_args.iter().map(|(ident, _)| ident);
+    let bound_args = call_args.clone();
 
     // Handler naming
     let handler_struct = format_ident!("{}Handler", func.sig.ident.to_string().to_case(Case::UpperCamel));
@@ -193,25 +260,38 @@ pub fn command(args: TokenStream, input: TokenStream) -> TokenStream {
     );
 
     // Code generation
+    let check_count = quote! {
+        if args.len() < #min_args {
+            return Err(crate::CommandError::TooFewArguments(args.len(), self.command_info()));
+        }
+        if args.len() > #max_args {
+            return Err(crate::CommandError::TooManyArguments(args.len(), self.command_info()));
+        }
+    };
+
     let output = quote! {
         #func
 
         struct #handler_struct;
 
         impl crate::CommandHandler for #handler_struct {
-            fn call(&self, args: &[&str]) -> Result<(), crate::CommandError> {
-                if args.len() < #min_args {
-                    return Err(crate::CommandError::TooFewArguments(args.len(), self.command_info()));
-                }
-                if args.len() > #max_args {
-                    return Err(crate::CommandError::TooManyArguments(args.len(), self.command_info()));
-                }
+            fn call_os(&self, args: &[&std::ffi::OsStr]) -> Result<(), crate::CommandError> {
+                #check_count
 
                 #(#parse_code)*
 
                 #fn_name(#(#call_args),*)
             }
 
+            fn bind_os(&self, args: &[&std::ffi::OsStr]) -> Result<(), crate::CommandError> {
+                #check_count
+
+                #(#parse_code)*
+
+                #(let _ = #bound_args;)*
+                Ok(())
+            }
+
             fn command_info(&self) -> &'static crate::CommandInfo {
                 #handler_static
             }
@@ -222,9 +302,14 @@ pub fn command(args: TokenStream, input: TokenStream) -> TokenStream {
             name: #name,
             description: #description,
             aliases: &[ #( #alias_literals ),* ],
+            usage: #usage,
+            examples: &[ #( #example_literals ),* ],
+            dry_run: #dry_run,
+            json: #json,
             min: #min_args,
             max: #max_args,
             handler: &#handler_struct,
+            source: concat!(module_path!(), "::", stringify!(#fn_name)),
         };
     };
 
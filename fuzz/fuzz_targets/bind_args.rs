@@ -0,0 +1,48 @@
+#![no_main]
+
+//! Binds fuzzed command lines to commands covering every kind of parameter the `command` macro
+//! accepts, as the shell's own builtins live in its binary.
+
+use std::time::Duration;
+
+use command_core::{bind_args, parse_line, ArgRange, Bounded, CommandError, CommandHandler, CommandInfo, CommandRegistry, FromStrArg, GlobPattern, ParseArgument, COMMANDS};
+use command_macro::{command, ParseArgument};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(ParseArgument)]
+enum Mode {
+    Fast,
+    Slow,
+}
+
+#[command(name = "scalars")]
+fn cmd_scalars(_text: &str, _count: u32, _signed: i64, _ratio: f64, _flag: bool, _letter: char) -> Result<(), CommandError> {
+    Ok(())
+}
+
+#[command(name = "wrappers")]
+fn cmd_wrappers(_range: ArgRange<u64>, _level: Bounded<u8, 1, 9>, _ip: FromStrArg<std::net::IpAddr>, _pattern: GlobPattern, _mode: Mode) -> Result<(), CommandError> {
+    Ok(())
+}
+
+#[command(name = "optional")]
+fn cmd_optional(_name: String, _after: Option<Duration>, _rest: Option<Vec<String>>) -> Result<(), CommandError> {
+    Ok(())
+}
+
+#[command(name = "variadic")]
+fn cmd_variadic(_first: i8, _rest: Vec<u16>) -> Result<(), CommandError> {
+    Ok(())
+}
+
+fuzz_target!(|line: &str| {
+    let Some(parsed) = parse_line(line) else {
+        return;
+    };
+    let info: &CommandInfo = match CommandRegistry::resolve(&parsed.name) {
+        Ok(Some(info)) => info,
+        _ => return,
+    };
+    let args: Vec<&str> = parsed.args.iter().map(String::as_str).collect();
+    _ = bind_args(info, &args);
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use command_core::parse_line;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|line: &str| {
+    if let Some(parsed) = parse_line(line) {
+        // Words never contain the whitespace they were split on.
+        assert!(!parsed.name.is_empty());
+        assert!(parsed.args.iter().all(|arg| !arg.is_empty() && !arg.bytes().any(|byte| byte.is_ascii_whitespace())));
+    }
+});
@@ -0,0 +1,121 @@
+use std::{collections::BTreeMap, fs, path::PathBuf, sync::RwLock};
+
+use command_core::{output, CommandError, CommandOutput, Table};
+use command_macro::command;
+use serde::{Deserialize, Serialize};
+
+use crate::{config, theme::{self, Role}};
+
+/// Abbreviations by name. Those from `[abbreviations]` in the configuration file are loaded first,
+/// then the ones `abbr` saved, which take precedence.
+static ABBREVIATIONS: RwLock<Option<BTreeMap<String, String>>> = RwLock::new(None);
+
+/// `~/.shell/abbr.toml`, written by `abbr` so the configuration file keeps its formatting.
+fn saved_path() -> Option<PathBuf> {
+    config::data_dir().map(|dir| dir.join("abbr.toml"))
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Saved {
+    #[serde(default)]
+    abbreviations: BTreeMap<String, String>,
+}
+
+fn load_saved() -> Result<Saved, CommandError> {
+    let Some(path) = saved_path().filter(|path| path.exists()) else {
+        return Ok(Saved::default());
+    };
+    let contents = fs::read_to_string(&path).map_err(|e| CommandError::FileReadError(path.clone(), e))?;
+    toml::from_str(&contents)
+        .map_err(|e| CommandError::CommandFailed(format!("Error parsing '{}': {}", path.display(), e)))
+}
+
+fn with_abbreviations<R>(f: impl FnOnce(&mut BTreeMap<String, String>) -> R) -> R {
+    let mut abbreviations = ABBREVIATIONS.write().unwrap_or_else(|e| e.into_inner());
+    let abbreviations = abbreviations.get_or_insert_with(|| {
+        let mut all = config::get().abbreviations.clone();
+        match load_saved() {
+            Ok(saved) => all.extend(saved.abbreviations),
+            Err(e) => log::error!("{}", e),
+        }
+        all
+    });
+    f(abbreviations)
+}
+
+/// `line` with the word in command position replaced by what `lookup` finds for it, if anything.
+pub fn replace_command(line: &str, lookup: impl FnOnce(&str) -> Option<String>) -> Option<String> {
+    let trimmed = line.trim_start();
+    let end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+    let (word, rest) = trimmed.split_at(end);
+    // The leading space stays, it keeps the line out of the history.
+    let indent = &line[..line.len() - trimmed.len()];
+    lookup(word).map(|expansion| format!("{}{}{}", indent, expansion, rest))
+}
+
+/// `line` with an abbreviation in command position replaced by what it stands for, if there is one.
+pub fn expand(line: &str) -> Option<String> {
+    with_abbreviations(|abbreviations| replace_command(line, |word| abbreviations.get(word).cloned()))
+}
+
+/// Applies `change` to the saved abbreviations and writes them back.
+fn save(change: impl FnOnce(&mut BTreeMap<String, String>)) -> Result<(), CommandError> {
+    let path = saved_path()
+        .ok_or_else(|| CommandError::CommandFailed("Could not determine the home directory to save abbreviations".to_string()))?;
+    let mut saved = load_saved()?;
+    change(&mut saved.abbreviations);
+
+    let contents = toml::to_string(&saved).map_err(|e| CommandError::CommandFailed(e.to_string()))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, contents)?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Abbreviations(BTreeMap<String, String>);
+
+impl CommandOutput for Abbreviations {
+    fn to_text(&self) -> String {
+        let mut table = Table::new();
+        for (name, expansion) in &self.0 {
+            table.push_row([theme::paint(Role::Heading, name), expansion.clone()]);
+        }
+        table.render()
+    }
+}
+
+#[command(
+    name = "abbr",
+    description = "List, add or erase abbreviations, which expand in place when Space or Enter follows them",
+    usage = "abbr [-l|--list] | abbr <name> <expansion...> | abbr -e|--erase <name>",
+    examples = ["abbr gs \"git status\"", "abbr -l", "abbr -e gs"],
+    json
+)]
+pub fn cmd_abbr(args: Vec<&str>) -> Result<(), CommandError> {
+    match args.as_slice() {
+        [] | ["-l" | "--list"] => output::emit(&with_abbreviations(|abbreviations| Abbreviations(abbreviations.clone()))),
+        ["-e" | "--erase", name] => {
+            // One from the configuration file is back in the next session; it is only ever read.
+            let known = with_abbreviations(|abbreviations| abbreviations.remove(*name).is_some());
+            if !known {
+                return Err(CommandError::CommandFailed(format!("No abbreviation '{}'", name)));
+            }
+            save(|saved| _ = saved.remove(*name))
+        }
+        [name, expansion @ ..] if !name.starts_with('-') && !expansion.is_empty() => {
+            // Words aren't unquoted when a line is split, so `"git status"` arrives as two quoted halves.
+            let expansion = expansion.join(" ");
+            let expansion = expansion.strip_prefix('"')
+                .and_then(|expansion| expansion.strip_suffix('"'))
+                .unwrap_or(&expansion)
+                .to_string();
+            with_abbreviations(|abbreviations| abbreviations.insert(name.to_string(), expansion.clone()));
+            save(|saved| _ = saved.insert(name.to_string(), expansion))
+        }
+        _ => Err(CommandError::InvalidArguments(
+            "Usage: abbr [-l|--list] | abbr <name> <expansion...> | abbr -e|--erase <name>".to_string(),
+        )),
+    }
+}
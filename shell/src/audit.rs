@@ -0,0 +1,337 @@
+use std::{
+    env,
+    ffi::OsStr,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use chrono::Local;
+use command_core::{output, CancellationToken, CommandError, CommandMiddleware, CommandOutput, Invocation, Next, Table};
+use command_macro::command;
+use humansize::{format_size, DECIMAL};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::{config, deterministic, glob, paths, theme::{self, Role}};
+
+/// `[audit]` section of the configuration file.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// Append what `rm`, `rmdir`, `cp`, `cat >` and `iconv -o` removed, trashed or overwrote to
+    /// `~/.shell/audit.log`. Off unless turned on, as sizes are measured before every removal,
+    /// which means walking a whole tree for `rm -r`.
+    pub enabled: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Removed,
+    /// Moved to the trash by `rm`, so it can still be restored.
+    Trashed,
+    Overwritten,
+    /// Written to the end of by `cat >>`; the size is what it held before.
+    Appended,
+}
+
+/// A path a command removed or overwrote, one line of the audit log.
+#[derive(Serialize, Deserialize)]
+struct AuditEntry {
+    at: String,
+    cwd: PathBuf,
+    command: String,
+    action: Action,
+    path: PathBuf,
+    /// Bytes before the command ran, everything below it for a directory.
+    size: u64,
+    /// Files below a removed directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    files: Option<u64>,
+}
+
+fn audit_path() -> Option<PathBuf> {
+    config::data_dir().map(|dir| dir.join("audit.log"))
+}
+
+/// Serializes appends from the shell's threads; other shells append whole lines on their own.
+static APPEND: Mutex<()> = Mutex::new(());
+
+fn append(entries: &[AuditEntry]) {
+    let Some(path) = audit_path() else {
+        return;
+    };
+    let _append = APPEND.lock().unwrap_or_else(|e| e.into_inner());
+    let written = path.parent().map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| OpenOptions::new().create(true).append(true).open(&path))
+        .and_then(|mut file| {
+            let mut text = String::new();
+            for entry in entries {
+                text.push_str(&serde_json::to_string(entry).map_err(std::io::Error::other)?);
+                text.push('\n');
+            }
+            file.write_all(text.as_bytes())
+        });
+    if let Err(e) = written {
+        debug!("could not write the audit log: {}", e);
+    }
+}
+
+/// Size of `path` in bytes and, for a directory, the number of files below it, or `None` if it
+/// doesn't exist. Links aren't followed.
+fn measure(path: &Path, token: &CancellationToken) -> Result<Option<(u64, Option<u64>)>, CommandError> {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return Ok(None);
+    };
+    if !metadata.is_dir() {
+        return Ok(Some((metadata.len(), None)));
+    }
+
+    let (mut size, mut files) = (0, 0);
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        token.check()?;
+        for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+            match entry.metadata() {
+                Ok(metadata) if metadata.is_dir() => pending.push(entry.path()),
+                Ok(metadata) => {
+                    size += metadata.len();
+                    files += 1;
+                }
+                Err(_) => {}
+            }
+        }
+    }
+    Ok(Some((size, Some(files))))
+}
+
+/// Files below the directory `source` that copying it onto the directory `target` replaces.
+/// Stops early when the command is interrupted, which it then fails with anyway.
+fn replaced_below(source: &Path, target: &Path, token: &CancellationToken) -> Vec<PathBuf> {
+    let mut replaced = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        if token.is_cancelled() {
+            break;
+        }
+        for entry in fs::read_dir(source.join(&relative)).into_iter().flatten().flatten() {
+            let relative = relative.join(entry.file_name());
+            let existing = target.join(&relative);
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir() && existing.is_dir() => pending.push(relative),
+                Ok(kind) if !kind.is_dir() && existing.is_file() => replaced.push(existing),
+                _ => {}
+            }
+        }
+    }
+    replaced
+}
+
+/// Paths an argument names, expanded like `rm` expands globs.
+pub fn expand(arg: &OsStr) -> Vec<PathBuf> {
+    if !glob::is_pattern(arg) || Path::new(arg).exists() {
+        return vec![PathBuf::from(arg)];
+    }
+    glob::expand(arg).unwrap_or_default()
+}
+
+/// The paths a destructive builtin may remove or overwrite, with the action that would be.
+pub fn candidates(command: &str, args: &[&OsStr]) -> Vec<(Action, PathBuf)> {
+    let is_flag = |arg: &&OsStr| arg.to_string_lossy().starts_with('-');
+    match command {
+        "rm" | "rmdir" => args.iter()
+            .filter(|arg| !is_flag(arg))
+            .flat_map(|arg| expand(arg))
+            .map(|path| (Action::Removed, path))
+            .collect(),
+        "cp" => {
+            let mut operands = Vec::new();
+            let mut args = args.iter();
+            while let Some(arg) = args.next() {
+                match arg.to_str() {
+                    // Their values aren't paths.
+                    Some("--threads" | "--buffer-size") => _ = args.next(),
+                    _ if is_flag(arg) => {}
+                    _ => operands.push(Path::new(arg)),
+                }
+            }
+            let Some((dest, sources)) = operands.split_last() else {
+                return Vec::new();
+            };
+            let token = CancellationToken::current();
+            sources.iter()
+                .flat_map(|source| {
+                    let target = match source.file_name() {
+                        Some(name) if dest.is_dir() => dest.join(name),
+                        _ => dest.to_path_buf(),
+                    };
+                    // Copying a directory onto one replaces the files the two have in common.
+                    if source.is_dir() && target.is_dir() {
+                        replaced_below(source, &target, &token)
+                    } else {
+                        target.is_file().then_some(target).into_iter().collect()
+                    }
+                })
+                .map(|target| (Action::Overwritten, target))
+                .collect()
+        }
+        "cat" => args.windows(2)
+            .filter_map(|pair| match pair[0].to_str() {
+                Some(">") => Some((Action::Overwritten, Path::new(pair[1]))),
+                Some(">>") => Some((Action::Appended, Path::new(pair[1]))),
+                _ => None,
+            })
+            .filter(|(_, target)| target.is_file())
+            .map(|(action, target)| (action, target.to_path_buf()))
+            .collect(),
+        "iconv" => args.windows(2)
+            .filter(|pair| pair[0] == "-o" || pair[0] == "--output")
+            .map(|pair| Path::new(pair[1]))
+            .filter(|target| target.is_file())
+            .map(|target| (Action::Overwritten, target.to_path_buf()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Whether `rm` with `args` moves what it removes to the trash.
+fn moves_to_trash(args: &[&OsStr]) -> bool {
+    args.iter()
+        .rev()
+        .find_map(|arg| match arg.to_str() {
+            Some("--trash") => Some(true),
+            Some("--no-trash") => Some(false),
+            _ => None,
+        })
+        .unwrap_or(config::get().trash.rm)
+}
+
+/// Records what `rm`, `rmdir`, `cp`, `cat >` and `iconv -o` actually removed, trashed or overwrote:
+/// the paths they were given are measured before the command runs and checked again afterwards.
+pub struct AuditMiddleware;
+
+impl CommandMiddleware for AuditMiddleware {
+    fn handle(&self, invocation: &Invocation, next: Next) -> Result<(), CommandError> {
+        let Some(info) = invocation.info else {
+            return next.run(invocation);
+        };
+        let candidates = candidates(info.name, invocation.args);
+        if candidates.is_empty() {
+            return next.run(invocation);
+        }
+
+        let cwd = env::current_dir().unwrap_or_default();
+        let token = CancellationToken::current();
+        let trashed = info.name == "rm" && moves_to_trash(invocation.args);
+        let mut measured = Vec::with_capacity(candidates.len());
+        for (action, path) in candidates {
+            if let Some(size) = measure(&path, &token)? {
+                let action = if action == Action::Removed && trashed { Action::Trashed } else { action };
+                measured.push((action, path, size));
+            }
+        }
+
+        let result = next.run(invocation);
+
+        let at = Local::now().to_rfc3339();
+        let entries: Vec<AuditEntry> = measured.into_iter()
+            .filter(|(action, path, _)| match action {
+                Action::Removed | Action::Trashed => fs::symlink_metadata(path).is_err(),
+                // A failed copy may have stopped before reaching the file, so only successful ones count.
+                Action::Overwritten | Action::Appended => result.is_ok(),
+            })
+            .map(|(action, path, (size, files))| AuditEntry {
+                at: at.clone(),
+                cwd: cwd.clone(),
+                command: info.name.to_string(),
+                action,
+                path: paths::normalize(&cwd.join(path)),
+                size,
+                files,
+            })
+            .collect();
+        if !entries.is_empty() {
+            append(&entries);
+        }
+        result
+    }
+}
+
+#[derive(Serialize)]
+struct AuditLog {
+    entries: Vec<AuditEntry>,
+}
+
+impl CommandOutput for AuditLog {
+    fn to_text(&self) -> String {
+        if self.entries.is_empty() && !config::get().audit.enabled {
+            return "Nothing recorded; set `enabled = true` under [audit] in the configuration file to record removals\n".to_string();
+        }
+        if self.entries.is_empty() {
+            return "Nothing has been removed or overwritten\n".to_string();
+        }
+
+        let mut table = Table::new();
+        for entry in &self.entries {
+            let (role, action) = match entry.action {
+                Action::Removed => (Role::Failure, "removed"),
+                Action::Trashed => (Role::Muted, "trashed"),
+                Action::Overwritten => (Role::Warn, "overwritten"),
+                Action::Appended => (Role::Muted, "appended"),
+            };
+            let size = match entry.files {
+                Some(files) => format!("{} in {} files", format_size(entry.size, DECIMAL), files),
+                None => format_size(entry.size, DECIMAL),
+            };
+            table.push_row([
+                theme::paint(Role::Muted, &entry.at),
+                theme::paint(role, action),
+                size,
+                paths::display(&entry.path),
+            ]);
+        }
+        table.render()
+    }
+}
+
+#[command(
+    name = "audit",
+    description = "Show what rm, rmdir, cp, cat > and iconv -o removed, trashed or overwrote",
+    usage = "audit [show [-n <count>]|path]",
+    examples = ["audit", "audit show -n 100", "audit path"],
+    json
+)]
+pub fn cmd_audit(args: Vec<&str>) -> Result<(), CommandError> {
+    let path = audit_path()
+        .ok_or_else(|| CommandError::CommandFailed("Could not determine the home directory for the audit log".to_string()))?;
+
+    let mut count = 20;
+    match args.as_slice() {
+        ["path"] => return output::print(&format!("{}\n", path.display())),
+        [] | ["show"] => {}
+        ["show", "-n", value] => {
+            count = value.parse()
+                .map_err(|_| CommandError::InvalidArguments("-n needs a number".to_string()))?;
+        }
+        _ => return Err(CommandError::InvalidArguments("Usage: audit [show [-n <count>]|path]".to_string())),
+    }
+
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(CommandError::FileReadError(path, e)),
+    };
+    let mut entries: Vec<AuditEntry> = contents.lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    entries.drain(..entries.len().saturating_sub(count));
+    for entry in &mut entries {
+        if let Some(at) = deterministic::recorded(&entry.at) {
+            entry.at = at.to_rfc3339();
+        }
+    }
+
+    output::emit(&AuditLog { entries })
+}
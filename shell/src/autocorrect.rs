@@ -0,0 +1,68 @@
+use std::{
+    env,
+    ffi::OsStr,
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use command_core::{suggest, CommandError, CommandRegistry};
+
+use crate::{call_executable, safety, tr};
+
+/// Set by `set autocorrect on`: a mistyped command or `cd` target is corrected once confirmed,
+/// instead of only being suggested.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// `path` with every component that isn't a directory replaced by the closest directory next to it,
+/// or `None` if it has no missing component or one has nothing close.
+pub fn directory(path: &Path) -> Option<PathBuf> {
+    let cwd = env::current_dir().ok()?;
+    let mut corrected = PathBuf::new();
+    let mut changed = false;
+    for component in path.components() {
+        let Component::Normal(name) = component else {
+            corrected.push(component);
+            continue;
+        };
+        if cwd.join(&corrected).join(name).is_dir() {
+            corrected.push(name);
+            continue;
+        }
+
+        let dirs = fs::read_dir(cwd.join(&corrected)).ok()?
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().into_string().ok());
+        corrected.push(suggest::closest(&name.to_string_lossy(), dirs).into_iter().next()?);
+        changed = true;
+    }
+    changed.then_some(corrected)
+}
+
+/// Whether to go with `correction`: only with autocorrect on, and once confirmed.
+pub fn accept(correction: &str) -> Result<bool, CommandError> {
+    Ok(is_enabled() && safety::confirm(&tr!("confirm.autocorrect", correction = correction))?)
+}
+
+/// Runs the closest command instead of `name` once [`accept`]ed. Otherwise the not-found error,
+/// which suggests it, stands.
+pub fn correct(name: &str, args: &[&OsStr]) -> Option<Result<(), CommandError>> {
+    if !is_enabled() {
+        return None;
+    }
+    let correction = suggest::suggestions(name).into_iter().next()?;
+    match accept(&correction) {
+        Ok(true) => Some(CommandRegistry::execute_or_else(&correction, args, call_executable)),
+        Ok(false) => None,
+        Err(e) => Some(Err(e)),
+    }
+}
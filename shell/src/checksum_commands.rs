@@ -0,0 +1,163 @@
+use std::{ffi::OsStr, fs, path::{Path, PathBuf}, sync::Mutex, thread};
+
+use command_core::{output, CancellationToken, CommandError};
+use command_macro::command;
+use rayon::{prelude::*, ThreadPoolBuilder};
+use sha2::{Digest, Sha256};
+
+use crate::{mapped, progress::Progress, theme::{self, Role}, walk::Walk};
+
+/// SHA-256 of a file as lowercase hex.
+fn hash_file(path: &Path) -> Result<String, String> {
+    let data = mapped::read(path, true).map_err(|e| format!("'{}': {}", path.display(), e))?;
+    Ok(Sha256::digest(&data[..]).iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Runs `f` over `items` on at most `jobs` threads, or on this one when `jobs` is 1. Results keep the
+/// order of `items`, so the output doesn't depend on which file happened to finish first.
+fn hash_all<T: Sync, R: Send>(
+    items: &[T],
+    jobs: usize,
+    f: impl Fn(&T) -> R + Sync,
+) -> Result<Vec<R>, CommandError> {
+    let token = CancellationToken::current();
+    let progress = Progress::items(items.len() as u64, "Hashing");
+    let run = |item: &T| -> Result<R, CommandError> {
+        token.check()?;
+        let result = f(item);
+        progress.inc(1);
+        Ok(result)
+    };
+
+    if jobs <= 1 {
+        return items.iter().map(run).collect();
+    }
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .map_err(|e| CommandError::CommandFailed(e.to_string()))?;
+    pool.install(|| items.par_iter().map(run).collect())
+}
+
+fn report(mut failures: Vec<String>, what: &str) -> Result<(), CommandError> {
+    match failures.len() {
+        0 => Ok(()),
+        1 => Err(CommandError::CommandFailed(failures.remove(0))),
+        count => Err(CommandError::CommandFailed(format!(
+            "{} {}:\n    {}",
+            count,
+            what,
+            failures.join("\n    ")
+        ))),
+    }
+}
+
+/// Checks every `<hash>  <path>` line of `manifest`, relative to the directory holding it.
+fn verify(manifest: &Path, jobs: usize) -> Result<(), CommandError> {
+    let contents = fs::read_to_string(manifest)
+        .map_err(|e| CommandError::CommandFailed(format!("'{}': {}", manifest.display(), e)))?;
+    let base = manifest.parent().unwrap_or(Path::new(""));
+
+    let mut entries = Vec::new();
+    for (number, line) in contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let Some((hash, name)) = line.split_once(char::is_whitespace) else {
+            return Err(CommandError::InvalidArguments(format!(
+                "'{}' line {}: expected '<hash>  <path>'",
+                manifest.display(),
+                number + 1
+            )));
+        };
+        // `sha256sum` marks binary mode with `*` before the name.
+        let name = name.trim_start().trim_start_matches('*');
+        entries.push((hash.to_ascii_lowercase(), name.to_string(), base.join(name)));
+    }
+
+    let results = hash_all(&entries, jobs, |(expected, _, path)| hash_file(path).map(|actual| actual == *expected))?;
+
+    let mut text = String::new();
+    let mut failures = Vec::new();
+    for ((_, name, _), result) in entries.iter().zip(results) {
+        match result {
+            Ok(true) => text.push_str(&format!("{}: {}\n", name, theme::paint(Role::Success, "OK"))),
+            Ok(false) => {
+                text.push_str(&format!("{}: {}\n", name, theme::paint(Role::Failure, "FAILED")));
+                failures.push(format!("'{}' does not match", name));
+            }
+            Err(e) => failures.push(e),
+        }
+    }
+
+    output::print(&text)?;
+    report(failures, "files failed verification")
+}
+
+#[command(
+    name = "sum",
+    description = "Print or verify SHA-256 checksums, hashing files in parallel",
+    usage = "sum [-r] [-j <jobs>] [--sequential] <path...> | sum -c <manifest> [-j <jobs>]",
+    examples = ["sum Cargo.toml", "sum -c SHA256SUMS", "sum -j 4 -r assets"]
+)]
+pub fn cmd_sum(args: Vec<&OsStr>) -> Result<(), CommandError> {
+    let mut recursive = false;
+    let mut manifest = None;
+    let mut jobs = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut targets = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("-c" | "--check") => {
+                let path = args.next()
+                    .ok_or_else(|| CommandError::InvalidArguments("-c needs a manifest".to_string()))?;
+                manifest = Some(Path::new(path));
+            }
+            Some("-j" | "--jobs") => {
+                jobs = args.next()
+                    .and_then(|value| value.to_str())
+                    .and_then(|value| value.parse().ok())
+                    .filter(|&jobs| jobs > 0)
+                    .ok_or_else(|| CommandError::InvalidArguments("-j needs a positive number".to_string()))?;
+            }
+            Some("-r" | "--recursive") => recursive = true,
+            Some("--sequential") => jobs = 1,
+            _ => targets.push(Path::new(arg)),
+        }
+    }
+
+    if let Some(manifest) = manifest {
+        return verify(manifest, jobs);
+    }
+    if targets.is_empty() {
+        return Err(CommandError::InvalidArguments("Usage: sum [-r] <path...>".to_string()));
+    }
+
+    let mut files = Vec::new();
+    for target in targets {
+        if recursive && target.is_dir() {
+            let found = Mutex::new(Vec::new());
+            Walk::new().sorted(true).run(target, |entry| {
+                if entry.metadata.is_file() {
+                    found.lock().unwrap_or_else(|e| e.into_inner()).push(entry.path);
+                }
+                Ok(())
+            })?;
+            files.extend(found.into_inner().unwrap_or_else(|e| e.into_inner()));
+        } else {
+            files.push(target.to_path_buf());
+        }
+    }
+
+    let results = hash_all(&files, jobs, |path: &PathBuf| hash_file(path))?;
+
+    let mut text = String::new();
+    let mut failures = Vec::new();
+    for (path, result) in files.iter().zip(results) {
+        match result {
+            Ok(hash) => text.push_str(&format!("{}  {}\n", hash, path.display())),
+            Err(e) => failures.push(e),
+        }
+    }
+
+    output::print(&text)?;
+    report(failures, "files could not be read")
+}
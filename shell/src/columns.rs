@@ -0,0 +1,65 @@
+use std::io::{self, IsTerminal};
+
+use unicode_width::UnicodeWidthStr;
+
+const GAP: usize = 2;
+
+/// Width of the terminal stdout is attached to, or 80 columns when it isn't a terminal.
+pub fn terminal_width() -> usize {
+    if !io::stdout().is_terminal() {
+        return 80;
+    }
+    crossterm::terminal::size()
+        .map(|(cols, _)| cols as usize)
+        .unwrap_or(80)
+}
+
+/// An entry to lay out: the text to print (possibly colored) and the plain text it displays as.
+pub struct Cell {
+    pub text: String,
+    pub width: usize,
+}
+
+impl Cell {
+    pub fn styled(text: String, plain: &str) -> Self {
+        Self { text, width: plain.width() }
+    }
+}
+
+/// Lays `cells` out top-to-bottom, then left-to-right, in as many columns as fit in `width`.
+pub fn render(cells: &[Cell], width: usize) -> String {
+    if cells.is_empty() {
+        return String::new();
+    }
+
+    let narrowest = cells.iter().map(|c| c.width).min().unwrap_or(1).max(1);
+    let max_cols = (width / (narrowest + GAP)).clamp(1, cells.len());
+
+    let (rows, col_widths) = (1..=max_cols)
+        .rev()
+        .find_map(|cols| {
+            let rows = cells.len().div_ceil(cols);
+            let col_widths: Vec<usize> = cells.chunks(rows)
+                .map(|column| column.iter().map(|c| c.width).max().unwrap_or(0))
+                .collect();
+            let total = col_widths.iter().sum::<usize>() + GAP * (col_widths.len() - 1);
+            (total <= width || cols == 1).then_some((rows, col_widths))
+        })
+        .unwrap_or((cells.len(), vec![0]));
+
+    let mut output = String::new();
+    for row in 0..rows {
+        let row_cells: Vec<(usize, &Cell)> = (0..col_widths.len())
+            .filter_map(|col| cells.get(col * rows + row).map(|cell| (col, cell)))
+            .collect();
+
+        for (i, &(col, cell)) in row_cells.iter().enumerate() {
+            output.push_str(&cell.text);
+            if i + 1 < row_cells.len() {
+                output.push_str(&" ".repeat(col_widths[col] - cell.width + GAP));
+            }
+        }
+        output.push('\n');
+    }
+    output
+}
@@ -0,0 +1,203 @@
+use std::{cell::RefCell, collections::BTreeMap, fs, path::PathBuf, sync::Mutex};
+
+use command_core::{input, output, parse_line, CommandError, CommandOutput, LastCommand, Shell};
+use command_macro::command;
+use serde::{Deserialize, Serialize};
+
+use crate::{call_executable, config, theme::{self, Role}};
+
+/// The macro being recorded, by name, with the lines recorded so far.
+static RECORDING: Mutex<Option<(String, Vec<String>)>> = Mutex::new(None);
+
+thread_local! {
+    /// The macros being played on this thread, outermost first, so one can't play itself.
+    static PLAYING: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Marks a macro as playing until dropped, also when one of its lines panics.
+struct Playing;
+
+impl Playing {
+    fn start(name: &str) -> Result<Self, CommandError> {
+        PLAYING.with(|playing| {
+            let mut playing = playing.borrow_mut();
+            if playing.iter().any(|other| other == name) {
+                return Err(CommandError::CommandFailed(format!(
+                    "Macro '{}' plays itself: {} -> {}",
+                    name,
+                    playing.join(" -> "),
+                    name
+                )));
+            }
+            playing.push(name.to_string());
+            Ok(Playing)
+        })
+    }
+}
+
+impl Drop for Playing {
+    fn drop(&mut self) {
+        PLAYING.with(|playing| playing.borrow_mut().pop());
+    }
+}
+
+/// `~/.shell/macros.toml`.
+fn macros_path() -> Option<PathBuf> {
+    config::data_dir().map(|dir| dir.join("macros.toml"))
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Saved {
+    #[serde(default)]
+    macros: BTreeMap<String, Vec<String>>,
+}
+
+fn load() -> Result<Saved, CommandError> {
+    let Some(path) = macros_path().filter(|path| path.exists()) else {
+        return Ok(Saved::default());
+    };
+    let contents = fs::read_to_string(&path).map_err(|e| CommandError::FileReadError(path.clone(), e))?;
+    toml::from_str(&contents)
+        .map_err(|e| CommandError::CommandFailed(format!("Error parsing '{}': {}", path.display(), e)))
+}
+
+/// Applies `change` to the saved macros and writes them back.
+fn save(change: impl FnOnce(&mut BTreeMap<String, Vec<String>>)) -> Result<(), CommandError> {
+    let path = macros_path()
+        .ok_or_else(|| CommandError::CommandFailed("Could not determine the home directory to save macros".to_string()))?;
+    let mut saved = load()?;
+    change(&mut saved.macros);
+
+    let contents = toml::to_string(&saved).map_err(|e| CommandError::CommandFailed(e.to_string()))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, contents)?;
+    Ok(())
+}
+
+/// `line` with the here-document it was given, under a delimiter none of its lines is.
+fn with_heredoc(line: &str, body: &[u8]) -> String {
+    let body = String::from_utf8_lossy(body);
+    let mut delimiter = "EOF".to_string();
+    while body.lines().any(|body_line| body_line == delimiter) {
+        delimiter.push('_');
+    }
+    format!("{} <<{}\n{}{}", line, delimiter, body, delimiter)
+}
+
+/// Adds a line that ran successfully to the macro being recorded, if there is one, with its
+/// here-document. Starting and stopping the recording aren't part of it.
+pub fn command_finished(line: &str, finished: &LastCommand) {
+    let mut recording = RECORDING.lock().unwrap_or_else(|e| e.into_inner());
+    let Some((_, lines)) = recording.as_mut() else {
+        return;
+    };
+    let is_control = parse_line(line).is_some_and(|parsed| {
+        parsed.name == "macro" && matches!(parsed.args.first().map(String::as_str), Some("record" | "stop"))
+    });
+    if finished.exit_code == 0 && !is_control {
+        lines.push(match input::provided() {
+            Some(body) => with_heredoc(line, &body),
+            None => line.to_string(),
+        });
+    }
+}
+
+/// `line` with `$1` to `$9` replaced by the arguments `macro play` was given.
+fn substitute(line: &str, args: &[&str]) -> Result<String, CommandError> {
+    let mut substituted = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let Some(digit) = chars.peek().and_then(|next| next.to_digit(10)).filter(|digit| c == '$' && *digit > 0) else {
+            substituted.push(c);
+            continue;
+        };
+        chars.next();
+        let arg = args.get(digit as usize - 1)
+            .ok_or_else(|| CommandError::InvalidArguments(format!("'{}' needs an argument for ${}", line, digit)))?;
+        substituted.push_str(arg);
+    }
+    Ok(substituted)
+}
+
+/// Runs the lines of macro `name` in order, echoing each first, and stops at the first that fails.
+/// They run the way typed lines do, here-documents included.
+fn play(name: &str, args: &[&str]) -> Result<(), CommandError> {
+    let _playing = Playing::start(name)?;
+    let lines = load()?.macros.remove(name)
+        .ok_or_else(|| CommandError::CommandFailed(format!("No macro '{}'", name)))?;
+    // Substituted up front, so a missing argument fails before anything ran.
+    let lines = lines.iter().map(|line| substitute(line, args)).collect::<Result<Vec<_>, _>>()?;
+
+    let mut shell = Shell::builder().external(call_executable).build();
+    for line in lines {
+        if parse_line(&line).is_none() {
+            continue;
+        }
+        output::print(&format!("{}\n", theme::paint(Role::Muted, &line)))?;
+        shell.eval(&line)
+            .map_err(|e| CommandError::CommandFailed(format!("Macro '{}' stopped at '{}': {}", name, line, e)))?;
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Macros(BTreeMap<String, Vec<String>>);
+
+impl CommandOutput for Macros {
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (name, lines) in &self.0 {
+            text.push_str(&format!("{}\n", theme::paint(Role::Heading, name)));
+            for line in lines {
+                text.push_str(&format!("  {}\n", line));
+            }
+        }
+        text
+    }
+}
+
+#[command(
+    name = "macro",
+    description = "Record the command lines that run into a named macro and play it back",
+    usage = "macro record <name> | macro stop | macro play <name> [args...] | macro list | macro delete <name>",
+    examples = ["macro record deploy", "macro stop", "macro play deploy staging", "macro list"],
+    json
+)]
+pub fn cmd_macro(args: Vec<&str>) -> Result<(), CommandError> {
+    let mut recording = RECORDING.lock().unwrap_or_else(|e| e.into_inner());
+    match args.as_slice() {
+        ["record", name] => {
+            if let Some((current, _)) = recording.as_ref() {
+                return Err(CommandError::CommandFailed(format!("Already recording macro '{}'", current)));
+            }
+            *recording = Some((name.to_string(), Vec::new()));
+            output::print(&format!("Recording macro '{}'; `$1` to `$9` in a line take the arguments of `macro play`\n", name))
+        }
+        ["stop"] => {
+            let (name, lines) = recording.take()
+                .ok_or_else(|| CommandError::CommandFailed("No macro is being recorded".to_string()))?;
+            let count = lines.len();
+            save(|macros| _ = macros.insert(name.clone(), lines))?;
+            output::print(&format!("Saved macro '{}' with {} lines\n", name, count))
+        }
+        ["play", name, args @ ..] => {
+            // Lines of the macro finishing mustn't wait for the recording.
+            drop(recording);
+            play(name, args)
+        }
+        ["list"] | [] => output::emit(&Macros(load()?.macros)),
+        ["delete", name] => {
+            let mut found = false;
+            save(|macros| found = macros.remove(*name).is_some())?;
+            if !found {
+                return Err(CommandError::CommandFailed(format!("No macro '{}'", name)));
+            }
+            Ok(())
+        }
+        _ => Err(CommandError::InvalidArguments(
+            "Usage: macro record <name> | macro stop | macro play <name> [args...] | macro list | macro delete <name>".to_string(),
+        )),
+    }
+}
@@ -0,0 +1,150 @@
+use std::{collections::BTreeMap, env, fs, path::{Path, PathBuf}, sync::OnceLock};
+
+use command_core::{LookupOptions, Precedence};
+use log::{error, warn};
+use serde::Deserialize;
+
+use crate::{audit::AuditConfig, copy::CopyConfig, file_index::IndexConfig, history::HistoryConfig, logging::LogConfig, not_found::NotFoundConfig, notify::NotifyConfig, paths, project::ProjectConfig, prompt::PromptConfig, remote::RemoteConfig, safety::SafetyConfig, session::SessionConfig, stats::StatsConfig, theme::ThemeConfig, trash::TrashConfig};
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    /// Language of the shell's messages, e.g. `de`; taken from `LANG` when unset.
+    pub locale: Option<String>,
+    pub lookup: LookupConfig,
+    pub theme: ThemeConfig,
+    pub log: LogConfig,
+    pub prompt: PromptConfig,
+    pub notify: NotifyConfig,
+    pub trash: TrashConfig,
+    pub rmdir: RmdirConfig,
+    pub index: IndexConfig,
+    pub copy: CopyConfig,
+    pub remote: RemoteConfig,
+    pub stats: StatsConfig,
+    pub audit: AuditConfig,
+    pub safety: SafetyConfig,
+    pub history: HistoryConfig,
+    pub command_not_found: NotFoundConfig,
+    pub session: SessionConfig,
+    pub project: ProjectConfig,
+    /// `gs = "git status"`: a line starting with `gs` runs `git status` instead, see `abbr`.
+    pub abbreviations: BTreeMap<String, String>,
+    /// Confine builtins and `cd` to this directory, as `--restrict` does.
+    pub restrict: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct LookupConfig {
+    pub case_insensitive: bool,
+    pub prefix_abbreviation: bool,
+    /// Let executables on `PATH` shadow builtins of the same name.
+    pub prefer_external: bool,
+}
+
+// Not derived: names are case-insensitive by default on Windows, which clippy can't see on Unix.
+#[allow(clippy::derivable_impls)]
+impl Default for LookupConfig {
+    fn default() -> Self {
+        Self {
+            case_insensitive: cfg!(windows),
+            prefix_abbreviation: false,
+            prefer_external: false,
+        }
+    }
+}
+
+impl LookupConfig {
+    pub fn precedence(&self) -> Precedence {
+        if self.prefer_external {
+            Precedence::External
+        } else {
+            Precedence::Builtin
+        }
+    }
+}
+
+impl From<&LookupConfig> for LookupOptions {
+    fn from(config: &LookupConfig) -> Self {
+        LookupOptions {
+            case_insensitive: config.case_insensitive,
+            prefix_abbreviation: config.prefix_abbreviation,
+        }
+    }
+}
+
+/// How far `rmdir -p` may prune empty parents.
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum PruneBoundary {
+    /// Stop at the current directory and the home directory, and at anything above either.
+    #[default]
+    Cwd,
+    /// Stop at the home directory and above.
+    Home,
+    /// Prune as far up as the parents are empty.
+    None,
+}
+
+impl PruneBoundary {
+    /// The directories that are kept, with everything above them, when starting from `cwd`.
+    pub fn directories(self, cwd: &Path) -> Vec<PathBuf> {
+        let home = home_dir().map(|home| paths::normalize(&home));
+        match self {
+            PruneBoundary::Cwd => std::iter::once(paths::normalize(cwd)).chain(home).collect(),
+            PruneBoundary::Home => home.into_iter().collect(),
+            PruneBoundary::None => Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct RmdirConfig {
+    pub boundary: PruneBoundary,
+}
+
+/// The user's home directory, from `HOME` or `USERPROFILE`.
+pub fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Directory holding the shell's configuration and state, `~/.shell`.
+pub fn data_dir() -> Option<PathBuf> {
+    home_dir().map(|home| home.join(".shell"))
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("config.toml"))
+}
+
+fn load() -> Config {
+    let Some(path) = config_path() else {
+        warn!("could not determine the home directory, using the default configuration");
+        return Config::default();
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
+            error!("parsing '{}': {}", path.display(), e);
+            Config::default()
+        }),
+        Err(_) => Config::default(),
+    }
+}
+
+/// The loaded configuration. The file is read on first access.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(load)
+}
+
+/// The configuration if it was loaded already, without loading it.
+pub fn try_get() -> Option<&'static Config> {
+    CONFIG.get()
+}
@@ -0,0 +1,129 @@
+use std::{fs::{self, File}, io::{self, Read, Seek, SeekFrom, Write}, path::Path, slice};
+
+use command_core::CancellationToken;
+use serde::Deserialize;
+
+/// Copy buffers are allocated in blocks of this alignment, which unbuffered and direct I/O need
+/// and which keeps every read on page boundaries otherwise.
+const ALIGNMENT: usize = 4096;
+
+/// The largest copy buffer allowed; every copying thread allocates one.
+pub const MAX_BUFFER_SIZE: usize = 256 * 1024 * 1024;
+
+/// `[copy]` section of the configuration file.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct CopyConfig {
+    /// Bytes read and written at a time, at most 256 MiB; rounded up to a multiple of 4 KiB.
+    pub buffer_size: usize,
+    /// Reserve the whole size of the destination before writing it, which avoids fragmentation
+    /// but fills holes of sparse files.
+    pub preallocate: bool,
+    /// Files copied at the same time by `cp -r`.
+    pub threads: usize,
+}
+
+impl Default for CopyConfig {
+    fn default() -> Self {
+        Self {
+            buffer_size: 1024 * 1024,
+            preallocate: false,
+            threads: 4,
+        }
+    }
+}
+
+#[repr(C, align(4096))]
+#[derive(Clone, Copy)]
+struct Block([u8; ALIGNMENT]);
+
+/// A zeroed buffer starting on a 4 KiB boundary.
+struct AlignedBuffer(Vec<Block>);
+
+impl AlignedBuffer {
+    fn new(size: usize) -> Self {
+        AlignedBuffer(vec![Block([0; ALIGNMENT]); size.div_ceil(ALIGNMENT).max(1)])
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        // Safety: the blocks are plain bytes laid out back to back without padding.
+        unsafe { slice::from_raw_parts_mut(self.0.as_mut_ptr().cast::<u8>(), self.0.len() * ALIGNMENT) }
+    }
+}
+
+/// Reserves `len` bytes for `file` on disk, falling back to only extending it where the OS can't.
+fn preallocate(file: &File, len: u64) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+
+        match unsafe { libc::posix_fallocate(file.as_raw_fd(), 0, len as libc::off_t) } {
+            0 => return Ok(()),
+            libc::EOPNOTSUPP | libc::EINVAL => {}
+            code => return Err(io::Error::from_raw_os_error(code)),
+        }
+    }
+    // NTFS allocates the clusters when the end of file is moved.
+    file.set_len(len)
+}
+
+/// How a file ended up being copied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CopyMethod {
+    /// Cloned by the filesystem (ReFS, APFS, btrfs, XFS) without copying any data.
+    Reflink,
+    /// Copied byte by byte, leaving runs of zeros as holes.
+    Bytes,
+}
+
+/// Copies the contents and permissions of `from` to `to`, cloning it when the filesystem
+/// supports copy-on-write and otherwise keeping sparse regions sparse unless preallocating.
+pub fn copy_file(from: &Path, to: &Path, config: &CopyConfig, token: &CancellationToken) -> io::Result<CopyMethod> {
+    if reflink_copy::reflink(from, to).is_ok() {
+        return Ok(CopyMethod::Reflink);
+    }
+
+    let mut source = File::open(from)?;
+    let metadata = source.metadata()?;
+    let mut target = File::create(to)?;
+    if config.preallocate && metadata.len() > 0 {
+        preallocate(&target, metadata.len())?;
+    }
+
+    let mut buffer = AlignedBuffer::new(config.buffer_size);
+    let buffer = buffer.as_mut_slice();
+    let mut pending_hole = 0i64;
+    loop {
+        if token.is_cancelled() {
+            drop(target);
+            _ = fs::remove_file(to);
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+        }
+
+        let read = match source.read(buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        let chunk = &buffer[..read];
+        if chunk.iter().all(|&byte| byte == 0) {
+            // Skip over zeros instead of writing them, so they stay a hole in the copy.
+            pending_hole += read as i64;
+            continue;
+        }
+
+        if pending_hole > 0 {
+            target.seek(SeekFrom::Current(pending_hole))?;
+            pending_hole = 0;
+        }
+        target.write_all(chunk)?;
+    }
+
+    // A trailing hole has to be materialized through the length, as nothing is written after it.
+    target.set_len(metadata.len())?;
+    fs::set_permissions(to, metadata.permissions())?;
+
+    Ok(CopyMethod::Bytes)
+}
@@ -1,27 +1,48 @@
-use chrono::{DateTime, Local};
+use std::{ffi::OsStr, path::PathBuf, time::{Duration, Instant}};
 
-use command_core::{CommandError, CommandRegistry, COMMANDS};
+use chrono::{DateTime, FixedOffset};
+
+use command_core::{executables, output, Align, CancellationToken, CommandError, CommandOutput, CommandOverride, CommandRegistry, OutputFormat, Precedence, Table, COMMANDS};
 use command_macro::command;
+use log::info;
+
+use serde::Serialize;
 
-use colored::*;
+use crate::{autocorrect, call_executable, deterministic, dry_run, get_current_user, logging::{self, Verbosity}, prompt, session, theme::{self, Role, Theme}, tr, transcript};
 
-use crate::{get_current_user, println_current_user};
+#[derive(Serialize)]
+struct WorkingDirectory {
+    path: PathBuf,
+}
 
-#[command(name = "pwd", description = "Print the current directory")]
+impl CommandOutput for WorkingDirectory {
+    fn to_text(&self) -> String {
+        format!("{}\n", theme::paint(Role::Path, self.path.to_str().unwrap_or_default()))
+    }
+}
+
+#[command(name = "pwd", description = "Print the current directory", json)]
 pub fn cmd_pwd() -> Result<(), CommandError> {
     match std::env::current_dir() {
-        Ok(path) => {
-            println!("{}", path.to_str().unwrap_or_default().green());
-            Ok(())
-        }
+        Ok(path) => output::emit(&WorkingDirectory { path }),
         Err(e) => Err(CommandError::CommandFailed(format!("Error retrieving current directory: {}", e)))
     }
 }
 
-#[command(name = "whoami", description = "Print the current user")]
+#[derive(Serialize)]
+struct User {
+    name: String,
+}
+
+impl CommandOutput for User {
+    fn to_text(&self) -> String {
+        format!("{}\n", theme::paint(Role::User, &self.name))
+    }
+}
+
+#[command(name = "whoami", description = "Print the current user", json)]
 pub fn cmd_whoami() -> Result<(), CommandError> {
-    println_current_user!();
-    Ok(())
+    output::emit(&User { name: get_current_user() })
 }
 
 #[command(name = "cls", description = "Clears the screen")]
@@ -32,16 +53,44 @@ pub fn cmd_cls() -> Result<(), CommandError> {
     Ok(())
 }
 
-#[command(name = "time", description = "Shows the current time")]
+#[derive(Serialize)]
+struct Clock {
+    time: String,
+    #[serde(skip)]
+    now: DateTime<FixedOffset>,
+}
+
+impl CommandOutput for Clock {
+    fn to_text(&self) -> String {
+        format!("Time is {}\n", self.now.format("%H : %M : %S"))
+    }
+}
+
+#[command(name = "time", description = "Shows the current time", json)]
 pub fn cmd_time() -> Result<(), CommandError> {
-    let now: DateTime<Local> = Local::now();
-    println!("Time is {}", now.format("%H : %M : %S").to_string());
+    let now = deterministic::now();
+    output::emit(&Clock { time: now.to_rfc3339(), now })
+}
 
-    Ok(())
+#[command(name = "sleep", description = "Waits for the given duration", examples = ["sleep 500ms", "sleep 1h30m"])]
+pub fn cmd_sleep(duration: Duration) -> Result<(), CommandError> {
+    let token = CancellationToken::current();
+    let deadline = Instant::now() + duration;
+
+    loop {
+        token.check()?;
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(());
+        }
+        std::thread::sleep(remaining.min(Duration::from_millis(50)));
+    }
 }
 
 #[command(name = "exit", description = "Exit the shell", aliases = ["quit", "bye"])]
 pub fn cmd_exit() -> Result<(), CommandError> {
+    session::exiting();
     std::process::exit(0);
 }
 
@@ -50,28 +99,381 @@ pub fn cmd_help(command: Option<String>) -> Result<(), CommandError> {
     if let Some(command) = command {
         match CommandRegistry::find(command.as_str()) {
             Some(info) => {
-                println!("name: {}", info.name);
+                let mut text = format!("{}: {}\n", tr!("help.name"), info.name);
                 if !info.description.is_empty() {
-                    println!("description: {}", info.description);
+                    text.push_str(&format!("{}: {}\n", tr!("help.description"), info.description));
                 }
                 if !info.aliases.is_empty() {
-                    println!("aliases: {}", info.aliases.join(", "));
+                    text.push_str(&format!("{}: {}\n", tr!("help.aliases"), info.aliases.join(", ")));
+                }
+                text.push_str(&format!("{}: {}\n", tr!("help.usage"), info.usage));
+                if !info.examples.is_empty() {
+                    text.push_str(&format!("{}:\n", tr!("help.examples")));
+                    for example in info.examples {
+                        text.push_str(&format!("    {}\n", example));
+                    }
                 }
-                Ok(())
+                output::print(&text)
             }
-            None => Err(CommandError::CommandNotFound(command.to_string()))
+            None => Err(CommandError::not_found(&command))
         }
     } else {
-        println!();
-        for info in COMMANDS {
-            if info.description.is_empty() {
-                println!("{}", info.name);
-            } else {
-                println!("{}:\t{}", info.name, info.description);
+        let mut commands: Vec<_> = COMMANDS.iter().collect();
+        // Registration follows link order, which differs between builds.
+        if deterministic::is_enabled() {
+            commands.sort_by_key(|info| info.name);
+        }
+
+        let mut table = Table::new();
+        for info in commands {
+            table.push_row([info.name, info.description]);
+        }
+
+        output::print(&format!("\n{}\n", table))
+    }
+}
+
+#[command(
+    name = "command",
+    description = "Enable, disable or list overridden builtins, or set builtin/external precedence",
+    usage = "command [enable|disable|reset <name>] [precedence <builtin|external>]",
+    examples = ["command disable rm", "command enable rm", "command precedence external"]
+)]
+pub fn cmd_command(action: Option<&str>, target: Option<&str>) -> Result<(), CommandError> {
+    let Some(action) = action else {
+        let precedence = match CommandRegistry::precedence() {
+            Precedence::Builtin => "builtin",
+            Precedence::External => "external",
+        };
+        let mut text = format!("precedence: {}\n", precedence);
+
+        let mut table = Table::new();
+        for (name, value) in CommandRegistry::overrides() {
+            let state = match value {
+                CommandOverride::Disabled => theme::paint(Role::Disabled, "disabled"),
+                CommandOverride::Enabled => theme::paint(Role::Enabled, "enabled"),
+            };
+            table.push_row([name.to_string(), state]);
+        }
+        text.push_str(&table.render());
+        return output::print(&text);
+    };
+
+    match (action, target) {
+        ("precedence", Some(value)) => {
+            let precedence = match value {
+                "builtin" => Precedence::Builtin,
+                "external" => Precedence::External,
+                other => return Err(CommandError::InvalidArguments(format!("Unknown precedence '{}', expected 'builtin' or 'external'", other))),
+            };
+            CommandRegistry::set_precedence(precedence);
+            Ok(())
+        }
+        (action @ ("enable" | "disable" | "reset"), Some(name)) => {
+            let value = match action {
+                "enable" => Some(CommandOverride::Enabled),
+                "disable" => Some(CommandOverride::Disabled),
+                _ => None,
+            };
+
+            if value == Some(CommandOverride::Disabled) && CommandRegistry::index().get(name).is_some_and(|info| info.name == "command") {
+                return Err(CommandError::CommandFailed("The 'command' builtin cannot be disabled".to_string()));
             }
+
+            CommandRegistry::set_override(name, value).map(|_| ())
+        }
+        (action, _) => Err(CommandError::InvalidArguments(format!(
+            "Unknown usage of '{}', expected 'enable <name>', 'disable <name>', 'reset <name>' or 'precedence <builtin|external>'",
+            action
+        ))),
+    }
+}
+
+#[command(
+    name = "rehash",
+    description = "Rebuild the index of executables on PATH, e.g. after installing new tools",
+    aliases = ["hash"]
+)]
+pub fn cmd_rehash() -> Result<(), CommandError> {
+    let count = executables::rehash();
+    info!("Indexed {} executables on PATH", count);
+    Ok(())
+}
+
+#[command(
+    name = "which",
+    description = "Show what a command name runs: a builtin or the path of an executable",
+    usage = "which <name>...",
+    examples = ["which ls", "which cargo git"]
+)]
+pub fn cmd_which(names: Vec<&str>) -> Result<(), CommandError> {
+    let mut text = String::new();
+    let mut missing = Vec::new();
+
+    for name in names {
+        if let Some(info) = CommandRegistry::resolve(name)? {
+            text.push_str(&format!("{}: shell builtin '{}'\n", name, info.name));
+        } else if let Some(path) = executables::find_executable(name) {
+            text.push_str(&format!("{}\n", path.display()));
+        } else {
+            missing.push(name);
+        }
+    }
+
+    output::print(&text)?;
+    match missing.as_slice() {
+        [] => Ok(()),
+        [name] => Err(CommandError::not_found(name)),
+        names => {
+            let names: Vec<String> = names.iter().map(|name| format!("'{}'", name)).collect();
+            Err(CommandError::CommandFailed(format!("No commands named {}", names.join(", "))))
         }
-        println!();
+    }
+}
+
+#[command(
+    name = "output",
+    description = "Show or set the output format of builtins; JSON is newline-delimited, one record per line",
+    usage = "output [text|json]",
+    examples = ["output json", "ls --json"]
+)]
+pub fn cmd_output(format: Option<&str>) -> Result<(), CommandError> {
+    match format {
+        None => {
+            let name = match output::format() {
+                OutputFormat::Text => "text",
+                OutputFormat::Json => "json",
+            };
+            output::print(&format!("{}\n", name))?;
+        }
+        Some("text") => output::set_format(OutputFormat::Text),
+        Some("json") => output::set_format(OutputFormat::Json),
+        Some(other) => return Err(CommandError::InvalidArguments(format!("Unknown output format '{}', expected 'text' or 'json'", other))),
+    }
+    Ok(())
+}
+
+/// A `set` option: its name, and how to read and change it.
+pub type ShellOption = (&'static str, fn() -> bool, fn(bool));
+
+/// The options `set` shows and changes.
+pub const SHELL_OPTIONS: [ShellOption; 2] = [
+    ("dry-run", dry_run::is_enabled, dry_run::set_enabled),
+    ("autocorrect", autocorrect::is_enabled, autocorrect::set_enabled),
+];
+
+#[command(
+    name = "set",
+    description = "Show or change shell options",
+    usage = "set [dry-run|autocorrect [on|off]] | set transcript [on [file]|off]",
+    examples = ["set", "set dry-run on", "set autocorrect on", "set transcript on session.log", "set transcript off"]
+)]
+pub fn cmd_set(option: Option<&str>, value: Option<&str>, file: Option<PathBuf>) -> Result<(), CommandError> {
+    let options = SHELL_OPTIONS;
+    let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+
+    let Some(option) = option else {
+        let mut text: String = options.iter().map(|(name, get, _)| format!("{} {}\n", name, on_off(get()))).collect();
+        text.push_str(&format!("transcript {}\n", on_off(transcript::is_active())));
+        return output::print(&text);
+    };
+    // Not one of `SHELL_OPTIONS`, as turning it on takes a file and it isn't kept in sessions.
+    if option == "transcript" {
+        return transcript::set_option(value, file.as_deref());
+    }
+    let (_, get, set) = options.iter()
+        .find(|(name, _, _)| *name == option)
+        .ok_or_else(|| CommandError::InvalidArguments(format!("Unknown option '{}'", option)))?;
+    match value {
+        None => output::print(&format!("{}\n", on_off(get()))),
+        Some("on") => {
+            set(true);
+            Ok(())
+        }
+        Some("off") => {
+            set(false);
+            Ok(())
+        }
+        Some(other) => Err(CommandError::InvalidArguments(format!("Expected 'on' or 'off', got '{}'", other))),
+    }
+}
+
+#[command(
+    name = "verbosity",
+    description = "Show or set how much informational output the shell prints",
+    usage = "verbosity [quiet|normal|verbose|trace]",
+    examples = ["verbosity quiet", "verbosity verbose"]
+)]
+pub fn cmd_verbosity(level: Option<&str>) -> Result<(), CommandError> {
+    match level {
+        None => output::print(&format!("{}\n", logging::verbosity().name()))?,
+        Some(name) => {
+            let verbosity = Verbosity::from_name(name).ok_or_else(|| CommandError::InvalidArguments(format!(
+                "Unknown verbosity '{}', expected 'quiet', 'normal', 'verbose' or 'trace'",
+                name
+            )))?;
+            logging::set_verbosity(verbosity);
+        }
+    }
+    Ok(())
+}
+
+#[command(
+    name = "loglevel",
+    description = "Show or change the log filter, optionally per module",
+    usage = "loglevel [filter]",
+    examples = ["loglevel debug", "loglevel warn,shell::file_commands=trace"]
+)]
+pub fn cmd_loglevel(spec: Option<&str>) -> Result<(), CommandError> {
+    match spec {
+        None => output::print(&format!("{}\n", logging::filter())),
+        Some(spec) => logging::set_filter(spec),
+    }
+}
 
-        Ok(())
+#[command(
+    name = "prompt",
+    description = "Show where the prompt is configured, or reload its configuration",
+    usage = "prompt [reload]",
+    examples = ["prompt", "prompt reload"]
+)]
+pub fn cmd_prompt(action: Option<&str>) -> Result<(), CommandError> {
+    match action {
+        None => {
+            let text = match prompt::config_path() {
+                Some(path) if path.exists() => format!("{}\n", path.display()),
+                Some(path) => format!("{} (not found, using the [prompt] section of the configuration)\n", path.display()),
+                None => "the home directory could not be determined\n".to_string(),
+            };
+            output::print(&text)
+        }
+        Some("reload") => prompt::reload(),
+        Some(other) => Err(CommandError::InvalidArguments(format!("Unknown action '{}', expected 'reload'", other))),
+    }
+}
+
+#[command(
+    name = "theme",
+    description = "Preview the color themes or switch to one",
+    usage = "theme [name]",
+    examples = ["theme", "theme mono"]
+)]
+pub fn cmd_theme(name: Option<&str>) -> Result<(), CommandError> {
+    if let Some(name) = name {
+        return theme::set(name);
+    }
+
+    let current = theme::current();
+    let mut table = Table::new();
+    for name in theme::names() {
+        let Some(preview_theme) = Theme::builtin(name) else {
+            continue;
+        };
+
+        let marker = if name == current { "*" } else { " " };
+        let preview: Vec<String> = Role::ALL.iter()
+            .map(|&role| if theme::colors_enabled() {
+                preview_theme.paint_always(role, role.name())
+            } else {
+                role.name().to_string()
+            })
+            .collect();
+        table.push_row([format!("{} {}", marker, name), preview.join(" ")]);
+    }
+    output::print(&table.render())
+}
+
+#[derive(Serialize)]
+struct Benchmark {
+    command: String,
+    runs: usize,
+    warmup: usize,
+    min_ms: f64,
+    mean_ms: f64,
+    max_ms: f64,
+    stddev_ms: f64,
+}
+
+impl CommandOutput for Benchmark {
+    fn to_text(&self) -> String {
+        let millis = |value: f64| format!("{:.2} ms", value);
+        let mut table = Table::new()
+            .headers(["", "time"])
+            .align(1, Align::Right);
+        table.push_row(["min".to_string(), millis(self.min_ms)]);
+        table.push_row(["mean".to_string(), millis(self.mean_ms)]);
+        table.push_row(["max".to_string(), millis(self.max_ms)]);
+        table.push_row(["stddev".to_string(), millis(self.stddev_ms)]);
+
+        format!(
+            "{} ({} runs, {} warmup)\n{}",
+            theme::paint(Role::Heading, &self.command),
+            self.runs,
+            self.warmup,
+            table.render()
+        )
+    }
+}
+
+#[command(
+    name = "bench",
+    description = "Time a builtin or external command over several runs",
+    usage = "bench [-r <runs>] [-w <warmup>] <command> [args...]",
+    examples = ["bench ls", "bench -r 20 grep -r TODO src", "bench -r 5 -w 2 cargo --version"],
+    json
+)]
+pub fn cmd_bench(args: Vec<&OsStr>) -> Result<(), CommandError> {
+    let mut runs = 10;
+    let mut warmup = 1;
+
+    let mut rest = args.as_slice();
+    while let [flag, value, tail @ ..] = rest {
+        let target = match flag.to_str() {
+            Some("-r" | "--runs") => &mut runs,
+            Some("-w" | "--warmup") => &mut warmup,
+            _ => break,
+        };
+        *target = value.to_str()
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| CommandError::InvalidArguments(format!("{} needs a number", flag.to_string_lossy())))?;
+        rest = tail;
+    }
+
+    let Some((name, command_args)) = rest.split_first() else {
+        return Err(CommandError::InvalidArguments("Usage: bench [-r <runs>] [-w <warmup>] <command> [args...]".to_string()));
+    };
+    if runs == 0 {
+        return Err(CommandError::InvalidArguments("-r needs at least one run".to_string()));
     }
-}
\ No newline at end of file
+
+    let name = name.to_string_lossy();
+    let token = CancellationToken::current();
+    let mut times = Vec::with_capacity(runs);
+    for run in 0..warmup + runs {
+        token.check()?;
+        let started = Instant::now();
+        CommandRegistry::execute_or_else(&name, command_args, call_executable)
+            .map_err(|e| match e {
+                CommandError::Interrupted => e,
+                e => CommandError::CommandFailed(format!("Run {} of '{}' failed: {}", run + 1, name, e)),
+            })?;
+        // Warmup runs fill caches, so they aren't representative and are left out.
+        if run >= warmup {
+            times.push(started.elapsed().as_secs_f64() * 1000.0);
+        }
+    }
+
+    let mean = times.iter().sum::<f64>() / runs as f64;
+    let variance = times.iter().map(|time| (time - mean).powi(2)).sum::<f64>() / (runs.max(2) - 1) as f64;
+    let command = rest.iter().map(|arg| arg.to_string_lossy()).collect::<Vec<_>>().join(" ");
+
+    output::emit(&Benchmark {
+        command,
+        runs,
+        warmup,
+        min_ms: times.iter().copied().fold(f64::INFINITY, f64::min),
+        mean_ms: mean,
+        max_ms: times.iter().copied().fold(0.0, f64::max),
+        stddev_ms: variance.sqrt(),
+    })
+}
@@ -0,0 +1,56 @@
+use std::{sync::atomic::{AtomicBool, Ordering}, time::SystemTime};
+
+use chrono::{DateTime, FixedOffset, Local, TimeZone, Utc};
+
+/// Set by `--deterministic`: output that depends on the machine, the clock or the time zone is
+/// replaced by fixed values, so scripted runs produce the same bytes everywhere.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The instant the clock shows in deterministic mode: 2000-01-01 00:00:00 UTC.
+const FIXED_NOW: i64 = 946_684_800;
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// The current local time, or a fixed instant in UTC.
+pub fn now() -> DateTime<FixedOffset> {
+    if is_enabled() {
+        Utc.timestamp_opt(FIXED_NOW, 0).unwrap().fixed_offset()
+    } else {
+        Local::now().fixed_offset()
+    }
+}
+
+/// `time` in the local time zone, or in UTC so it doesn't depend on where the shell runs.
+pub fn local(time: impl Into<SystemTime>) -> DateTime<FixedOffset> {
+    let time = time.into();
+    if is_enabled() {
+        DateTime::<Utc>::from(time).fixed_offset()
+    } else {
+        DateTime::<Local>::from(time).fixed_offset()
+    }
+}
+
+/// A time recorded as RFC 3339, e.g. in a log, for display: in the local time zone, or the fixed
+/// instant, as it depends on when the recording command ran. Records keep the real time either way.
+pub fn recorded(time: &str) -> Option<DateTime<FixedOffset>> {
+    if is_enabled() {
+        return Some(now());
+    }
+    DateTime::parse_from_rfc3339(time).ok().map(|time| time.with_timezone(&Local).fixed_offset())
+}
+
+/// `name`, e.g. of the user or a file owner, or a placeholder.
+pub fn user(name: String) -> String {
+    if is_enabled() { "user".to_string() } else { name }
+}
+
+/// `name` of the host, or a placeholder.
+pub fn host(name: String) -> String {
+    if is_enabled() { "host".to_string() } else { name }
+}
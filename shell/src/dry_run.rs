@@ -0,0 +1,115 @@
+use std::{
+    ffi::OsStr,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex},
+};
+
+use command_core::{output, vfs::{self, Fs, FsMetadata}, CommandError, CommandMiddleware, Invocation, Next};
+
+use crate::tr;
+
+/// Set by `--dry-run` and `set dry-run on`: destructive builtins print what they would do instead.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// The filesystem [`DryRunFs`] replaced, put back when dry-run mode ends.
+static PREVIOUS: Mutex<Option<Arc<dyn Fs>>> = Mutex::new(None);
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn set_enabled(enabled: bool) {
+    if ENABLED.swap(enabled, Ordering::Relaxed) == enabled {
+        return;
+    }
+
+    let mut previous = PREVIOUS.lock().unwrap_or_else(|e| e.into_inner());
+    if enabled {
+        let current = vfs::current();
+        vfs::set(Arc::new(DryRunFs { inner: Arc::clone(&current) }));
+        *previous = Some(current);
+    } else if let Some(previous) = previous.take() {
+        vfs::set(previous);
+    }
+}
+
+fn announce(text: String) -> io::Result<()> {
+    output::print(&format!("{}\n", text)).map_err(|e| io::Error::other(e.to_string()))
+}
+
+/// Passes reads through and reports writes instead of making them, so every command using
+/// [`vfs::current`] is safe in dry-run mode without knowing about it.
+struct DryRunFs {
+    inner: Arc<dyn Fs>,
+}
+
+impl Fs for DryRunFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        self.inner.read_dir(path)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FsMetadata> {
+        self.inner.metadata(path)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        self.inner.metadata(path)?;
+        announce(tr!("fs.would_remove", path = path.display()))
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        announce(tr!("fs.would_create_dir", path = path.display()))
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + Send>> {
+        announce(tr!("fs.would_write", path = path.display()))?;
+        Ok(Box::new(io::sink()))
+    }
+
+    fn append(&self, path: &Path) -> io::Result<Box<dyn Write + Send>> {
+        announce(tr!("fs.would_write", path = path.display()))?;
+        Ok(Box::new(io::sink()))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.inner.metadata(from)?;
+        announce(tr!("fs.would_move", from = from.display(), to = to.display()))
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        self.inner.open(path)
+    }
+}
+
+/// In dry-run mode, tells builtins declared with `#[command(dry_run)]` to use `--dry-run` and reports external programs
+/// and output redirections instead of running them. Everything else runs on [`DryRunFs`].
+pub struct DryRunMiddleware;
+
+impl CommandMiddleware for DryRunMiddleware {
+    fn handle(&self, invocation: &Invocation, next: Next) -> Result<(), CommandError> {
+        if !is_enabled() {
+            return next.run(invocation);
+        }
+
+        let Some(info) = invocation.info else {
+            let line = std::iter::once(invocation.name.into())
+                .chain(invocation.args.iter().map(|arg| arg.to_string_lossy()))
+                .collect::<Vec<_>>()
+                .join(" ");
+            return output::print(&format!("{}\n", tr!("fs.would_run", command = line)));
+        };
+
+        let redirect = invocation.args.windows(2).find(|pair| pair[0] == ">" || pair[0] == ">>");
+        if let Some(pair) = redirect {
+            return output::print(&format!("{}\n", tr!("fs.would_write", path = Path::new(pair[1]).display())));
+        }
+
+        if !info.dry_run || invocation.args.iter().any(|arg| *arg == "--dry-run") {
+            return next.run(invocation);
+        }
+        let args: Vec<&OsStr> = std::iter::once(OsStr::new("--dry-run"))
+            .chain(invocation.args.iter().copied())
+            .collect();
+        next.run(&Invocation { args: &args, ..*invocation })
+    }
+}
@@ -0,0 +1,299 @@
+use std::{
+    char::REPLACEMENT_CHARACTER,
+    ffi::OsStr,
+    fs,
+    io::{self, Cursor, Read, Write},
+    path::PathBuf,
+};
+
+use command_core::{input, output, vfs, CommandError};
+use command_macro::command;
+
+/// A text encoding `iconv` converts between and `cat` decodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+impl Encoding {
+    pub const ALL: [Encoding; 4] = [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be, Encoding::Latin1];
+
+    /// The encoding called `name`, ignoring case, e.g. `utf8`, `UTF-16LE` or `latin1`. A bare
+    /// `utf-16` is little-endian, as Windows writes it.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" => Some(Encoding::Utf8),
+            "utf-16" | "utf16" | "utf-16le" | "utf16le" => Some(Encoding::Utf16Le),
+            "utf-16be" | "utf16be" => Some(Encoding::Utf16Be),
+            "latin-1" | "latin1" | "iso-8859-1" | "iso8859-1" | "l1" => Some(Encoding::Latin1),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Latin1 => "ISO-8859-1",
+        }
+    }
+
+    /// The byte order mark text in this encoding may start with; Latin-1 has none.
+    pub fn bom(self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8 => &[0xEF, 0xBB, 0xBF],
+            Encoding::Utf16Le => &[0xFF, 0xFE],
+            Encoding::Utf16Be => &[0xFE, 0xFF],
+            Encoding::Latin1 => &[],
+        }
+    }
+
+    fn is_utf16(self) -> bool {
+        matches!(self, Encoding::Utf16Le | Encoding::Utf16Be)
+    }
+}
+
+/// The encoding a byte order mark at the start of `data` names, with the length of the mark.
+pub fn detect_bom(data: &[u8]) -> Option<(Encoding, usize)> {
+    [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be].into_iter()
+        .find(|encoding| data.starts_with(encoding.bom()))
+        .map(|encoding| (encoding, encoding.bom().len()))
+}
+
+/// The UTF-16 code units in `data`, without a trailing odd byte.
+fn units(data: &[u8], encoding: Encoding) -> impl Iterator<Item = u16> + '_ {
+    data.chunks_exact(2).map(move |pair| match encoding {
+        Encoding::Utf16Be => u16::from_be_bytes([pair[0], pair[1]]),
+        _ => u16::from_le_bytes([pair[0], pair[1]]),
+    })
+}
+
+/// `data` in `encoding` as text. An invalid sequence is an error naming its offset, or with
+/// `skip_invalid` it is left out.
+pub fn decode(data: &[u8], encoding: Encoding, skip_invalid: bool) -> Result<String, CommandError> {
+    let invalid = |offset: usize| CommandError::CommandFailed(format!(
+        "Invalid {} input at byte {}",
+        encoding.name(),
+        offset
+    ));
+
+    match encoding {
+        Encoding::Utf8 => match std::str::from_utf8(data) {
+            Ok(text) => Ok(text.to_string()),
+            Err(_) if skip_invalid => Ok(data.utf8_chunks().map(|chunk| chunk.valid()).collect()),
+            Err(e) => Err(invalid(e.valid_up_to())),
+        },
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let mut text = String::with_capacity(data.len() / 2);
+            let mut offset = 0;
+            for c in char::decode_utf16(units(data, encoding)) {
+                match c {
+                    Ok(c) => {
+                        text.push(c);
+                        offset += c.len_utf16() * 2;
+                    }
+                    Err(_) if skip_invalid => offset += 2,
+                    Err(_) => return Err(invalid(offset)),
+                }
+            }
+            if !data.len().is_multiple_of(2) && !skip_invalid {
+                return Err(invalid(data.len() - 1));
+            }
+            Ok(text)
+        }
+        Encoding::Latin1 => Ok(data.iter().map(|&byte| char::from(byte)).collect()),
+    }
+}
+
+/// `text` in `encoding`. A character Latin-1 can't represent is an error, or with `skip_invalid`
+/// it is left out.
+pub fn encode(text: &str, encoding: Encoding, skip_invalid: bool) -> Result<Vec<u8>, CommandError> {
+    match encoding {
+        Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+        Encoding::Utf16Le => Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+        Encoding::Utf16Be => Ok(text.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+        Encoding::Latin1 => {
+            let mut bytes = Vec::with_capacity(text.len());
+            for c in text.chars() {
+                match u8::try_from(c) {
+                    Ok(byte) => bytes.push(byte),
+                    Err(_) if skip_invalid => {}
+                    Err(_) => return Err(CommandError::CommandFailed(format!(
+                        "'{}' can't be represented in {}",
+                        c,
+                        encoding.name()
+                    ))),
+                }
+            }
+            Ok(bytes)
+        }
+    }
+}
+
+/// Decodes UTF-16 from `inner` to UTF-8 as it is read, with invalid units shown as `�`.
+struct Utf16Reader<R> {
+    inner: R,
+    encoding: Encoding,
+    /// Read bytes not decoded yet: half a unit, or a high surrogate whose pair hasn't been read.
+    pending: Vec<u8>,
+    decoded: Vec<u8>,
+    position: usize,
+    finished: bool,
+}
+
+impl<R: Read> Utf16Reader<R> {
+    fn decode_pending(&mut self) {
+        let mut end = self.pending.len() - self.pending.len() % 2;
+        if !self.finished && end >= 2 {
+            let last = units(&self.pending[end - 2..end], self.encoding).next().unwrap_or_default();
+            if (0xD800..0xDC00).contains(&last) {
+                end -= 2;
+            }
+        }
+
+        let mut text: String = char::decode_utf16(units(&self.pending[..end], self.encoding))
+            .map(|c| c.unwrap_or(REPLACEMENT_CHARACTER))
+            .collect();
+        if self.finished && end < self.pending.len() {
+            text.push(REPLACEMENT_CHARACTER);
+            end = self.pending.len();
+        }
+        self.pending.drain(..end);
+        self.decoded = text.into_bytes();
+        self.position = 0;
+    }
+}
+
+impl<R: Read> Read for Utf16Reader<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.decoded.len() && !self.finished {
+            let mut chunk = [0; 8 * 1024];
+            let read = self.inner.read(&mut chunk)?;
+            self.finished = read == 0;
+            self.pending.extend_from_slice(&chunk[..read]);
+            self.decode_pending();
+        }
+
+        let available = &self.decoded[self.position..];
+        let count = available.len().min(buffer.len());
+        buffer[..count].copy_from_slice(&available[..count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+/// `reader` as UTF-8 for display: a UTF-8 byte order mark is dropped, and text starting with a
+/// UTF-16 one is decoded. Anything else is passed through unchanged.
+pub fn decoding_reader(mut reader: Box<dyn Read>) -> io::Result<Box<dyn Read>> {
+    let mut head = Vec::with_capacity(3);
+    reader.by_ref().take(3).read_to_end(&mut head)?;
+    let (encoding, bom) = detect_bom(&head).unwrap_or((Encoding::Utf8, 0));
+    let rest = Cursor::new(head.split_off(bom)).chain(reader);
+
+    Ok(if encoding.is_utf16() {
+        Box::new(Utf16Reader {
+            inner: rest,
+            encoding,
+            pending: Vec::new(),
+            decoded: Vec::new(),
+            position: 0,
+            finished: false,
+        })
+    } else {
+        Box::new(rest)
+    })
+}
+
+/// The encoding named after `flag`, and whether it is a bare `utf-16`, which leaves the byte order
+/// to a byte order mark.
+fn parse_encoding(name: Option<&OsStr>, flag: &str) -> Result<(Encoding, bool), CommandError> {
+    let name = name.and_then(OsStr::to_str)
+        .ok_or_else(|| CommandError::InvalidArguments(format!("{} needs an encoding", flag)))?;
+    let encoding = Encoding::from_name(name).ok_or_else(|| CommandError::InvalidArguments(format!(
+        "Unknown encoding '{}', see 'iconv -l'",
+        name
+    )))?;
+    Ok((encoding, matches!(name.to_ascii_lowercase().as_str(), "utf-16" | "utf16")))
+}
+
+#[command(
+    name = "iconv",
+    description = "Convert text between UTF-8, UTF-16LE, UTF-16BE and Latin-1",
+    usage = "iconv [-f <from>] [-t <to>] [-c] [--bom] [-o <out>] [file...] | iconv -l",
+    examples = [
+        "iconv -f utf-16le -t utf-8 windows.log",
+        "iconv -f latin1 -o notes.txt old-notes.txt",
+        "iconv -t utf-16 --bom -o for-excel.csv data.csv",
+        "iconv -l"
+    ]
+)]
+pub fn cmd_iconv(args: Vec<&OsStr>) -> Result<(), CommandError> {
+    let mut from = None;
+    let mut to = (Encoding::Utf8, false);
+    let mut skip_invalid = false;
+    let mut write_bom = false;
+    let mut out_path = None;
+    let mut files = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("-l" | "--list") => {
+                let names: String = Encoding::ALL.iter().map(|encoding| format!("{}\n", encoding.name())).collect();
+                return output::print(&names);
+            }
+            Some("-f" | "--from-code") => from = Some(parse_encoding(args.next(), "-f")?),
+            Some("-t" | "--to-code") => to = parse_encoding(args.next(), "-t")?,
+            Some("-c") => skip_invalid = true,
+            Some("--bom") => write_bom = true,
+            Some("-o" | "--output") => {
+                out_path = Some(PathBuf::from(args.next().ok_or_else(|| {
+                    CommandError::InvalidArguments("-o needs a file name".to_string())
+                })?));
+            }
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+
+    let mut inputs = Vec::new();
+    if files.is_empty() {
+        let mut data = Vec::new();
+        input::stdin().read_to_end(&mut data)?;
+        inputs.push(data);
+    }
+    for file in &files {
+        inputs.push(fs::read(file).map_err(|e| CommandError::FileReadError(file.clone(), e))?);
+    }
+
+    // Without `-f`, or with a bare `utf-16`, a byte order mark decides; a mark matching the
+    // source encoding is dropped either way rather than converted into the text.
+    let mut text = String::new();
+    for data in &inputs {
+        let (encoding, bom) = match (from, detect_bom(data)) {
+            (None, found) => found.unwrap_or((Encoding::Utf8, 0)),
+            (Some((_, true)), Some((found, bom))) if found.is_utf16() => (found, bom),
+            (Some((from, _)), Some((found, bom))) if found == from => (found, bom),
+            (Some((from, _)), _) => (from, 0),
+        };
+        text.push_str(&decode(&data[bom..], encoding, skip_invalid)?);
+    }
+
+    let (to, bare_utf16) = to;
+    let mut converted = Vec::with_capacity(text.len());
+    if write_bom || bare_utf16 {
+        converted.extend_from_slice(to.bom());
+    }
+    converted.extend(encode(&text, to, skip_invalid)?);
+
+    match out_path {
+        // Through the virtual filesystem, so dry-run mode and a restricted root apply.
+        Some(path) => vfs::current().create(&path)
+            .and_then(|mut file| file.write_all(&converted).and_then(|()| file.flush()))
+            .map_err(|e| CommandError::CommandFailed(format!("Could not write '{}': {}", path.display(), e))),
+        None => output::write_data(&converted),
+    }
+}
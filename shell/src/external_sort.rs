@@ -0,0 +1,182 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    env,
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+static NEXT_RUN: AtomicUsize = AtomicUsize::new(0);
+
+/// Sorts more names than should be held in memory at once: every `run_size` names are sorted and
+/// spilled to a temporary file, and the files are merged when reading the result back.
+pub struct ExternalSort {
+    run_size: usize,
+    descending: bool,
+    current: Vec<OsString>,
+    runs: Vec<PathBuf>,
+}
+
+impl ExternalSort {
+    pub fn new(run_size: usize, descending: bool) -> Self {
+        Self {
+            run_size,
+            descending,
+            current: Vec::new(),
+            runs: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, name: OsString) -> io::Result<()> {
+        self.current.push(name);
+        if self.current.len() >= self.run_size {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn sort_current(&mut self) {
+        self.current.sort();
+        if self.descending {
+            self.current.reverse();
+        }
+    }
+
+    /// Writes the names collected so far to a new run, each as its length and its encoded bytes.
+    fn spill(&mut self) -> io::Result<()> {
+        self.sort_current();
+
+        let path = env::temp_dir().join(format!(
+            "shell-sort-{}-{}",
+            std::process::id(),
+            NEXT_RUN.fetch_add(1, Ordering::Relaxed)
+        ));
+        self.runs.push(path.clone());
+
+        let mut writer = BufWriter::new(File::create(&path)?);
+        for name in self.current.drain(..) {
+            let bytes = name.as_encoded_bytes();
+            writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+            writer.write_all(bytes)?;
+        }
+        writer.flush()
+    }
+
+    /// The names in order. Nothing touches the disk when they all fit into one run.
+    pub fn finish(mut self) -> io::Result<Sorted> {
+        if self.runs.is_empty() {
+            self.sort_current();
+            return Ok(Sorted::InMemory(std::mem::take(&mut self.current).into_iter()));
+        }
+        if !self.current.is_empty() {
+            self.spill()?;
+        }
+
+        let mut readers = Vec::with_capacity(self.runs.len());
+        for path in &self.runs {
+            readers.push(BufReader::new(File::open(path)?));
+        }
+
+        let mut merge = Merge {
+            readers,
+            heap: BinaryHeap::new(),
+            descending: self.descending,
+            paths: std::mem::take(&mut self.runs),
+        };
+        for run in 0..merge.readers.len() {
+            merge.refill(run)?;
+        }
+        Ok(Sorted::Merged(merge))
+    }
+}
+
+impl Drop for ExternalSort {
+    fn drop(&mut self) {
+        for path in &self.runs {
+            _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Reads the next name of a run, or `None` at its end.
+fn read_name(reader: &mut impl Read) -> io::Result<Option<OsString>> {
+    let mut len = [0; 8];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let mut bytes = vec![0; u64::from_le_bytes(len) as usize];
+    reader.read_exact(&mut bytes)?;
+    // Safety: the bytes were written from `as_encoded_bytes` by this process.
+    Ok(Some(unsafe { OsString::from_encoded_bytes_unchecked(bytes) }))
+}
+
+/// Heap entry ordering the smallest name first, or the largest when descending.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Head {
+    Ascending(Reverse<OsString>, usize),
+    Descending(OsString, usize),
+}
+
+/// The runs of an [`ExternalSort`] merged back into one sequence.
+pub struct Merge {
+    readers: Vec<BufReader<File>>,
+    heap: BinaryHeap<Head>,
+    descending: bool,
+    paths: Vec<PathBuf>,
+}
+
+impl Merge {
+    fn refill(&mut self, run: usize) -> io::Result<()> {
+        if let Some(name) = read_name(&mut self.readers[run])? {
+            self.heap.push(if self.descending {
+                Head::Descending(name, run)
+            } else {
+                Head::Ascending(Reverse(name), run)
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Merge {
+    type Item = io::Result<OsString>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (name, run) = match self.heap.pop()? {
+            Head::Ascending(Reverse(name), run) | Head::Descending(name, run) => (name, run),
+        };
+        Some(self.refill(run).map(|_| name))
+    }
+}
+
+impl Drop for Merge {
+    fn drop(&mut self) {
+        // Windows does not delete files that are still open.
+        self.readers.clear();
+        for path in &self.paths {
+            _ = fs::remove_file(path);
+        }
+    }
+}
+
+pub enum Sorted {
+    InMemory(std::vec::IntoIter<OsString>),
+    Merged(Merge),
+}
+
+impl Iterator for Sorted {
+    type Item = io::Result<OsString>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Sorted::InMemory(names) => names.next().map(Ok),
+            Sorted::Merged(merge) => merge.next(),
+        }
+    }
+}
@@ -1,13 +1,14 @@
-use std::{env, fs::{self}, io::{self, Write}, os::windows::fs::MetadataExt, path::{Path, PathBuf}, sync::Mutex};
+use std::{borrow::Cow, collections::HashSet, env, ffi::{OsStr, OsString}, fs::{self}, io::{self, IsTerminal, Write}, path::{Path, PathBuf}, sync::{atomic::{AtomicU64, Ordering}, Mutex}, time::UNIX_EPOCH};
 
-use command_core::CommandError;
+use command_core::{input, output, vfs::{self, FileKind}, Align, CancellationToken, CommandError, CommandOutput, OutputFormat, Table};
 use command_macro::command;
 use log::{error, info, warn};
 
-use crate::{get_current_user, println_current_dir};
+use crate::{audit, autocorrect, columns::{self, Cell}, config, copy::{self, CopyMethod}, deterministic, encoding, external_sort::ExternalSort, finder, get_current_user, glob, highlight::Highlighter, ls_colors::LsColors, i18n, metadata::{self, is_hidden, same_file, Dereference, FileDetails}, paths, permissions, println_current_dir, progress::Progress, remove, theme::{self, Role}, tr, trash, walk::Walk};
 
-use colored::*;
 use humansize::{format_size, DECIMAL};
+use rayon::prelude::*;
+use serde::Serialize;
 
 macro_rules! parent_flag_patterns {
     () => {
@@ -20,9 +21,14 @@ macro_rules! verbose_flag_patterns {
     };
 }
 
-fn is_directory_empty(path: &Path) -> io::Result<bool> {
-    let mut entries = fs::read_dir(path)?;
-    Ok(entries.next().is_none())
+/// Whether `path` holds nothing but entries in `removed`, which are normalized absolute paths.
+fn is_empty_after(path: &Path, removed: &HashSet<PathBuf>) -> io::Result<bool> {
+    for entry in vfs::current().read_dir(path)? {
+        if !removed.contains(&paths::normalize(&entry)) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
 }
 
 #[command(name = "cd", description = "Print the current directory, or change it")]
@@ -31,8 +37,19 @@ pub fn cmd_cd(path: Option<PathBuf>) -> Result<(), CommandError> {
         let curr_dir = env::current_dir()
             .map_err(|e| CommandError::CommandFailed(format!("Failed to get current directory: {e}")))?;
     
-        let mut new_dir = PathBuf::from(curr_dir);
-        new_dir.push(path);
+        let mut new_dir = paths::normalize(&curr_dir.join(&path));
+        if !new_dir.is_dir() {
+            if let Some(correction) = autocorrect::directory(&path) {
+                if !autocorrect::accept(&correction.to_string_lossy())? {
+                    return Err(CommandError::CommandFailed(format!(
+                        "Error changing directory: '{}' is not a directory. Did you mean '{}'?",
+                        path.display(),
+                        correction.display()
+                    )));
+                }
+                new_dir = paths::normalize(&curr_dir.join(correction));
+            }
+        }
     
         env::set_current_dir(&new_dir)
             .map(|_| println_current_dir!())
@@ -47,13 +64,21 @@ lazy_static::lazy_static! {
     static ref DIR_STACK: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
 }
 
+/// The directories `pushd` saved, oldest first.
+pub fn dir_stack() -> Vec<PathBuf> {
+    DIR_STACK.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+pub fn set_dir_stack(stack: Vec<PathBuf>) {
+    *DIR_STACK.lock().unwrap_or_else(|e| e.into_inner()) = stack;
+}
+
 #[command(name = "pushd", description = "Save current directory and change to new one")]
 pub fn cmd_pushd(target: PathBuf) -> Result<(), CommandError> {
     let curr_dir = env::current_dir()
         .map_err(|e| CommandError::CommandFailed(format!("Failed to get current directory: {e}")))?;
 
-    let mut new_dir = PathBuf::from(&curr_dir);
-    new_dir.push(target);
+    let new_dir = paths::normalize(&curr_dir.join(target));
 
     env::set_current_dir(&new_dir)
         .map_err(|e| CommandError::CommandFailed(format!("Error changing directory: {}", e)))?;
@@ -68,7 +93,7 @@ pub fn cmd_pushd(target: PathBuf) -> Result<(), CommandError> {
 
 #[command(name = "popd", description = "Pop directory from stack and change to it")]
 pub fn cmd_popd() -> Result<(), CommandError> {
-    let mut stack = DIR_STACK.lock().unwrap();
+    let mut stack = DIR_STACK.lock().unwrap_or_else(|e| e.into_inner());
     let dir = stack.pop()
         .ok_or_else(|| CommandError::CommandFailed("Directory stack is empty".to_string()))?;
 
@@ -78,26 +103,36 @@ pub fn cmd_popd() -> Result<(), CommandError> {
 }
 
 #[command(name = "touch", description = "Makes a new empty file")]
-pub fn cmd_touch(files: Vec<String>) -> Result<(), CommandError> {
-    use fs::File;
-
+pub fn cmd_touch(files: Vec<PathBuf>) -> Result<(), CommandError> {
+    let filesystem = vfs::current();
     for file in &files {
-        File::create(file)
-            .map(|_| ())
-            .map_err(|e| CommandError::CommandFailed(format!("Could not create file '{}': {e}", file)))?;
+        let existed = filesystem.metadata(file).is_ok();
+        filesystem.create(file)
+            // Only a file that now exists was created, e.g. not in dry-run mode.
+            .and_then(|_| if existed || filesystem.metadata(file).is_err() { Ok(()) } else { permissions::apply_umask(file) })
+            .map_err(|e| CommandError::CommandFailed(format!("Could not create file '{}': {e}", file.display())))?;
     }
 
     Ok(())
 }
 
-#[command(name = "mkdir", description = "Makes a new directory")]
+#[command(
+    name = "mkdir",
+    description = "Makes a new directory",
+    usage = "mkdir [-p|--parents] [-v|--verbose] [-m|--mode <mode>] [-n|--dry-run] <dir>...",
+    examples = ["mkdir build", "mkdir -p src/nested/module", "mkdir -m 700 private", "mkdir -m go-rwx private"],
+    dry_run
+)]
 pub fn cmd_mkdir(args: Vec<&str>) -> Result<(), CommandError> {
    let mut parents = false; 
    let mut verbose = false;
+   let mut mode = None;
+   let mut dry_run = false;
 
    let mut dirs = Vec::new();
+   let mut args = args.into_iter();
 
-    for cmd in args {
+    while let Some(cmd) = args.next() {
         match cmd {
             parent_flag_patterns!() => {
                 parents = true;
@@ -105,6 +140,14 @@ pub fn cmd_mkdir(args: Vec<&str>) -> Result<(), CommandError> {
             verbose_flag_patterns!() => {
                 verbose = true;
             }
+            "-m" | "--mode" => {
+                let value = args.next()
+                    .ok_or_else(|| CommandError::InvalidArguments("Missing mode after -m".to_string()))?;
+                mode = Some(permissions::ModeChange::parse(value)?);
+            }
+            "-n" | "--dry-run" => {
+                dry_run = true;
+            }
             file => {
                 dirs.push(Path::new(file));
             }
@@ -112,34 +155,138 @@ pub fn cmd_mkdir(args: Vec<&str>) -> Result<(), CommandError> {
     }
     
     for dir in &dirs {
-        if parents {
-            fs::create_dir_all(dir)
-        } else {
-            fs::create_dir(dir)
+        if dry_run {
+            output::print(&format!("{}\n", tr!("fs.would_create_dir", path = dir.display())))?;
+            continue;
         }
-        .map_err(|e| CommandError::CommandFailed(format!("Failed to make directory '{}': {e}", dir.display())))?;
+        permissions::create_dir(dir, parents, mode.as_ref())
+        .map_err(|e| CommandError::CommandFailed(tr!("error.create_dir", path = dir.display(), error = e)))?;
 
         if verbose {
-            info!("Created directory '{}'", dir.display());
+            info!("{}", tr!("fs.created_dir", path = dir.display()));
         }
     }
 
     Ok(())
 }
 
+#[command(
+    name = "umask",
+    description = "Show or set the permissions removed from files created by builtins",
+    usage = "umask [-S] [mode]",
+    examples = ["umask", "umask -S", "umask 077"]
+)]
+pub fn cmd_umask(args: Vec<&str>) -> Result<(), CommandError> {
+    let mut symbolic = false;
+    let mut mask = None;
+
+    for arg in args {
+        match arg {
+            "-S" => symbolic = true,
+            value => mask = Some(permissions::parse_octal(value)?),
+        }
+    }
+
+    if let Some(mask) = mask {
+        permissions::set_umask(mask);
+        return Ok(());
+    }
+
+    let mask = permissions::umask();
+    if symbolic {
+        output::print(&format!("{}\n", permissions::symbolic(!mask & 0o777)))
+    } else {
+        output::print(&format!("{:04o}\n", mask))
+    }
+}
+
+/// Applies `change` to `path`, and with `recursively` to everything below it without following links.
+fn change_mode(path: &Path, change: &permissions::ModeChange, recursively: bool, verbose: bool, failures: &mut Vec<String>) {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) => return failures.push(format!("Cannot access '{}': {}", path.display(), e)),
+    };
+
+    let old = permissions::Mode::from_metadata(&metadata);
+    let new = change.apply(old, metadata.is_dir());
+    if new != old {
+        match permissions::set(path, new) {
+            Ok(()) if verbose => info!("Changed mode of '{}' to {:04o}", path.display(), new.bits),
+            Ok(()) => {}
+            Err(e) => failures.push(format!("Cannot change mode of '{}': {}", path.display(), e)),
+        }
+    }
+
+    if !recursively || !metadata.is_dir() {
+        return;
+    }
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(e) => return failures.push(format!("Cannot read '{}': {}", path.display(), e)),
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        if entry.file_type().is_ok_and(|t| !t.is_symlink()) {
+            change_mode(&entry.path(), change, recursively, verbose, failures);
+        }
+    }
+}
+
+#[command(
+    name = "chmod",
+    description = "Change permissions, or the readonly and hidden attributes on Windows",
+    aliases = ["attrib"],
+    usage = "chmod [-R|--recursive] [-v|--verbose] <mode> <path>...",
+    examples = ["chmod 644 notes.txt", "chmod u+x,go-w build.sh", "chmod -R a-w release", "chmod +h desktop.ini"]
+)]
+pub fn cmd_chmod(args: Vec<&OsStr>) -> Result<(), CommandError> {
+    let mut recursively = false;
+    let mut verbose = false;
+    let mut mode = None;
+    let mut targets = Vec::new();
+
+    for arg in args {
+        match arg.to_str() {
+            Some("-R" | "--recursive") => recursively = true,
+            Some(verbose_flag_patterns!()) => verbose = true,
+            // `-w` and `-x` are modes, not flags.
+            Some(text) if mode.is_none() => mode = Some(permissions::ModeChange::parse(text)?),
+            _ => targets.push(Path::new(arg)),
+        }
+    }
+
+    let Some(mode) = mode.filter(|_| !targets.is_empty()) else {
+        return Err(CommandError::InvalidArguments("Usage: chmod [-R] <mode> <path>...".to_string()));
+    };
+
+    let token = CancellationToken::current();
+    let mut failures = Vec::new();
+    for target in targets {
+        token.check()?;
+        change_mode(target, &mode, recursively, verbose, &mut failures);
+    }
+
+    match failures.len() {
+        0 => Ok(()),
+        1 => Err(CommandError::CommandFailed(failures.remove(0))),
+        count => Err(CommandError::CommandFailed(format!(
+            "{} paths could not be changed:\n    {}",
+            count,
+            failures.join("\n    ")
+        ))),
+    }
+}
+
 macro_rules! remove_interactive_common {
     ($interactive:expr, $path:expr, $verbose:expr) => {
         if $interactive {
-            print!("Remove '{}'? [y/N]: ", $path.display());
-            io::stdout().flush().unwrap();
-    
+            output::print(&tr!("confirm.remove", path = $path.display()))?;
+
             let mut input = String::new();
-            io::stdin().read_line(&mut input).unwrap();
+            io::stdin().read_line(&mut input)?;
     
-            let input = input.trim().to_lowercase();
-            if input != "y" && input != "yes" {
+            if !i18n::is_yes(&input) {
                 if $verbose {
-                    info!("Skipped '{}'", $path.display());
+                    info!("{}", tr!("fs.skipped", path = $path.display()));
                 }
                 continue;
             }
@@ -147,47 +294,88 @@ macro_rules! remove_interactive_common {
     };
 }
 
-#[command(name = "rmdir", description = "Removes a given directory (if empty)")]
-pub fn cmd_rmdir(args: Vec<&str>) -> Result<(), CommandError> {
+#[command(
+    name = "rmdir",
+    description = "Removes a given directory (if empty)",
+    usage = "rmdir [-p|--parents] [-i|--interactive] [-v|--verbose] [-n|--dry-run] <dir>...",
+    examples = ["rmdir build", "rmdir -p src/nested/module", "rmdir -p --dry-run src/nested/module"],
+    dry_run
+)]
+pub fn cmd_rmdir(args: Vec<&OsStr>) -> Result<(), CommandError> {
     let mut parents = false;
     let mut interactive = false;
     let mut verbose = false;
+    let mut dry_run = false;
     let mut dirs = Vec::new();
 
     for cmd in args {
-        match cmd {
-            parent_flag_patterns!() => {
+        match cmd.to_str() {
+            Some(parent_flag_patterns!()) => {
                 parents = true;
             }
-            "-i" | "--interactive" => {
+            Some("-i" | "--interactive") => {
                 interactive = true;
             }
-            verbose_flag_patterns!() => {
+            Some(verbose_flag_patterns!()) => {
                 verbose = true;
             }
-            file => {
-                dirs.push(Path::new(file));
+            Some("-n" | "--dry-run") => {
+                dry_run = true;
+            }
+            _ => {
+                dirs.push(Path::new(cmd));
             }
         }
     }
 
+    let cwd = env::current_dir()?;
+    let filesystem = vfs::current();
+    let boundaries = config::get().rmdir.boundary.directories(&cwd);
+    // Directories removed so far, or that would be with `--dry-run`, so parents left with only those count as empty.
+    let mut removed = HashSet::new();
+
+    let remove = |dir: &Path, removed: &mut HashSet<PathBuf>| -> Result<(), CommandError> {
+        if dry_run {
+            output::print(&format!("{}\n", tr!("fs.would_remove", path = dir.display())))?;
+        } else {
+            // Only empty directories are removed, so a file given by mistake is an error rather than removed.
+            let is_dir = filesystem.metadata(dir).map(|metadata| metadata.is_dir());
+            match is_dir {
+                Ok(true) => filesystem.remove(dir),
+                Ok(false) => Err(io::Error::new(io::ErrorKind::NotADirectory, "Not a directory")),
+                Err(e) => Err(e),
+            }
+            .map_err(|e| CommandError::CommandFailed(tr!("error.remove_dir", path = dir.display(), error = e)))?;
+            if verbose {
+                info!("{}", tr!("fs.removed", path = dir.display()));
+            }
+        }
+        removed.insert(paths::normalize(&cwd.join(dir)));
+        Ok(())
+    };
+
     for dir in &dirs {
         remove_interactive_common!(interactive, dir, verbose);
-        
-        fs::remove_dir(dir)
-            .map_err(|e| CommandError::CommandFailed(format!("Failed to remove directory '{}': {e}", dir.display())))?;
-    
+
+        if dry_run && !is_empty_after(dir, &removed)? {
+            return Err(CommandError::CommandFailed(tr!("error.remove_dir", path = dir.display(), error = "Directory not empty")));
+        }
+        remove(dir, &mut removed)?;
+
         if parents {
-            let mut parent_opt = dir.parent();
+            let mut parent_opt = dir.parent().filter(|parent| !parent.as_os_str().is_empty());
             while let Some(parent) = parent_opt {
-                if !is_directory_empty(parent).unwrap_or(false) {
+                // Never prune the starting directory, the home directory or anything above them.
+                let absolute = paths::normalize(&cwd.join(parent));
+                if boundaries.iter().any(|boundary| boundary.starts_with(&absolute)) {
+                    break;
+                }
+                if !is_empty_after(parent, &removed).unwrap_or(false) {
                     break;
                 }
 
-                parent_opt = parent.parent();
-    
-                fs::remove_dir(parent)
-                    .map_err(|e| CommandError::CommandFailed(format!("Failed to remove directory '{}': {e}", dir.display())))?;
+                remove(parent, &mut removed)?;
+                parent_opt = parent.parent().filter(|parent| !parent.as_os_str().is_empty());
             }
         }
     }
@@ -195,147 +383,968 @@ pub fn cmd_rmdir(args: Vec<&str>) -> Result<(), CommandError> {
     Ok(())
 }
 
-#[command(name = "rm", description = "Removes a given file or directory (with its contents)")]
-pub fn cmd_rm(args: Vec<&str>) -> Result<(), CommandError> {
+/// Lets the user check which of `paths` to `action`, or returns `None` if they cancelled.
+fn pick_paths(action: &str, paths: Vec<PathBuf>) -> Result<Option<Vec<PathBuf>>, CommandError> {
+    let items: Vec<String> = paths.iter().map(|path| path.display().to_string()).collect();
+    let Some(checked) = finder::select_many(action, &items)? else {
+        return Ok(None);
+    };
+    let mut paths: Vec<Option<PathBuf>> = paths.into_iter().map(Some).collect();
+    Ok(Some(checked.into_iter().filter_map(|index| paths[index].take()).collect()))
+}
+
+#[command(
+    name = "rm",
+    description = "Removes a given file or directory (with its contents)",
+    usage = "rm [-r|--recursive] [-d|--dir] [-i|--interactive] [-I|--pick] [-f|--force] [-v|--verbose] [-n|--dry-run] [--trash|--no-trash] [--sequential] <path|glob>...",
+    examples = ["rm notes.txt", "rm -r -i target", "rm -f *.tmp", "rm -I *.log", "rm --trash draft.txt"],
+    dry_run
+)]
+pub fn cmd_rm(args: Vec<&OsStr>) -> Result<(), CommandError> {
     let mut recursively = false;
     let mut interactive = false;
+    let mut pick = false;
+    let mut force = false;
     let mut verbose = false;
-    let mut paths = Vec::new();
+    let mut to_trash = config::get().trash.rm;
+    let mut parallel = true;
+    let mut dry_run = false;
+    let mut patterns = Vec::new();
 
     for cmd in args {
-        match cmd {
-            "-r" | "-R" | "--recursive" => {
-                recursively = true;
-            }
-            "-i" | "--interactive" => {
-                interactive = true;
+        match cmd.to_str() {
+            Some("--recursive") => recursively = true,
+            Some("--interactive") => interactive = true,
+            Some("-I" | "--pick") => pick = true,
+            Some("--dir") => recursively = false,
+            Some("--force") => force = true,
+            Some("--verbose") => verbose = true,
+            Some("--trash") => to_trash = true,
+            Some("--no-trash") => to_trash = false,
+            Some("--sequential") => parallel = false,
+            Some("--dry-run") => dry_run = true,
+            Some(flags) if flags.len() > 1 && flags.starts_with('-') && flags[1..].chars().all(|c| "rRdifnv".contains(c)) => {
+                for flag in flags[1..].chars() {
+                    match flag {
+                        'r' | 'R' => recursively = true,
+                        'd' => recursively = false,
+                        'i' => interactive = true,
+                        'f' => force = true,
+                        'n' => dry_run = true,
+                        _ => verbose = true,
+                    }
+                }
             }
-            "-d" | "--dir" => {
-                recursively = false;
+            _ => {
+                // Kept as the original OsStr so names that aren't valid UTF-8 are removed as given.
+                patterns.push(cmd);
             }
-            verbose_flag_patterns!() => {
-                verbose = true;
+        }
+    }
+
+    let token = CancellationToken::current();
+    let filesystem = vfs::current();
+    let mut failures = Vec::new();
+
+    let mut targets = Vec::with_capacity(patterns.len());
+    for pattern in patterns {
+        if !glob::is_pattern(pattern) || Path::new(pattern).exists() {
+            targets.push(PathBuf::from(pattern));
+            continue;
+        }
+
+        let matches = glob::expand(pattern)?;
+        if matches.is_empty() && !force {
+            failures.push(format!("No matches for '{}'", Path::new(pattern).display()));
+        }
+        targets.extend(matches);
+    }
+    if pick {
+        let Some(picked) = pick_paths("remove", targets)? else {
+            return Ok(());
+        };
+        targets = picked;
+    }
+
+    for path in targets {
+        token.check()?;
+
+        let path = path.as_path();
+        if filesystem.metadata(path).is_err() {
+            if !force {
+                failures.push(tr!("error.missing_path", path = path.display()));
             }
-            path => {
-                paths.push(Path::new(path));
+            continue;
+        }
+
+        remove_interactive_common!(interactive && !force, path, verbose);
+
+        // rm never follows links: removing a link to a directory leaves the directory alone.
+        let is_dir = Dereference::Never.is_dir(path, true);
+        if is_dir && !recursively {
+            failures.push(tr!("error.is_directory", path = path.display()));
+            continue;
+        }
+
+        if dry_run {
+            output::print(&format!("{}\n", tr!("fs.would_remove", path = path.display())))?;
+            continue;
+        }
+
+        if to_trash {
+            match trash::move_to_trash(path) {
+                Ok(id) if verbose => info!("Moved '{}' to the trash as '{}'", path.display(), id),
+                Ok(_) => {}
+                Err(e) => failures.push(e.to_string()),
             }
+            continue;
+        }
+
+        let removed = if is_dir {
+            remove::remove_tree(path, parallel, &token)
+        } else {
+            filesystem.remove(path)
+        };
+
+        match removed {
+            Ok(()) if verbose => info!("{}", tr!("fs.removed", path = path.display())),
+            Ok(()) => {}
+            Err(e) => failures.push(tr!("error.remove", path = path.display(), error = e)),
         }
     }
 
-    for path in paths {
-        if !path.exists() {
-            return Err(CommandError::CommandFailed(format!(
-                "Path '{}' doesn't exist",
-                path.to_string_lossy()
-            )));
-        }
-        
-        remove_interactive_common!(interactive, path, verbose);
-
-        if path.is_dir() {
-            if recursively {
-                fs::remove_dir_all(&path)
-            } else {
-                return Err(CommandError::CommandFailed(format!(
-                    "Cannot remove directory '{}': is a directory (use -r)",
-                    path.display()
-                )));
+    token.check()?;
+    match failures.len() {
+        0 => Ok(()),
+        1 => Err(CommandError::CommandFailed(failures.remove(0))),
+        count => Err(CommandError::CommandFailed(format!(
+            "{} paths could not be removed:\n    {}",
+            count,
+            failures.join("\n    ")
+        ))),
+    }
+}
+
+/// Size of the buffer `cat` streams through.
+const CAT_BUFFER_SIZE: usize = 64 * 1024;
+/// Inputs up to this size are shown through the pager; larger ones are streamed.
+const CAT_PAGER_LIMIT: u64 = 1024 * 1024;
+
+enum CatSource<'a> {
+    Stdin,
+    File(&'a Path),
+}
+
+impl CatSource<'_> {
+    fn name(&self) -> String {
+        match self {
+            CatSource::Stdin => "stdin".to_string(),
+            CatSource::File(path) => path.file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "?".into()),
+        }
+    }
+
+    fn open(&self) -> Result<Box<dyn io::Read>, CommandError> {
+        match self {
+            CatSource::Stdin => Ok(input::stdin()),
+            CatSource::File(path) => vfs::current().open(path)
+                .map(|file| file as Box<dyn io::Read>)
+                .map_err(|e| CommandError::CommandFailed(format!("Failed to open file `{}`: {e}", path.display()))),
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            CatSource::Stdin => None,
+            CatSource::File(path) => Some(path),
+        }
+    }
+
+    /// Opens the source as UTF-8 to show, decoding UTF-16 text with a byte order mark.
+    fn open_decoded(&self) -> Result<Box<dyn io::Read>, CommandError> {
+        encoding::decoding_reader(self.open()?)
+            .map_err(|e| CommandError::CommandFailed(format!("Error reading file: {e}")))
+    }
+}
+
+/// Which lines `cat -n` and `cat -b` number.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Numbering {
+    All,
+    NonBlank,
+}
+
+/// Numbers lines passing through in chunks, continuing from one file to the next like `cat`.
+struct LineNumbers {
+    numbering: Numbering,
+    next: usize,
+    at_line_start: bool,
+}
+
+impl LineNumbers {
+    fn new(numbering: Numbering) -> Self {
+        Self { numbering, next: 1, at_line_start: true }
+    }
+
+    /// The number in front of a line, unless it is blank and only non-blank lines are numbered.
+    fn prefix(&mut self, blank: bool) -> Option<String> {
+        if blank && self.numbering == Numbering::NonBlank {
+            return None;
+        }
+        let prefix = format!("{:>6}\t", self.next);
+        self.next += 1;
+        Some(prefix)
+    }
+
+    /// `chunk` with numbers in front of the lines starting in it.
+    fn apply<'a>(&mut self, chunk: &'a [u8]) -> Cow<'a, [u8]> {
+        let mut numbered = Vec::with_capacity(chunk.len() + 64);
+        for &byte in chunk {
+            if self.at_line_start {
+                if let Some(prefix) = self.prefix(byte == b'\n') {
+                    numbered.extend_from_slice(prefix.as_bytes());
+                }
             }
-        } else {
-            fs::remove_file(&path)
+            numbered.push(byte);
+            self.at_line_start = byte == b'\n';
         }
-        .map_err(|e| CommandError::CommandFailed(format!("Failed to remove '{}': {e}", path.display())))?;
+        Cow::Owned(numbered)
+    }
+}
 
-        if verbose {
-            info!("Removed '{}'", path.display());
+/// `chunk` numbered by `numbers`, if lines are numbered.
+fn numbered<'a>(numbers: Option<&mut LineNumbers>, chunk: &'a [u8]) -> Cow<'a, [u8]> {
+    match numbers {
+        Some(numbers) => numbers.apply(chunk),
+        None => Cow::Borrowed(chunk),
+    }
+}
+
+/// Copies `reader` to `write` in fixed-size chunks, returning the number of bytes copied.
+fn stream(
+    reader: &mut dyn io::Read,
+    mut write: impl FnMut(&[u8]) -> Result<(), CommandError>,
+    token: &CancellationToken,
+) -> Result<u64, CommandError> {
+    let mut buffer = vec![0; CAT_BUFFER_SIZE];
+    let mut total = 0;
+
+    loop {
+        token.check()?;
+
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(total),
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(CommandError::CommandFailed(format!("Error reading file: {e}"))),
+        };
+
+        write(&buffer[..read])?;
+        total += read as u64;
+    }
+}
+
+/// A path in the same directory as `path` for writing its replacement, so the final rename stays on one filesystem.
+fn temporary_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+/// How `cp` copies: into directories with `recursively`, and links as links unless `dereference` follows them.
+struct CopyOptions {
+    recursively: bool,
+    verbose: bool,
+    dereference: Dereference,
+    engine: copy::CopyConfig,
+}
+
+/// Copies `from` to `to`; `argument` is set for sources named on the command line. Directories and
+/// links are created right away, while regular files are added to `files` to be copied together after.
+fn copy_path(
+    from: &Path,
+    to: &Path,
+    argument: bool,
+    options: &CopyOptions,
+    files: &mut Vec<(PathBuf, PathBuf)>,
+    token: &CancellationToken,
+) -> Result<(), CommandError> {
+    token.check()?;
+
+    let source_metadata = options.dereference.metadata(from, argument)
+        .map_err(|e| CommandError::FileReadError(from.to_path_buf(), e))?;
+
+    if source_metadata.is_symlink() {
+        metadata::copy_symlink(from, to)
+            .map_err(|e| CommandError::CommandFailed(format!("Failed to copy link '{}' to '{}': {e}", from.display(), to.display())))?;
+        if options.verbose {
+            info!("'{}' -> '{}' (link)", from.display(), to.display());
+        }
+        return Ok(());
+    }
+
+    if source_metadata.is_dir() {
+        if !options.recursively {
+            return Err(CommandError::CommandFailed(format!("'{}' is a directory (use -r)", from.display())));
         }
+
+        fs::create_dir_all(to)
+            .map_err(|e| CommandError::CommandFailed(tr!("error.create_dir", path = to.display(), error = e)))?;
+        let entries = fs::read_dir(from).map_err(|e| CommandError::DirectoryReadError(from.to_path_buf(), e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| CommandError::DirectoryReadError(from.to_path_buf(), e))?;
+            copy_path(&entry.path(), &to.join(entry.file_name()), false, options, files, token)?;
+        }
+        return Ok(());
     }
 
+    files.push((from.to_path_buf(), to.to_path_buf()));
     Ok(())
 }
 
-#[command(name = "cat", description = "Output given files, create if doesn't exist")]
-pub fn cmd_cat(args: Vec<&str>) -> Result<(), CommandError> {
-    use std::fs::{File, OpenOptions};
-    use std::io::{Read, Write};
-    use std::path::Path;
+/// Copies the files `copy_path` collected, several at a time when `--threads` allows, which is
+/// what speeds up trees of many small files.
+fn copy_files(files: &[(PathBuf, PathBuf)], options: &CopyOptions, token: &CancellationToken) -> Result<(), CommandError> {
+    let size = |from: &PathBuf| fs::metadata(from).map_or(0, |metadata| metadata.len());
+    let progress = Progress::bytes(files.iter().map(|(from, _)| size(from)).sum(), "Copying");
+    let copy_one = |(from, to): &(PathBuf, PathBuf)| -> Result<(), CommandError> {
+        token.check()?;
+        let method = copy::copy_file(from, to, &options.engine, token).map_err(|e| match e.kind() {
+            io::ErrorKind::Interrupted => CommandError::Interrupted,
+            _ => CommandError::CommandFailed(format!("Failed to copy '{}' to '{}': {e}", from.display(), to.display())),
+        })?;
 
-    let mut files: Vec<(&Path, Vec<u8>)> = Vec::with_capacity(args.len());
-    let mut args = args.iter().peekable();
-    let mut output_redirected = false;
+        if options.verbose {
+            let how = match method {
+                CopyMethod::Reflink => "cloned",
+                CopyMethod::Bytes => "copied",
+            };
+            info!("'{}' -> '{}' ({})", from.display(), to.display(), how);
+        }
+        progress.inc(size(from));
+        Ok(())
+    };
 
-    while let Some(&arg) = args.next() {
-        match arg {
-            ">" | ">>" => {
-                output_redirected = true;
+    if options.engine.threads <= 1 || files.len() <= 1 {
+        return files.iter().try_for_each(copy_one);
+    }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.engine.threads)
+        .build()
+        .map_err(|e| CommandError::CommandFailed(e.to_string()))?;
+    pool.install(|| files.par_iter().try_for_each(copy_one))
+}
+
+#[command(
+    name = "cp",
+    description = "Copies files, or directories with -r, using copy-on-write clones where supported",
+    usage = "cp [-r|--recursive] [-L|-H|-P] [-I|--pick] [-v|--verbose] [--dry-run] [--threads <n>] [--buffer-size <bytes>] [--preallocate] <source>... <dest>",
+    examples = ["cp notes.txt notes.bak", "cp -r src backup", "cp a.txt b.txt docs", "cp -I *.log archive", "cp -r --threads 16 node_modules /mnt/backup"],
+    dry_run
+)]
+pub fn cmd_cp(args: Vec<&OsStr>) -> Result<(), CommandError> {
+    let mut recursively = false;
+    let mut verbose = false;
+    let mut dereference = None;
+    let mut engine = config::get().copy;
+    let mut dry_run = false;
+    let mut pick = false;
+    let mut paths = Vec::new();
 
-                let Some(&path_str) = args.peek() else {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some(flag @ ("--threads" | "--buffer-size")) => {
+                let value = args.next()
+                    .and_then(|value| value.to_str())
+                    .and_then(|value| value.parse().ok())
+                    .filter(|&value: &usize| value > 0)
+                    .ok_or_else(|| CommandError::InvalidArguments(format!("{} needs a positive number", flag)))?;
+                match flag {
+                    "--threads" => engine.threads = value,
+                    _ => engine.buffer_size = value,
+                }
+            }
+            Some("--preallocate") => engine.preallocate = true,
+            Some("-I" | "--pick") => pick = true,
+            Some("--dry-run") => dry_run = true,
+            Some("-r" | "-R" | "--recursive") => recursively = true,
+            Some(verbose_flag_patterns!()) => verbose = true,
+            Some(flag) if Dereference::from_flag(flag).is_some() => dereference = Dereference::from_flag(flag),
+            _ => paths.push(Path::new(arg)),
+        }
+    }
+
+    // Checked after parsing, so a buffer size from the configuration file is limited as well.
+    if engine.buffer_size > copy::MAX_BUFFER_SIZE {
+        return Err(CommandError::InvalidArguments(format!(
+            "The buffer size can be at most {} bytes, got {}",
+            copy::MAX_BUFFER_SIZE,
+            engine.buffer_size
+        )));
+    }
+
+    // Like other cp implementations: copy links as links when recursing, otherwise copy what they point to.
+    let options = CopyOptions {
+        recursively,
+        verbose,
+        dereference: dereference.unwrap_or(if recursively { Dereference::Never } else { Dereference::Always }),
+        engine,
+    };
+
+    let Some((dest, sources)) = paths.split_last().filter(|(_, sources)| !sources.is_empty()) else {
+        return Err(CommandError::InvalidArguments("Expected at least one source and a destination".to_string()));
+    };
+    let picked;
+    let sources: Vec<&Path> = if pick {
+        let matches = sources.iter().flat_map(|source| audit::expand(source.as_os_str())).collect();
+        match pick_paths("copy", matches)? {
+            Some(paths) => {
+                picked = paths;
+                picked.iter().map(PathBuf::as_path).collect()
+            }
+            None => return Ok(()),
+        }
+    } else {
+        sources.to_vec()
+    };
+    if sources.is_empty() {
+        return Ok(());
+    }
+
+    let token = CancellationToken::current();
+    let into_directory = dest.is_dir() || sources.len() > 1;
+    if into_directory && !dest.is_dir() {
+        return Err(CommandError::CommandFailed(format!("Target '{}' is not a directory", dest.display())));
+    }
+
+    let mut files = Vec::new();
+    for source in &sources {
+        let target = match source.file_name() {
+            Some(name) if into_directory => dest.join(name),
+            _ => dest.to_path_buf(),
+        };
+
+        if same_file(source, &target) {
+            return Err(CommandError::CommandFailed(format!("'{}' and '{}' are the same file", source.display(), target.display())));
+        }
+        let inside_source = match (source.canonicalize(), target.parent().map(Path::canonicalize)) {
+            (Ok(source), Some(Ok(parent))) => source.is_dir() && parent.starts_with(source),
+            _ => false,
+        };
+        if inside_source {
+            return Err(CommandError::CommandFailed(format!("Cannot copy '{}' into itself", source.display())));
+        }
+
+        if dry_run {
+            output::print(&format!("{}\n", tr!("fs.would_copy", from = source.display(), to = target.display())))?;
+            continue;
+        }
+        copy_path(source, &target, true, &options, &mut files, &token)?;
+    }
+
+    copy_files(&files, &options, &token)
+}
+
+#[command(
+    name = "trash",
+    description = "List or restore items removed with 'rm --trash', or empty the trash",
+    usage = "trash [list|restore <id>|empty]",
+    examples = ["trash list", "trash restore 20240101-120000", "trash empty"]
+)]
+pub fn cmd_trash(action: Option<&str>, id: Option<&str>) -> Result<(), CommandError> {
+    match (action.unwrap_or("list"), id) {
+        ("list", None) => {
+            let items = trash::list()?;
+            if items.is_empty() {
+                info!("The trash is empty");
+                return Ok(());
+            }
+
+            let mut table = Table::new().headers(["ID", "DELETED", "ORIGINAL PATH"]);
+            for item in items {
+                let deleted = deterministic::recorded(&item.info.deleted)
+                    .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or(item.info.deleted);
+                table.push_row([item.id, deleted, item.info.original.display().to_string()]);
+            }
+            output::print(&table.render())
+        }
+        ("restore", Some(id)) => {
+            let path = trash::restore(id)?;
+            info!("Restored '{}'", path.display());
+            Ok(())
+        }
+        ("empty", None) => {
+            let count = trash::empty()?;
+            info!("Deleted {} items from the trash", count);
+            Ok(())
+        }
+        (action, _) => Err(CommandError::InvalidArguments(format!(
+            "Unknown usage of '{}', expected 'list', 'restore <id>' or 'empty'",
+            action
+        ))),
+    }
+}
+
+#[command(
+    name = "cat",
+    description = "Output given files, create if doesn't exist",
+    usage = "cat [-n|-b] [--highlight] <file|->... [> <out>|>> <out>]",
+    examples = ["cat Cargo.toml", "cat -n --highlight src/main.rs", "cat a.txt b.txt > both.txt", "cat - >> log.txt", "cat <<EOF > notes.txt"]
+)]
+pub fn cmd_cat(args: Vec<&OsStr>) -> Result<(), CommandError> {
+    let mut sources = Vec::with_capacity(args.len());
+    let mut redirect = None;
+    let mut numbering = None;
+    let mut highlight = false;
+    let mut args = args.iter();
+
+    while let Some(&arg) = args.next() {
+        match arg.to_str() {
+            Some(mode @ (">" | ">>")) => {
+                let Some(&out_path) = args.next() else {
                     return Err(CommandError::CommandFailed("Missing file name after redirection".into()));
                 };
-                args.next(); // consume the path
-
-                let mut options = OpenOptions::new();
-                
-                options
-                    .write(true)
-                    .create(true);
-
-                match arg {
-                    ">" => { options.truncate(true); }
-                    ">>" => { options.append(true); }
-                    _ => unreachable!(),
+                redirect = Some((mode, Path::new(out_path)));
+            }
+            Some("-") => sources.push(CatSource::Stdin),
+            Some("-n" | "--number") => numbering = numbering.or(Some(Numbering::All)),
+            // Numbering only non-blank lines wins over numbering all, as in GNU cat.
+            Some("-b" | "--number-nonblank") => numbering = Some(Numbering::NonBlank),
+            Some("--highlight") => highlight = true,
+            _ => {
+                // Opened through the original OsStr; only the messages show the name lossily.
+                let path = Path::new(arg);
+                let is_file = match vfs::current().metadata(path) {
+                    // A link counts as what it points to, as that is what gets opened.
+                    Ok(metadata) if metadata.kind == FileKind::Symlink => path.is_file(),
+                    Ok(metadata) => metadata.is_file(),
+                    Err(_) => false,
+                };
+                if !is_file {
+                    warn!("file '{}' does not exist", path.display());
+                    continue;
                 }
+                sources.push(CatSource::File(path));
+            }
+        }
+    }
+    // `cat <<EOF` reads the here-document.
+    if sources.is_empty() && input::provided().is_some() {
+        sources.push(CatSource::Stdin);
+    }
+
+    let token = CancellationToken::current();
+    let mut numbers = numbering.map(LineNumbers::new);
 
-                let mut output_file = options.open(path_str)
-                    .map_err(|e| CommandError::CommandFailed(format!("Could not open output file `{path_str}`: {e}")))?;
+    if let Some((mode, out_path)) = redirect {
+        let conflict = sources.iter().any(|source| matches!(source, CatSource::File(path) if same_file(path, out_path)));
+        if conflict && mode == ">>" {
+            return Err(CommandError::CommandFailed(format!("'{}': input file is output file", out_path.display())));
+        }
+
+        // Truncating an input before reading it would lose its contents, so write a temporary file
+        // next to it and move that over the original once everything has been copied. A link is
+        // written through, so the file it points to is replaced rather than the link.
+        let replaced = if conflict && out_path.is_symlink() { out_path.canonicalize()? } else { out_path.to_path_buf() };
+        let write_path = if conflict { temporary_path(&replaced) } else { replaced.clone() };
 
-                for (_, contents) in &mut files {
-                    output_file.write_all(contents)
-                        .map_err(|e| CommandError::CommandFailed(format!("Error writing to output file: {e}")))?;
+        let filesystem = vfs::current();
+        let created = filesystem.metadata(&write_path).is_err();
+        let opened = match mode {
+            ">" => filesystem.create(&write_path),
+            _ => filesystem.append(&write_path),
+        };
+        let mut output_file = opened
+            .and_then(|file| match permissions::apply_umask(&write_path) {
+                // A file that isn't on the disk, e.g. in memory, has no attributes to set.
+                Err(e) if created && e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(file),
+            })
+            .map_err(|e| CommandError::CommandFailed(format!("Could not open output file `{}`: {e}", write_path.display())))?;
+        if conflict {
+            // The replacement keeps the original's permissions; there is nothing to set in dry-run mode.
+            match fs::metadata(&replaced).and_then(|metadata| fs::set_permissions(&write_path, metadata.permissions())) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    drop(output_file);
+                    _ = filesystem.remove(&write_path);
+                    return Err(CommandError::CommandFailed(format!("Could not copy the permissions of `{}`: {e}", out_path.display())));
                 }
+                _ => {}
             }
-            path_str => match path_str {
-                "-" => {
-                    let mut contents = String::new();
-                    io::stdin()
-                        .read_to_string(&mut contents)
-                        .map_err(|e| CommandError::CommandFailed(format!("Failed to read from stdin: {e}")))?;
+        }
 
-                    files.push((Path::new("stdin"), contents.into_bytes()));
-                }
-                _ => {
-                    let path = Path::new(path_str);
-                    if !path.is_file() {
-                        warn!("file '{}' does not exist", path.display());
-                        continue;
-                    }
+        let copied = sources.iter().try_for_each(|source| {
+            stream(&mut *source.open()?, |chunk| {
+                output_file.write_all(&numbered(numbers.as_mut(), chunk))
+                    .map_err(|e| CommandError::CommandFailed(format!("Error writing to output file: {e}")))
+            }, &token).map(|_| ())
+        });
+        drop(output_file);
 
-                    let mut file = File::open(path)
-                        .map_err(|e| CommandError::CommandFailed(format!("Failed to open file `{path_str}`: {e}")))?;
+        if conflict {
+            match copied {
+                Ok(()) => filesystem.rename(&write_path, &replaced)
+                    .map_err(|e| CommandError::CommandFailed(format!("Could not replace `{}`: {e}", out_path.display())))?,
+                Err(_) => _ = filesystem.remove(&write_path),
+            }
+        }
+        return copied;
+    }
 
-                    let mut contents = Vec::new();
-                    file.read_to_end(&mut contents)
-                        .map_err(|e| CommandError::CommandFailed(format!("Error reading file: {e}")))?;
+    if !io::stdout().is_terminal() {
+        // Piped or redirected by the caller: pass the contents through without headers.
+        return output::with_data_writer(|out| {
+            for source in &sources {
+                stream(&mut *source.open()?, |chunk| {
+                    out.write_all(&numbered(numbers.as_mut(), chunk)).map_err(CommandError::from)
+                }, &token)?;
+            }
+            Ok(())
+        });
+    }
 
-                    files.push((path, contents));
+    let header = |source: &CatSource| theme::paint(Role::Heading, &format!("[{}]", source.name()));
+    let total_size: u64 = sources.iter()
+        .map(|source| match source {
+            CatSource::File(path) => fs::metadata(path).map_or(0, |metadata| metadata.len()),
+            CatSource::Stdin => 0,
+        })
+        .sum();
+
+    if total_size > CAT_PAGER_LIMIT {
+        // Too large to hold for the pager: stream each file under its header, numbered but not
+        // highlighted.
+        return output::with_data_writer(|out| {
+            for source in &sources {
+                write!(out, "\n{}\n\n", header(source))?;
+                let copied = stream(&mut *source.open_decoded()?, |chunk| {
+                    out.write_all(&numbered(numbers.as_mut(), chunk)).map_err(CommandError::from)
+                }, &token)?;
+                if copied == 0 {
+                    info!("{}", tr!("fs.empty_file", name = source.name()));
                 }
+                out.write_all(b"\n")?;
             }
+            Ok(())
+        });
+    }
+
+    let mut text = String::new();
+    for source in &sources {
+        let mut contents = Vec::new();
+        stream(&mut *source.open_decoded()?, |chunk| {
+            contents.extend_from_slice(chunk);
+            Ok(())
+        }, &token)?;
+
+        if contents.is_empty() {
+            info!("{}", tr!("fs.empty_file", name = source.name()));
+            continue;
         }
+
+        let contents = String::from_utf8_lossy(&contents);
+        let mut highlighter = if highlight && theme::colors_enabled() {
+            Highlighter::for_file(source.path(), contents.lines().next().unwrap_or_default())
+        } else {
+            None
+        };
+        let body = if numbers.is_none() && highlighter.is_none() {
+            contents.into_owned()
+        } else {
+            contents.split_inclusive('\n')
+                .map(|line| {
+                    let prefix = numbers.as_mut()
+                        .and_then(|numbers| numbers.prefix(line == "\n"))
+                        .map(|prefix| theme::paint(Role::Muted, &prefix))
+                        .unwrap_or_default();
+                    let line = match highlighter.as_mut() {
+                        Some(highlighter) => highlighter.line(line),
+                        None => line.to_string(),
+                    };
+                    format!("{}{}", prefix, line)
+                })
+                .collect()
+        };
+        text.push_str(&format!("\n{}\n\n{}\n", header(source), body));
+    }
+
+    output::print(&text)
+}
+
+/// An entry as `ls` shows it: read from its directory, or looked up again by name after an external sort.
+struct LsEntry {
+    name: OsString,
+    path: PathBuf,
+    /// Metadata of the entry itself, not of what a link points to.
+    link_metadata: io::Result<fs::Metadata>,
+}
+
+impl LsEntry {
+    fn from_dir_entry(entry: &fs::DirEntry) -> Self {
+        Self {
+            name: entry.file_name(),
+            path: entry.path(),
+            link_metadata: entry.metadata(),
+        }
+    }
+
+    fn from_name(dir: &Path, name: OsString) -> Self {
+        let path = dir.join(&name);
+        Self {
+            link_metadata: fs::symlink_metadata(&path),
+            name,
+            path,
+        }
+    }
+
+    fn file_type(&self) -> Option<fs::FileType> {
+        self.link_metadata.as_ref().ok().map(fs::Metadata::file_type)
+    }
+
+    fn is_hidden(&self) -> bool {
+        self.link_metadata.as_ref().is_ok_and(|metadata| is_hidden(&self.name, metadata))
+    }
+
+    /// Metadata of the entry, or of what it links to when `dereference` follows links.
+    fn metadata(&self, dereference: Dereference) -> io::Result<fs::Metadata> {
+        if dereference.follows(false) {
+            fs::metadata(&self.path)
+        } else {
+            self.link_metadata.as_ref()
+                .map(Clone::clone)
+                .map_err(|e| io::Error::new(e.kind(), e.to_string()))
+        }
+    }
+}
+
+fn classify_suffix(entry: &LsEntry) -> &'static str {
+    match entry.file_type() {
+        Some(file_type) if file_type.is_dir() => "/",
+        Some(file_type) if file_type.is_symlink() => "@",
+        _ => "",
+    }
+}
+
+fn entry_name(entry: &LsEntry, classify: bool) -> String {
+    let mut name = entry.name.to_string_lossy().into_owned();
+    if classify {
+        name.push_str(classify_suffix(entry));
+    }
+    name
+}
+
+/// The entry's name colored by file type, or plain when colors are disabled.
+fn painted_name(entry: &LsEntry, classify: bool, dereference: Dereference) -> String {
+    let name = entry.name.to_string_lossy().into_owned();
+    let mut painted = match (LsColors::get(), entry.metadata(dereference)) {
+        (Some(colors), Ok(metadata)) => colors.paint(&name, &entry.path, &metadata),
+        _ => name,
+    };
+    if classify {
+        painted.push_str(classify_suffix(entry));
     }
+    painted
+}
 
-    if !output_redirected {
-        for (path, contents) in &files {
-            let name = path.file_name()
-                .map(|n| n.to_string_lossy())
-                .unwrap_or_else(|| "?".into());
+/// `ls -l` rows: mode, links, owner, size, modification time and name, aligned per column.
+fn long_listing(entries: &[LsEntry], classify: bool, dereference: Dereference) -> String {
+    let mut table = Table::new()
+        .align(1, Align::Right)
+        .align(3, Align::Right);
 
-            let text = String::from_utf8_lossy(contents);
-            if text.len() > 0 {
-                println!();
-                info!("[{}]", name);
-                print!("\n{}\n", text);
-            } else {
-                info!("File '{}' is empty.", name);
+    for entry in entries {
+        let name = painted_name(entry, classify, dereference);
+        match entry.metadata(dereference) {
+            Ok(metadata) => {
+                let details = FileDetails::from_metadata(&metadata);
+                let modified = details.modified
+                    .map(|time| deterministic::local(time).format("%b %e %H:%M").to_string())
+                    .unwrap_or_else(|| "-".to_string());
+                table.push_row([details.mode, details.links.to_string(), deterministic::user(details.owner), format_size(details.size, DECIMAL), modified, name]);
+            }
+            Err(_) => table.push_row(["?".into(), "?".into(), "?".into(), "?".into(), "?".into(), name]),
+        }
+    }
+
+    table.render()
+}
+
+#[derive(Default)]
+struct LsOptions {
+    all: bool,
+    classify: bool,
+    one_per_line: bool,
+    long: bool,
+    sort_by_time: bool,
+    sort_by_size: bool,
+    unsorted: bool,
+    reverse: bool,
+    recursive: bool,
+    dereference: Dereference,
+}
+
+impl LsOptions {
+    /// Applies a short flag such as `l`, returning `false` if it isn't an `ls` flag.
+    fn set_short(&mut self, flag: char) -> bool {
+        match flag {
+            'a' => self.all = true,
+            'F' => self.classify = true,
+            '1' => self.one_per_line = true,
+            'l' => self.long = true,
+            't' => self.sort_by_time = true,
+            'S' => self.sort_by_size = true,
+            'U' => self.unsorted = true,
+            'r' => self.reverse = true,
+            'R' => self.recursive = true,
+            'L' => self.dereference = Dereference::Always,
+            'H' => self.dereference = Dereference::CommandLine,
+            'P' => self.dereference = Dereference::Never,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Whether entries are listed in plain name order, which an external sort can produce.
+    fn sorts_by_name(&self) -> bool {
+        !self.unsorted && !self.sort_by_time && !self.sort_by_size
+    }
+}
+
+/// Entries read before `ls` switches to streaming, so ordinary directories keep the pager and one column layout.
+const LS_STREAM_THRESHOLD: usize = 10_000;
+/// Entries rendered together when streaming; columns and `-l` alignment hold within a batch.
+const LS_BATCH_SIZE: usize = 1_000;
+
+fn sort_entries(entries: &mut [LsEntry], options: &LsOptions) {
+    if options.unsorted {
+        return;
+    }
+
+    if options.sort_by_size {
+        entries.sort_by_cached_key(|e| std::cmp::Reverse(e.metadata(options.dereference).map(|m| m.len()).unwrap_or(0)));
+    } else if options.sort_by_time {
+        entries.sort_by_cached_key(|e| std::cmp::Reverse(e.metadata(options.dereference).and_then(|m| m.modified()).ok()));
+    } else {
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    if options.reverse {
+        entries.reverse();
+    }
+}
+
+fn read_entries(dir: &Path, options: &LsOptions) -> Result<Vec<LsEntry>, CommandError> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| CommandError::DirectoryReadError(dir.to_path_buf(), e))? {
+        let entry = LsEntry::from_dir_entry(&entry?);
+        if options.all || !entry.is_hidden() {
+            entries.push(entry);
+        }
+    }
+
+    sort_entries(&mut entries, options);
+    Ok(entries)
+}
+
+/// One `ls` entry as written in JSON output mode.
+#[derive(Serialize)]
+struct EntryRecord {
+    name: String,
+    path: String,
+    kind: &'static str,
+    size: Option<u64>,
+    /// Seconds since the Unix epoch.
+    modified: Option<u64>,
+}
+
+impl EntryRecord {
+    fn from_entry(entry: &LsEntry, dereference: Dereference) -> Self {
+        let metadata = entry.metadata(dereference).ok();
+        let kind = match entry.file_type() {
+            Some(t) if t.is_dir() => "dir",
+            Some(t) if t.is_symlink() => "symlink",
+            Some(t) if t.is_file() => "file",
+            _ => "other",
+        };
+
+        Self {
+            name: entry.name.to_string_lossy().into_owned(),
+            path: entry.path.to_string_lossy().into_owned(),
+            kind,
+            size: metadata.as_ref().map(|m| m.len()),
+            modified: metadata
+                .and_then(|m| m.modified().ok())
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs()),
+        }
+    }
+}
+
+/// Everything `ls` listed: records for JSON output and the rendered text.
+#[derive(Default)]
+struct Listing {
+    records: Vec<EntryRecord>,
+    text: String,
+    /// Directories already listed with `-R`, so followed links can't make it loop.
+    visited: HashSet<PathBuf>,
+}
+
+impl Serialize for Listing {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.records.serialize(serializer)
+    }
+}
+
+impl CommandOutput for Listing {
+    fn to_text(&self) -> String {
+        self.text.clone()
+    }
+}
+
+/// `entries` laid out in columns, or as `ls -l` rows.
+fn render_entries(entries: &[LsEntry], options: &LsOptions) -> String {
+    if options.long {
+        return long_listing(entries, options.classify, options.dereference);
+    }
+
+    let cells: Vec<Cell> = entries.iter()
+        .map(|entry| Cell::styled(painted_name(entry, options.classify, options.dereference), &entry_name(entry, options.classify)))
+        .collect();
+
+    let width = if options.one_per_line { 0 } else { columns::terminal_width() };
+    columns::render(&cells, width)
+}
+
+fn list_directory(dir: &Path, options: &LsOptions, listing: &mut Listing) -> Result<(), CommandError> {
+    let entries = read_entries(dir, options)?;
+    list_entries(entries, options, listing)
+}
+
+fn list_entries(entries: Vec<LsEntry>, options: &LsOptions, listing: &mut Listing) -> Result<(), CommandError> {
+    listing.records.extend(entries.iter().map(|entry| EntryRecord::from_entry(entry, options.dereference)));
+    listing.text.push_str(&render_entries(&entries, options));
+
+    if options.recursive {
+        let token = CancellationToken::current();
+        for entry in &entries {
+            token.check()?;
+
+            let path = &entry.path;
+            if options.dereference.is_dir(path, false) {
+                if path.canonicalize().is_ok_and(|real| !listing.visited.insert(real)) {
+                    warn!("'{}' was already listed, not following it again", path.display());
+                    continue;
+                }
+
+                listing.text.push_str(&format!("\n{}:\n", paths::display(path)));
+                if let Err(e) = list_directory(path, options, listing) {
+                    match e {
+                        CommandError::Interrupted => return Err(e),
+                        e => warn!("{}", e),
+                    }
+                }
             }
         }
     }
@@ -343,59 +1352,415 @@ pub fn cmd_cat(args: Vec<&str>) -> Result<(), CommandError> {
     Ok(())
 }
 
-#[command(name = "ls", description = "Displays files and folders from the passed directory or current if none passed")]
-pub fn cmd_ls(path: Option<PathBuf>) -> Result<(), CommandError> {
+/// Writes `entries` as soon as a batch is complete instead of collecting the whole directory first.
+/// Text bypasses the pager. JSON is newline-delimited like all JSON output, so records written
+/// per batch read the same as a listing emitted at once.
+fn stream_entries(
+    entries: impl Iterator<Item = Result<LsEntry, CommandError>>,
+    options: &LsOptions,
+) -> Result<(), CommandError> {
+    let token = CancellationToken::current();
+    let mut batches = entries.peekable();
+
+    let mut next_batch = || -> Result<Vec<LsEntry>, CommandError> {
+        let mut batch = Vec::with_capacity(LS_BATCH_SIZE);
+        while batch.len() < LS_BATCH_SIZE {
+            token.check()?;
+            match batches.next() {
+                Some(entry) => batch.push(entry?),
+                None => break,
+            }
+        }
+        Ok(batch)
+    };
+
+    if output::format() == OutputFormat::Json {
+        loop {
+            let batch = next_batch()?;
+            if batch.is_empty() {
+                return Ok(());
+            }
+            output::emit(&Listing {
+                records: batch.iter().map(|entry| EntryRecord::from_entry(entry, options.dereference)).collect(),
+                ..Listing::default()
+            })?;
+        }
+    }
+
+    output::with_data_writer(|out| loop {
+        let batch = next_batch()?;
+        if batch.is_empty() {
+            return Ok(());
+        }
+        out.write_all(render_entries(&batch, options).as_bytes())?;
+    })
+}
+
+/// Lists `dir` without holding all of it: in directory order with `-U`, and otherwise, once it holds more
+/// than [`LS_STREAM_THRESHOLD`] entries, in name order through an external sort. Returns the entries
+/// unlisted when the directory turned out small enough, or needs sorting by time or size.
+fn list_streaming(dir: &Path, options: &LsOptions) -> Result<Option<Vec<LsEntry>>, CommandError> {
+    let visible = |entry: &LsEntry| options.all || !entry.is_hidden();
+    let keep = |entry: &io::Result<LsEntry>| match entry {
+        Ok(entry) => visible(entry),
+        Err(_) => true,
+    };
+    let mut read = fs::read_dir(dir)
+        .map_err(|e| CommandError::DirectoryReadError(dir.to_path_buf(), e))?
+        .map(|entry| entry.map(|entry| LsEntry::from_dir_entry(&entry)));
+
+    if options.unsorted {
+        return stream_entries(read.filter(keep).map(|entry| entry.map_err(CommandError::from)), options).map(|_| None);
+    }
+
+    let mut first = Vec::new();
+    for entry in read.by_ref() {
+        let entry = entry?;
+        if visible(&entry) {
+            first.push(entry);
+        }
+        if first.len() > LS_STREAM_THRESHOLD {
+            break;
+        }
+    }
+    if first.len() <= LS_STREAM_THRESHOLD || !options.sorts_by_name() {
+        first.extend(read.filter(keep).collect::<Result<Vec<_>, _>>()?);
+        sort_entries(&mut first, options);
+        return Ok(Some(first));
+    }
+
+    let mut sort = ExternalSort::new(LS_STREAM_THRESHOLD * 10, options.reverse);
+    for entry in first.into_iter().map(Ok).chain(read) {
+        let entry = entry?;
+        if visible(&entry) {
+            sort.push(entry.name)?;
+        }
+    }
+
+    let sorted = sort.finish()?;
+    stream_entries(sorted.map(|name| Ok(LsEntry::from_name(dir, name?))), options).map(|_| None)
+}
+
+#[command(
+    name = "ls",
+    description = "Displays files and folders from the passed directory or current if none passed",
+    usage = "ls [-a] [-l] [-t|-S|-U] [-r] [-R] [-L|-H|-P] [-F|--classify] [-1] [dir]",
+    examples = ["ls", "ls -F src", "ls -la", "ls -tr", "ls -R src", "ls -U1 huge-directory"],
+    json
+)]
+pub fn cmd_ls(args: Option<Vec<&OsStr>>) -> Result<(), CommandError> {
+    let mut options = LsOptions::default();
+    let mut path = None;
+
+    for arg in args.unwrap_or_default() {
+        match arg.to_str() {
+            Some("--classify") => options.classify = true,
+            Some("--all") => options.all = true,
+            Some("--reverse") => options.reverse = true,
+            Some("--recursive") => options.recursive = true,
+            Some("--unsorted") => options.unsorted = true,
+            Some(flag) if Dereference::from_flag(flag).is_some() => options.dereference = Dereference::from_flag(flag).unwrap_or_default(),
+            Some(flags) if flags.len() > 1 && flags.starts_with('-') && flags[1..].chars().all(|c| options.set_short(c)) => {}
+            Some(flags) if flags.starts_with('-') && flags.len() > 1 => {
+                return Err(CommandError::InvalidArguments(format!("Unknown option '{}' for ls", flags)));
+            }
+            _ => path = Some(PathBuf::from(arg)),
+        }
+    }
+    // Directory order differs between filesystems, so scripted runs always get entries by name.
+    if deterministic::is_enabled() {
+        options.unsorted = false;
+    }
+
     let target = if let Some(path) =  path {
         path
     } else {
         env::current_dir()
-            .map_err(|e| CommandError::CannotAccessCurrentDirectory(e))?
+            .map_err(CommandError::CannotAccessCurrentDirectory)?
     };
 
-    let mut entries: Vec<_> = fs::read_dir(&target)
-        .map_err(|e| CommandError::DirectoryReadError(target, e))?
-        .collect::<Result<_, _>>()?;
-
-    entries.sort_by_key(|e| e.path());
+    let mut listing = Listing {
+        visited: target.canonicalize().into_iter().collect(),
+        ..Listing::default()
+    };
+    if options.recursive {
+        listing.text.push_str(&format!("{}:\n", paths::display(&target)));
+        list_directory(&target, &options, &mut listing)?;
+    } else {
+        // Huge directories are written while they are read; the rest is listed as a whole.
+        let Some(entries) = list_streaming(&target, &options)? else {
+            return Ok(());
+        };
+        list_entries(entries, &options, &mut listing)?;
+    }
 
-    if entries.is_empty() {
-        info!("The directory is empty");
+    if listing.records.is_empty() && output::format() == OutputFormat::Text {
+        info!("{}", tr!("fs.empty_dir"));
         return Ok(());
     }
 
-    println!();
-    for entry in entries {
-        let path = entry.path();
-        match entry.file_type() {
-            Ok(file_type) => {
-                let kind = if file_type.is_file() {
-                    "[File]"
-                } else if file_type.is_dir() {
-                    "[Dir]"
-                } else if file_type.is_symlink() {
-                    "[Symlink]"
-                } else {
-                    "[Other]"
-                };
-                println!("{}\t{}", kind, path.display());
+    output::emit(&listing)
+}
+
+#[command(
+    name = "du",
+    description = "Print the size of the files passed, including everything inside directories",
+    usage = "du [-L|-H|-P] [--sequential] <path>...",
+    examples = ["du notes.txt", "du -L current", "du --sequential target"],
+    json
+)]
+pub fn cmd_du(args: Vec<&OsStr>) -> Result<(), CommandError> {
+    #[derive(Serialize)]
+    struct SizeRecord {
+        path: String,
+        size: u64,
+    }
+
+    struct Sizes(Vec<SizeRecord>);
+
+    impl Serialize for Sizes {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.0.serialize(serializer)
+        }
+    }
+
+    impl CommandOutput for Sizes {
+        fn to_text(&self) -> String {
+            self.0.iter()
+                .map(|record| format!("Sizeof '{}' is: {}\n", record.path, format_size(record.size, DECIMAL)))
+                .collect()
+        }
+    }
+
+    let mut dereference = Dereference::Never;
+    let mut parallel = true;
+    let mut paths = Vec::new();
+    for arg in args {
+        if arg == "--sequential" {
+            parallel = false;
+            continue;
+        }
+        match arg.to_str().and_then(Dereference::from_flag) {
+            Some(mode) => dereference = mode,
+            None => paths.push(Path::new(arg)),
+        }
+    }
+
+    let mut sizes = Vec::with_capacity(paths.len());
+
+    for path in &paths {
+        let size = if dereference.is_dir(path, true) {
+            let total = AtomicU64::new(0);
+            Walk::new()
+                .dereference(dereference)
+                .parallel(parallel)
+                .run(path, |entry| {
+                    total.fetch_add(entry.metadata.len(), Ordering::Relaxed);
+                    Ok(())
+                })?;
+            total.into_inner()
+        } else {
+            dereference.metadata(path, true)
+                .map_err(|e| CommandError::DirectoryReadError(path.to_path_buf(), e))?
+                .len()
+        };
+
+        sizes.push(SizeRecord {
+            path: path.display().to_string(),
+            size,
+        });
+    }
+
+    output::emit(&Sizes(sizes))
+}
+
+#[command(
+    name = "stat",
+    description = "Print the type, size, permissions, owner and modification time of files",
+    usage = "stat [-L|-P] <path>...",
+    examples = ["stat notes.txt", "stat -L current"],
+    json
+)]
+pub fn cmd_stat(args: Vec<&OsStr>) -> Result<(), CommandError> {
+    #[derive(Serialize)]
+    struct StatRecord {
+        path: String,
+        kind: &'static str,
+        target: Option<String>,
+        size: u64,
+        mode: String,
+        links: u64,
+        owner: String,
+        modified: Option<String>,
+    }
+
+    struct Stats(Vec<StatRecord>);
+
+    impl Serialize for Stats {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.0.serialize(serializer)
+        }
+    }
+
+    impl CommandOutput for Stats {
+        fn to_text(&self) -> String {
+            self.0.iter()
+                .map(|record| {
+                    let target = record.target.as_ref().map(|target| format!(" -> {}", target)).unwrap_or_default();
+                    format!(
+                        "  File: {}{}\n  Type: {}\n  Size: {}\n  Mode: {}\n Links: {}\n Owner: {}\nModify: {}\n",
+                        record.path,
+                        target,
+                        record.kind,
+                        record.size,
+                        record.mode,
+                        record.links,
+                        record.owner,
+                        record.modified.as_deref().unwrap_or("-")
+                    )
+                })
+                .collect()
+        }
+    }
+
+    // Like GNU stat, a link is described as itself unless `-L` is given.
+    let mut dereference = Dereference::Never;
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.to_str() {
+            Some("-L" | "--dereference") => dereference = Dereference::Always,
+            Some("-P" | "--no-dereference") => dereference = Dereference::Never,
+            Some(flag) if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(CommandError::InvalidArguments(format!("Unknown option '{}' for stat", flag)));
             }
-            Err(_) => println!("{}", path.display()),
+            _ => paths.push(Path::new(arg)),
         }
     }
-    println!();
+    if paths.is_empty() {
+        return Err(CommandError::InvalidArguments("Usage: stat [-L|-P] <path>...".to_string()));
+    }
 
-    Ok(())
+    let mut records = Vec::with_capacity(paths.len());
+    for path in paths {
+        let metadata = dereference.metadata(path, true)
+            .map_err(|e| CommandError::FileReadError(path.to_path_buf(), e))?;
+        let kind = if metadata.is_symlink() {
+            "symbolic link"
+        } else if metadata.is_dir() {
+            "directory"
+        } else if metadata.is_file() {
+            "regular file"
+        } else {
+            "other"
+        };
+        let details = FileDetails::from_metadata(&metadata);
+
+        records.push(StatRecord {
+            path: path.display().to_string(),
+            kind,
+            target: metadata.is_symlink()
+                .then(|| fs::read_link(path).ok())
+                .flatten()
+                .map(|target| target.display().to_string()),
+            size: details.size,
+            mode: details.mode,
+            links: details.links,
+            owner: deterministic::user(details.owner),
+            modified: details.modified.map(|time| deterministic::local(time).format("%Y-%m-%d %H:%M:%S").to_string()),
+        });
+    }
+
+    output::emit(&Stats(records))
 }
 
-#[command(name = "du", description = "Print the size of the file passed")]
-pub fn cmd_du(paths: Vec<&Path>) -> Result<(), CommandError> {
-    for path in &paths {
-        fs::metadata(path)
-            .map(|metadata| {
-                println!("Sizeof '{}' is: {}", path.display(), format_size(metadata.file_size(), DECIMAL));
-            })
-            .map_err(|e| CommandError::DirectoryReadError(path.to_path_buf(), e))?
+#[command(
+    name = "find",
+    description = "List the files below directories, optionally only those matching a name or type",
+    usage = "find [-L|-H|-P] [path...] [-name <glob>] [-type f|d|l] [-maxdepth <n>]",
+    examples = ["find src -name *.rs", "find -L . -type d", "find . -maxdepth 1"]
+)]
+pub fn cmd_find(args: Vec<&OsStr>) -> Result<(), CommandError> {
+    let mut dereference = Dereference::Never;
+    let mut roots = Vec::new();
+    let mut name = None;
+    let mut kind = None;
+    let mut max_depth = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("-name") => {
+                let pattern = args.next()
+                    .map(|value| value.to_string_lossy().into_owned())
+                    .ok_or_else(|| CommandError::InvalidArguments("-name needs a pattern".to_string()))?;
+                let matcher = globset::Glob::new(&pattern)
+                    .map_err(|e| CommandError::InvalidArguments(format!("Invalid glob pattern '{}': {}", pattern, e.kind())))?
+                    .compile_matcher();
+                name = Some(matcher);
+            }
+            Some("-type") => {
+                kind = match args.next().and_then(|value| value.to_str()) {
+                    Some(value @ ("f" | "d" | "l")) => Some(value.to_string()),
+                    _ => return Err(CommandError::InvalidArguments("-type needs f, d or l".to_string())),
+                };
+            }
+            Some("-maxdepth") => {
+                let depth = args.next()
+                    .and_then(|value| value.to_str())
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(|| CommandError::InvalidArguments("-maxdepth needs a number".to_string()))?;
+                max_depth = Some(depth);
+            }
+            Some(flag) if Dereference::from_flag(flag).is_some() => dereference = Dereference::from_flag(flag).unwrap_or_default(),
+            Some(flag) if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(CommandError::InvalidArguments(format!("Unknown option '{}' for find", flag)));
+            }
+            _ => roots.push(PathBuf::from(arg)),
+        }
+    }
+    if roots.is_empty() {
+        roots.push(PathBuf::from("."));
     }
 
-    Ok(())
+    let mut found = String::new();
+    for root in &roots {
+        // Without `-L` or `-H` a link given as the root is listed, not walked into.
+        if !dereference.is_dir(root, true) {
+            let metadata = dereference.metadata(root, true)
+                .map_err(|e| CommandError::FileReadError(root.clone(), e))?;
+            if matches_find(root, &metadata, name.as_ref(), kind.as_deref()) {
+                found.push_str(&format!("{}\n", root.display()));
+            }
+            continue;
+        }
+
+        let paths = Mutex::new(Vec::new());
+        Walk::new()
+            .sorted(true)
+            .max_depth(max_depth)
+            .dereference(dereference)
+            .run(root, |entry| {
+                if matches_find(&entry.path, &entry.metadata, name.as_ref(), kind.as_deref()) {
+                    paths.lock().unwrap_or_else(|e| e.into_inner()).push(entry.path);
+                }
+                Ok(())
+            })?;
+        for path in paths.into_inner().unwrap_or_else(|e| e.into_inner()) {
+            found.push_str(&format!("{}\n", path.display()));
+        }
+    }
+
+    output::print(&found)
+}
+
+/// Whether `find` lists `path`: its file name matches `-name` and its metadata `-type`, where given.
+fn matches_find(path: &Path, metadata: &fs::Metadata, name: Option<&globset::GlobMatcher>, kind: Option<&str>) -> bool {
+    let name_matches = name.is_none_or(|matcher| path.file_name().is_some_and(|file_name| matcher.is_match(file_name)));
+    let kind_matches = match kind {
+        Some("f") => metadata.is_file(),
+        Some("d") => metadata.is_dir(),
+        Some("l") => metadata.is_symlink(),
+        _ => true,
+    };
+    name_matches && kind_matches
 }
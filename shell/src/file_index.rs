@@ -0,0 +1,218 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{atomic::{AtomicBool, Ordering}, RwLock},
+    thread,
+    time::SystemTime,
+};
+
+use log::debug;
+use serde::Deserialize;
+
+use crate::config;
+
+/// `[index]` section of the configuration file.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct IndexConfig {
+    /// Directory levels below the current directory that are indexed.
+    pub max_depth: usize,
+    /// Entries indexed at most, so starting in `/` or a huge tree stays cheap.
+    pub max_entries: usize,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: 8,
+            max_entries: 200_000,
+        }
+    }
+}
+
+/// One indexed directory: when it last changed and the entries it held then, relative to the root.
+struct DirState {
+    modified: Option<SystemTime>,
+    depth: usize,
+    entries: Vec<PathBuf>,
+}
+
+/// Paths below `root`, by the directory holding them.
+struct FileIndex {
+    root: PathBuf,
+    dirs: BTreeMap<PathBuf, DirState>,
+    len: usize,
+}
+
+static INDEX: RwLock<Option<FileIndex>> = RwLock::new(None);
+static UPDATING: AtomicBool = AtomicBool::new(false);
+
+fn is_hidden_name(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+impl FileIndex {
+    fn new(root: PathBuf) -> Self {
+        Self { root, dirs: BTreeMap::new(), len: 0 }
+    }
+
+    /// Reads the directory `relative`, and the subdirectories not indexed yet, below it.
+    fn scan(&mut self, relative: &Path, depth: usize, config: &IndexConfig) {
+        let dir = self.root.join(relative);
+        let Ok(read) = fs::read_dir(&dir) else {
+            return;
+        };
+
+        let mut entries = Vec::new();
+        let mut subdirs = Vec::new();
+        for entry in read.filter_map(|entry| entry.ok()) {
+            let path = relative.join(entry.file_name());
+            if is_hidden_name(&path) {
+                continue;
+            }
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                subdirs.push(path.clone());
+            }
+            entries.push(path);
+        }
+
+        self.len += entries.len();
+        if let Some(previous) = self.dirs.insert(relative.to_path_buf(), DirState {
+            modified: fs::metadata(&dir).and_then(|m| m.modified()).ok(),
+            depth,
+            entries,
+        }) {
+            self.len -= previous.entries.len();
+        }
+
+        if depth >= config.max_depth {
+            return;
+        }
+        for subdir in subdirs {
+            if self.len >= config.max_entries {
+                return;
+            }
+            if !self.dirs.contains_key(&subdir) {
+                self.scan(&subdir, depth + 1, config);
+            }
+        }
+    }
+
+    /// Rescans only the directories whose modification time changed, and drops the ones that are gone.
+    fn update(&mut self, config: &IndexConfig) {
+        let changed: Vec<(PathBuf, usize)> = self.dirs.iter()
+            .filter(|(relative, state)| fs::metadata(self.root.join(relative)).and_then(|m| m.modified()).ok() != state.modified)
+            .map(|(relative, state)| (relative.clone(), state.depth))
+            .collect();
+
+        for (relative, depth) in changed {
+            if self.root.join(&relative).is_dir() {
+                self.scan(&relative, depth, config);
+            } else {
+                let gone: Vec<PathBuf> = self.dirs.keys().filter(|dir| dir.starts_with(&relative)).cloned().collect();
+                for dir in gone {
+                    if let Some(state) = self.dirs.remove(&dir) {
+                        self.len -= state.entries.len();
+                    }
+                }
+            }
+        }
+    }
+
+    fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.dirs.values().flat_map(|state| state.entries.iter())
+    }
+}
+
+/// Brings the index up to date with the current directory on a background thread, unless an update is running.
+/// A new current directory starts a new index; otherwise only changed directories are read again.
+pub fn refresh() {
+    if UPDATING.swap(true, Ordering::AcqRel) {
+        return;
+    }
+
+    thread::spawn(|| {
+        update_now();
+        UPDATING.store(false, Ordering::Release);
+    });
+}
+
+fn update_now() {
+    let Ok(root) = env::current_dir() else {
+        return;
+    };
+    let config = &config::get().index;
+
+    // The index is taken out of the lock while it is read again, so a slow disk never blocks a search;
+    // a search meanwhile builds its own.
+    let previous = INDEX.write().unwrap_or_else(|e| e.into_inner()).take();
+    let mut index = match previous {
+        Some(index) if index.root == root && !index.dirs.is_empty() => index,
+        _ => FileIndex::new(root),
+    };
+    if index.dirs.is_empty() {
+        index.scan(Path::new(""), 0, config);
+    } else {
+        index.update(config);
+    }
+
+    debug!("file index of '{}' holds {} entries", index.root.display(), index.len);
+    *INDEX.write().unwrap_or_else(|e| e.into_inner()) = Some(index);
+}
+
+/// How well `query` matches `candidate` as a subsequence, ignoring case, or `None` when it doesn't.
+/// Consecutive characters and characters starting a path component or word score higher,
+/// and shorter candidates win ties.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let mut score = 0i64;
+    let mut previous_match: Option<usize> = None;
+    let mut chars = candidate.char_indices();
+    let mut previous_char = None;
+
+    for wanted in query.chars().flat_map(char::to_lowercase) {
+        loop {
+            let (at, c) = chars.next()?;
+            let before = previous_char.replace(c);
+            if !c.to_lowercase().eq(std::iter::once(wanted)) {
+                continue;
+            }
+
+            score += 1;
+            if previous_match.is_some_and(|p| p + before.map_or(0, char::len_utf8) == at) {
+                score += 5;
+            }
+            if before.is_none_or(|b| matches!(b, '/' | '\\' | '_' | '-' | '.' | ' ')) {
+                score += 8;
+            }
+            previous_match = Some(at);
+            break;
+        }
+    }
+
+    Some(score * 100 - candidate.len() as i64)
+}
+
+/// The `limit` indexed paths matching `query` best, relative to the current directory. Builds the
+/// index first when there is none for the current directory yet.
+pub fn search(query: &str, limit: usize) -> Vec<PathBuf> {
+    let current = env::current_dir().ok();
+    let ready = INDEX.read()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .is_some_and(|index| Some(&index.root) == current.as_ref());
+    if !ready {
+        update_now();
+    }
+
+    let index = INDEX.read().unwrap_or_else(|e| e.into_inner());
+    let Some(index) = index.as_ref() else {
+        return Vec::new();
+    };
+
+    let mut scored: Vec<(i64, &PathBuf)> = index.paths()
+        .filter_map(|path| fuzzy_score(query, &path.to_string_lossy()).map(|score| (score, path)))
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+    scored.into_iter().take(limit).map(|(_, path)| path.clone()).collect()
+}
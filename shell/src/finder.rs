@@ -0,0 +1,189 @@
+use std::io::{self, IsTerminal, Write};
+
+use command_core::CommandError;
+use crossterm::{
+    cursor, event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, Clear, ClearType},
+};
+
+use crate::{file_index::fuzzy_score, pager::FullScreen};
+
+/// Runs `screen` on the alternate screen in raw mode, which needs a terminal.
+fn full_screen<R>(screen: impl FnOnce(&mut io::Stdout) -> io::Result<R>) -> Result<R, CommandError> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Err(CommandError::NotSupported("Picking interactively needs a terminal".to_string()));
+    }
+
+    let mut stdout = io::stdout();
+    let _screen = FullScreen::enter(&mut stdout)?;
+    Ok(screen(&mut stdout)?)
+}
+
+/// Lets the user narrow `items` down by typing and pick one, full-screen like `fzf`. Returns `None`
+/// when cancelled with Esc or Ctrl+C.
+pub fn select(items: &[String], query: &str) -> Result<Option<String>, CommandError> {
+    full_screen(|out| Finder::new(items, query).run(out))
+}
+
+/// Lists `items` with checkboxes, none checked, to pick exactly which ones `action` applies to.
+/// Returns the indices of the checked items, or `None` when cancelled with Esc or Ctrl+C.
+pub fn select_many(action: &str, items: &[String]) -> Result<Option<Vec<usize>>, CommandError> {
+    full_screen(|out| Checklist { action, items, checked: vec![false; items.len()], cursor: 0 }.run(out))
+}
+
+struct Finder<'a> {
+    items: &'a [String],
+    query: String,
+    /// Indices into `items` that match the query, best first.
+    matches: Vec<usize>,
+    /// Position of the highlighted entry in `matches`.
+    selected: usize,
+}
+
+impl<'a> Finder<'a> {
+    fn new(items: &'a [String], query: &str) -> Self {
+        let mut finder = Self { items, query: query.to_string(), matches: Vec::new(), selected: 0 };
+        finder.filter();
+        finder
+    }
+
+    fn filter(&mut self) {
+        let mut scored: Vec<(i64, usize)> = self.items.iter()
+            .enumerate()
+            .filter_map(|(index, item)| fuzzy_score(&self.query, item).map(|score| (score, index)))
+            .collect();
+        // Ties keep the order the items came in, e.g. newest first for the history.
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        self.matches = scored.into_iter().map(|(_, index)| index).collect();
+        self.selected = 0;
+    }
+
+    fn run(&mut self, out: &mut impl Write) -> io::Result<Option<String>> {
+        loop {
+            let (_, rows) = terminal::size()?;
+            let page = (rows as usize).saturating_sub(1).max(1);
+            self.draw(out, page)?;
+
+            let Event::Key(KeyEvent { code, modifiers, kind: KeyEventKind::Press, .. }) = event::read()? else {
+                continue;
+            };
+            let control = modifiers.contains(KeyModifiers::CONTROL);
+            match code {
+                KeyCode::Char('c') if control => return Ok(None),
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Enter => return Ok(self.matches.get(self.selected).map(|&index| self.items[index].clone())),
+                KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+                KeyCode::Char('p') if control => self.selected = self.selected.saturating_sub(1),
+                KeyCode::Down => self.selected = (self.selected + 1).min(self.matches.len().saturating_sub(1)),
+                KeyCode::Char('n') if control => self.selected = (self.selected + 1).min(self.matches.len().saturating_sub(1)),
+                KeyCode::Backspace => {
+                    self.query.pop();
+                    self.filter();
+                }
+                KeyCode::Char(c) if !control => {
+                    self.query.push(c);
+                    self.filter();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn draw(&self, out: &mut impl Write, page: usize) -> io::Result<()> {
+        queue!(out, Clear(ClearType::All))?;
+
+        // The best match sits right above the query, like fzf, and the list scrolls with the selection.
+        let first = self.selected.saturating_sub(page - 1);
+        for (row, (position, &index)) in self.matches.iter().enumerate().skip(first).take(page).enumerate() {
+            queue!(out, cursor::MoveTo(0, (page - 1 - row) as u16))?;
+            if position == self.selected {
+                queue!(out, SetAttribute(Attribute::Reverse), Print("> "), Print(&self.items[index]))?;
+            } else {
+                queue!(out, Print("  "), Print(&self.items[index]))?;
+            }
+            queue!(out, SetAttribute(Attribute::Reset))?;
+        }
+
+        queue!(
+            out,
+            cursor::MoveTo(0, page as u16),
+            Print(format!("{}/{} > {}", self.matches.len(), self.items.len(), self.query)),
+        )?;
+        out.flush()
+    }
+}
+
+struct Checklist<'a> {
+    action: &'a str,
+    items: &'a [String],
+    checked: Vec<bool>,
+    cursor: usize,
+}
+
+impl Checklist<'_> {
+    fn run(&mut self, out: &mut impl Write) -> io::Result<Option<Vec<usize>>> {
+        let last = self.items.len().saturating_sub(1);
+        loop {
+            let (_, rows) = terminal::size()?;
+            let page = (rows as usize).saturating_sub(1).max(1);
+            self.draw(out, page)?;
+
+            let Event::Key(KeyEvent { code, modifiers, kind: KeyEventKind::Press, .. }) = event::read()? else {
+                continue;
+            };
+            match code {
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+                KeyCode::Esc | KeyCode::Char('q') => return Ok(None),
+                KeyCode::Enter => {
+                    return Ok(Some((0..self.items.len()).filter(|&index| self.checked[index]).collect()));
+                }
+                KeyCode::Down | KeyCode::Char('j') => self.cursor = (self.cursor + 1).min(last),
+                KeyCode::Up | KeyCode::Char('k') => self.cursor = self.cursor.saturating_sub(1),
+                KeyCode::PageDown => self.cursor = (self.cursor + page).min(last),
+                KeyCode::PageUp => self.cursor = self.cursor.saturating_sub(page),
+                KeyCode::Char(' ') => {
+                    if let Some(checked) = self.checked.get_mut(self.cursor) {
+                        *checked = !*checked;
+                    }
+                    self.cursor = (self.cursor + 1).min(last);
+                }
+                KeyCode::Char('a') => {
+                    let all = self.checked.iter().all(|checked| *checked);
+                    self.checked.iter_mut().for_each(|checked| *checked = !all);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn draw(&self, out: &mut impl Write, page: usize) -> io::Result<()> {
+        queue!(out, Clear(ClearType::All))?;
+
+        let first = self.cursor.saturating_sub(page - 1);
+        for (row, (index, item)) in self.items.iter().enumerate().skip(first).take(page).enumerate() {
+            let line = format!("[{}] {}", if self.checked[index] { 'x' } else { ' ' }, item);
+            queue!(out, cursor::MoveTo(0, row as u16))?;
+            if index == self.cursor {
+                queue!(out, SetAttribute(Attribute::Reverse), Print(line), SetAttribute(Attribute::Reset))?;
+            } else {
+                queue!(out, Print(line))?;
+            }
+        }
+
+        let count = self.checked.iter().filter(|checked| **checked).count();
+        let status = format!(
+            "{} {} of {} (space check, a all, enter {}, esc cancel)",
+            self.action, count, self.items.len(), self.action
+        );
+        queue!(
+            out,
+            cursor::MoveTo(0, page as u16),
+            SetAttribute(Attribute::Reverse),
+            Print(status),
+            SetAttribute(Attribute::Reset)
+        )?;
+        out.flush()
+    }
+}
@@ -0,0 +1,59 @@
+use std::{ffi::OsStr, fs, path::{Component, Path, PathBuf}};
+
+use command_core::CommandError;
+use globset::GlobBuilder;
+
+/// Whether `arg` contains `*`, `?`, `[` or `{` and should be expanded.
+pub fn is_pattern(arg: &OsStr) -> bool {
+    arg.to_string_lossy().contains(['*', '?', '[', '{'])
+}
+
+/// Paths matching `pattern`, sorted. Each component is matched on its own, so `*` does not cross
+/// directories, and entries starting with `.` only match components that start with `.` too.
+pub fn expand(pattern: &OsStr) -> Result<Vec<PathBuf>, CommandError> {
+    let mut matches = vec![PathBuf::new()];
+
+    for component in Path::new(pattern).components() {
+        let Component::Normal(name) = component else {
+            // Roots, prefixes, `.` and `..` are kept as written.
+            for path in &mut matches {
+                path.push(component);
+            }
+            continue;
+        };
+
+        if !is_pattern(name) {
+            for path in &mut matches {
+                path.push(name);
+            }
+            continue;
+        }
+
+        let glob = name.to_string_lossy();
+        let matcher = GlobBuilder::new(&glob)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| CommandError::InvalidArguments(format!("Invalid glob pattern '{}': {}", glob, e.kind())))?
+            .compile_matcher();
+        let match_hidden = glob.starts_with('.');
+
+        matches = matches.iter()
+            .flat_map(|dir| {
+                let read_from = if dir.as_os_str().is_empty() { Path::new(".") } else { dir.as_path() };
+                fs::read_dir(read_from)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.file_name())
+                    .filter(|name| match_hidden || !name.to_string_lossy().starts_with('.'))
+                    .filter(|name| matcher.is_match(name))
+                    .map(|name| dir.join(name))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+    }
+
+    matches.retain(|path| !path.as_os_str().is_empty() && fs::symlink_metadata(path).is_ok());
+    matches.sort();
+    Ok(matches)
+}
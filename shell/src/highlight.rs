@@ -0,0 +1,51 @@
+use std::{path::Path, sync::OnceLock};
+
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Theme, ThemeSet},
+    parsing::SyntaxSet,
+    util::as_24_bit_terminal_escaped,
+};
+
+/// The syntax definitions bundled with syntect, loaded on first use.
+fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme() -> &'static Theme {
+    static THEME: OnceLock<Theme> = OnceLock::new();
+    THEME.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults().themes;
+        themes.remove("base16-ocean.dark").unwrap_or_default()
+    })
+}
+
+/// Colors the lines of one file by the syntax its extension, or else its first line, suggests.
+pub struct Highlighter {
+    lines: HighlightLines<'static>,
+}
+
+impl Highlighter {
+    /// For the file at `path`, or `None` if no syntax is known for it.
+    pub fn for_file(path: Option<&Path>, first_line: &str) -> Option<Self> {
+        let syntaxes = syntaxes();
+        let syntax = path
+            .and_then(|path| path.extension())
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| syntaxes.find_syntax_by_extension(extension))
+            .or_else(|| syntaxes.find_syntax_by_first_line(first_line))?;
+        Some(Self { lines: HighlightLines::new(syntax, theme()) })
+    }
+
+    /// `line`, given with its line ending, in terminal colors that are reset before the ending.
+    /// Lines have to come in order, as each continues the state of the one before.
+    pub fn line(&mut self, line: &str) -> String {
+        let Ok(ranges) = self.lines.highlight_line(line, syntaxes()) else {
+            return line.to_string();
+        };
+        let text = line.trim_end_matches(['\r', '\n']);
+        let escaped = as_24_bit_terminal_escaped(&ranges, false);
+        format!("{}\x1b[0m{}", escaped.trim_end_matches(['\r', '\n']), &line[text.len()..])
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+use command_core::{output, parse_line, Align, CommandError, CommandOutput, Table};
+use command_macro::command;
+use log::{debug, error};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{config, theme::{self, Role}};
+
+/// Which earlier entries a line that is already in the history replaces.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Dedup {
+    /// Keep every line.
+    Off,
+    /// Skip a line that repeats the one before it.
+    #[default]
+    Consecutive,
+    /// Drop every earlier copy of a line, so it is only kept as the latest entry.
+    All,
+}
+
+/// `[history]` section of the configuration file.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    pub dedup: Dedup,
+    /// Entries kept, the oldest are dropped beyond that.
+    pub max_entries: usize,
+    /// Don't keep lines typed with a leading space.
+    pub ignore_space: bool,
+    /// Regular expressions for lines not to keep, e.g. `["password", "^export .*TOKEN"]`.
+    pub ignore_patterns: Vec<String>,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            dedup: Dedup::Consecutive,
+            max_entries: 10_000,
+            ignore_space: true,
+            ignore_patterns: Vec::new(),
+        }
+    }
+}
+
+/// `ignore_patterns`, compiled on first use; invalid ones are reported once and left out.
+fn ignore_patterns() -> &'static [Regex] {
+    static PATTERNS: OnceLock<Vec<Regex>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        config::get().history.ignore_patterns.iter()
+            .filter_map(|pattern| Regex::new(pattern)
+                .map_err(|e| error!("Invalid history ignore pattern '{}': {}", pattern, e))
+                .ok())
+            .collect()
+    })
+}
+
+/// Lines entered so far, oldest first, with those of earlier sessions loaded on first use.
+static HISTORY: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+/// `~/.shell/history`, one line per entry.
+fn history_path() -> Option<PathBuf> {
+    config::data_dir().map(|dir| dir.join("history"))
+}
+
+fn with_history<R>(f: impl FnOnce(&mut Vec<String>) -> R) -> R {
+    let mut history = HISTORY.lock().unwrap_or_else(|e| e.into_inner());
+    let history = history.get_or_insert_with(|| {
+        history_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|contents| contents.lines().map(str::to_string).collect())
+            .unwrap_or_default()
+    });
+    f(history)
+}
+
+/// Entered lines, newest first, each only once.
+pub fn recent() -> Vec<String> {
+    with_history(|history| {
+        let mut seen = std::collections::HashSet::new();
+        history.iter().rev().filter(|line| seen.insert(line.as_str())).cloned().collect()
+    })
+}
+
+/// Appends an entered line, here and to the history file, unless `[history]` says not to keep it.
+pub fn add(line: &str) {
+    let config = &config::get().history;
+    if line.trim().is_empty()
+        || config.ignore_space && line.starts_with(char::is_whitespace)
+        || ignore_patterns().iter().any(|pattern| pattern.is_match(line))
+    {
+        return;
+    }
+
+    // Appending is enough unless earlier entries had to go, then the file is written anew. A
+    // repeated line that isn't kept isn't written at all.
+    let Some(rewrite) = with_history(|history| {
+        let before = history.len();
+        match config.dedup {
+            Dedup::Off => {}
+            Dedup::Consecutive if history.last().is_some_and(|last| last == line) => return None,
+            Dedup::Consecutive => {}
+            Dedup::All => history.retain(|entry| entry != line),
+        }
+        history.push(line.to_string());
+        history.drain(..history.len().saturating_sub(config.max_entries));
+        Some(history.len() <= before)
+    }) else {
+        return;
+    };
+
+    let Some(path) = history_path() else {
+        return;
+    };
+    let written = path.parent().map_or(Ok(()), fs::create_dir_all).and_then(|()| if rewrite {
+        rewrite_file(&path, line, config)
+    } else {
+        OpenOptions::new().create(true).append(true).open(&path)
+            .and_then(|mut file| writeln!(file, "{}", line))
+    });
+    if let Err(e) = written {
+        debug!("could not write the history file: {}", e);
+    }
+}
+
+/// Writes the history file anew with `line` added, applying `[history]` to what the file holds
+/// rather than to this session's entries, so lines other shells appended meanwhile are kept. The
+/// new file replaces the old one by renaming, so a shell reading it never sees it half written.
+fn rewrite_file(path: &Path, line: &str, config: &HistoryConfig) -> io::Result<()> {
+    let mut entries: Vec<String> = match fs::read_to_string(path) {
+        Ok(contents) => contents.lines().map(str::to_string).collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    if config.dedup == Dedup::All {
+        entries.retain(|entry| entry != line);
+    }
+    entries.push(line.to_string());
+    entries.drain(..entries.len().saturating_sub(config.max_entries));
+
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, entries.iter().map(|entry| format!("{}\n", entry)).collect::<String>())
+        .and_then(|()| fs::rename(&temporary, path))
+        .inspect_err(|_| _ = fs::remove_file(&temporary))
+}
+
+/// The entry an event designator such as `!`, `42` or `grep` refers to, or for `$` the last
+/// argument of the previous command.
+fn event(history: &[String], designator: &str) -> Option<String> {
+    match designator {
+        "!" => return history.last().cloned(),
+        "$" => return history.last()
+            .and_then(|line| parse_line(line))
+            .and_then(|parsed| parsed.args.last().cloned()),
+        _ => {}
+    }
+    if let Ok(number) = designator.parse::<usize>() {
+        return number.checked_sub(1).and_then(|index| history.get(index)).cloned();
+    }
+    history.iter().rev().find(|entry| entry.starts_with(designator)).cloned()
+}
+
+/// `line` with `!!`, `!N`, `!prefix` and `!$` replaced by the history entries they refer to, or `None` if
+/// it has none. A `!` followed by a space, `=` or the end of the line is kept.
+pub fn expand(line: &str) -> Result<Option<String>, CommandError> {
+    if !line.contains('!') {
+        return Ok(None);
+    }
+
+    with_history(|history| {
+        let mut expanded = String::with_capacity(line.len());
+        let mut changed = false;
+        let mut rest = line;
+        while let Some(at) = rest.find('!') {
+            expanded.push_str(&rest[..at]);
+            let after = &rest[at + 1..];
+            let length = if after.starts_with(['!', '$']) {
+                1
+            } else if after.starts_with(|c: char| c.is_ascii_digit()) {
+                after.find(|c: char| !c.is_ascii_digit()).unwrap_or(after.len())
+            } else {
+                after.find(|c: char| c.is_whitespace() || c == '!').unwrap_or(after.len())
+            };
+
+            let designator = &after[..length];
+            if designator.is_empty() || designator.starts_with('=') {
+                expanded.push('!');
+                rest = after;
+                continue;
+            }
+            let entry = event(history, designator)
+                .ok_or_else(|| CommandError::CommandFailed(format!("!{}: event not found", designator)))?;
+            expanded.push_str(&entry);
+            changed = true;
+            rest = &after[length..];
+        }
+        expanded.push_str(rest);
+        Ok(changed.then_some(expanded))
+    })
+}
+
+#[derive(Serialize)]
+struct HistoryEntry {
+    number: usize,
+    line: String,
+}
+
+#[derive(Serialize)]
+struct History(Vec<HistoryEntry>);
+
+impl CommandOutput for History {
+    fn to_text(&self) -> String {
+        let mut table = Table::new().align(0, Align::Right);
+        for entry in &self.0 {
+            table.push_row([theme::paint(Role::Muted, &entry.number.to_string()), entry.line.clone()]);
+        }
+        table.render()
+    }
+}
+
+#[command(
+    name = "history",
+    description = "List entered lines, numbered for `!N`",
+    usage = "history [count]",
+    examples = ["history", "history 20", "!42", "cd !$"],
+    json
+)]
+pub fn cmd_history(count: Option<usize>) -> Result<(), CommandError> {
+    let entries = with_history(|history| {
+        let skip = history.len().saturating_sub(count.unwrap_or(usize::MAX));
+        history.iter()
+            .enumerate()
+            .skip(skip)
+            .map(|(index, line)| HistoryEntry { number: index + 1, line: line.clone() })
+            .collect()
+    });
+    output::emit(&History(entries))
+}
@@ -0,0 +1,162 @@
+use std::{env, sync::OnceLock};
+
+/// Translated messages per language. Keys missing from a language fall back to English.
+const CATALOGS: &[(&str, &[(&str, &str)])] = &[
+    ("en", &[
+        ("startup.location", "{user} is in {path}"),
+        ("help.name", "name"),
+        ("help.description", "description"),
+        ("help.aliases", "aliases"),
+        ("help.usage", "usage"),
+        ("help.examples", "examples"),
+        ("confirm.remove", "Remove '{path}'? [y/N]: "),
+        ("confirm.yes", "y|yes"),
+        ("confirm.dangerous", "{reason}. Continue? [y/N]: "),
+        ("confirm.autocorrect", "Did you mean '{correction}'? [y/N]: "),
+        ("safety.root", "'{path}' is a filesystem root or the home directory"),
+        ("safety.large_tree", "'{path}' holds more than {count} files"),
+        ("safety.glob", "'{pattern}' matches {count} paths"),
+        ("safety.paranoid", "'{command}' changes '{path}'"),
+        ("fs.skipped", "Skipped '{path}'"),
+        ("fs.removed", "Removed '{path}'"),
+        ("fs.would_remove", "Would remove '{path}'"),
+        ("fs.would_create_dir", "Would create directory '{path}'"),
+        ("fs.would_write", "Would write '{path}'"),
+        ("fs.would_move", "Would move '{from}' to '{to}'"),
+        ("fs.would_copy", "Would copy '{from}' to '{to}'"),
+        ("fs.would_run", "Would run '{command}'"),
+        ("fs.created_dir", "Created directory '{path}'"),
+        ("fs.empty_file", "File '{name}' is empty."),
+        ("fs.empty_dir", "The directory is empty"),
+        ("error.missing_path", "Path '{path}' doesn't exist"),
+        ("error.is_directory", "Cannot remove directory '{path}': is a directory (use -r)"),
+        ("error.create_dir", "Failed to make directory '{path}': {error}"),
+        ("error.remove_dir", "Failed to remove directory '{path}': {error}"),
+        ("error.remove", "Failed to remove '{path}': {error}"),
+    ]),
+    ("de", &[
+        ("startup.location", "{user} ist in {path}"),
+        ("help.name", "Name"),
+        ("help.description", "Beschreibung"),
+        ("help.aliases", "Aliase"),
+        ("help.usage", "Aufruf"),
+        ("help.examples", "Beispiele"),
+        ("confirm.remove", "'{path}' entfernen? [j/N]: "),
+        ("confirm.yes", "j|ja|y|yes"),
+        ("confirm.dangerous", "{reason}. Fortfahren? [j/N]: "),
+        ("confirm.autocorrect", "Meinten Sie '{correction}'? [j/N]: "),
+        ("safety.root", "'{path}' ist ein Dateisystem-Stamm oder das Home-Verzeichnis"),
+        ("safety.large_tree", "'{path}' enthält mehr als {count} Dateien"),
+        ("safety.glob", "'{pattern}' trifft auf {count} Pfade zu"),
+        ("safety.paranoid", "'{command}' ändert '{path}'"),
+        ("fs.skipped", "'{path}' übersprungen"),
+        ("fs.removed", "'{path}' entfernt"),
+        ("fs.would_remove", "'{path}' würde entfernt"),
+        ("fs.would_create_dir", "Verzeichnis '{path}' würde erstellt"),
+        ("fs.would_write", "'{path}' würde geschrieben"),
+        ("fs.would_move", "'{from}' würde nach '{to}' verschoben"),
+        ("fs.would_copy", "'{from}' würde nach '{to}' kopiert"),
+        ("fs.would_run", "'{command}' würde ausgeführt"),
+        ("fs.created_dir", "Verzeichnis '{path}' erstellt"),
+        ("fs.empty_file", "Datei '{name}' ist leer."),
+        ("fs.empty_dir", "Das Verzeichnis ist leer"),
+        ("error.missing_path", "Pfad '{path}' existiert nicht"),
+        ("error.is_directory", "Verzeichnis '{path}' kann nicht entfernt werden: ist ein Verzeichnis (-r verwenden)"),
+        ("error.create_dir", "Verzeichnis '{path}' konnte nicht erstellt werden: {error}"),
+        ("error.remove_dir", "Verzeichnis '{path}' konnte nicht entfernt werden: {error}"),
+        ("error.remove", "'{path}' konnte nicht entfernt werden: {error}"),
+    ]),
+    ("fr", &[
+        ("startup.location", "{user} est dans {path}"),
+        ("help.name", "nom"),
+        ("help.description", "description"),
+        ("help.aliases", "alias"),
+        ("help.usage", "utilisation"),
+        ("help.examples", "exemples"),
+        ("confirm.remove", "Supprimer '{path}' ? [o/N] : "),
+        ("confirm.yes", "o|oui|y|yes"),
+        ("confirm.dangerous", "{reason}. Continuer ? [o/N] : "),
+        ("confirm.autocorrect", "Vouliez-vous dire '{correction}' ? [o/N] : "),
+        ("safety.root", "'{path}' est une racine du système de fichiers ou le répertoire personnel"),
+        ("safety.large_tree", "'{path}' contient plus de {count} fichiers"),
+        ("safety.glob", "'{pattern}' correspond à {count} chemins"),
+        ("safety.paranoid", "'{command}' modifie '{path}'"),
+        ("fs.skipped", "'{path}' ignoré"),
+        ("fs.removed", "'{path}' supprimé"),
+        ("fs.would_remove", "'{path}' serait supprimé"),
+        ("fs.would_create_dir", "Le répertoire '{path}' serait créé"),
+        ("fs.would_write", "'{path}' serait écrit"),
+        ("fs.would_move", "'{from}' serait déplacé vers '{to}'"),
+        ("fs.would_copy", "'{from}' serait copié vers '{to}'"),
+        ("fs.would_run", "'{command}' serait exécuté"),
+        ("fs.created_dir", "Répertoire '{path}' créé"),
+        ("fs.empty_file", "Le fichier '{name}' est vide."),
+        ("fs.empty_dir", "Le répertoire est vide"),
+        ("error.missing_path", "Le chemin '{path}' n'existe pas"),
+        ("error.is_directory", "Impossible de supprimer '{path}' : c'est un répertoire (utiliser -r)"),
+        ("error.create_dir", "Impossible de créer le répertoire '{path}' : {error}"),
+        ("error.remove_dir", "Impossible de supprimer le répertoire '{path}' : {error}"),
+        ("error.remove", "Impossible de supprimer '{path}' : {error}"),
+    ]),
+];
+
+static LANGUAGE: OnceLock<&'static str> = OnceLock::new();
+
+/// `de` for `de_DE.UTF-8`, `C`/`POSIX` and unknown languages as `en`.
+fn language_of(locale: &str) -> &'static str {
+    let language = locale.split(['_', '.', '-', '@']).next().unwrap_or_default().to_lowercase();
+    CATALOGS.iter()
+        .map(|(name, _)| *name)
+        .find(|name| *name == language)
+        .unwrap_or("en")
+}
+
+/// Selects the language from `locale` in the configuration, or else `LC_ALL`, `LC_MESSAGES` and `LANG`.
+pub fn init(configured: Option<&str>) {
+    let locale = configured.map(str::to_string).or_else(|| {
+        ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+    });
+
+    _ = LANGUAGE.set(locale.as_deref().map_or("en", language_of));
+}
+
+fn lookup(language: &str, key: &str) -> Option<&'static str> {
+    CATALOGS.iter()
+        .find(|(name, _)| *name == language)
+        .and_then(|(_, messages)| messages.iter().find(|(k, _)| *k == key))
+        .map(|(_, text)| *text)
+}
+
+/// The message for `key` in the selected language, or `key` itself if no catalog has it.
+pub fn message(key: &'static str) -> &'static str {
+    let language = LANGUAGE.get().copied().unwrap_or("en");
+    lookup(language, key)
+        .or_else(|| lookup("en", key))
+        .unwrap_or(key)
+}
+
+/// The message for `key` with each `{name}` placeholder replaced.
+pub fn format(key: &'static str, args: &[(&str, String)]) -> String {
+    args.iter().fold(message(key).to_string(), |text, (name, value)| {
+        text.replace(&format!("{{{}}}", name), value)
+    })
+}
+
+/// Whether `answer` to a confirmation means yes in the selected language.
+pub fn is_yes(answer: &str) -> bool {
+    let answer = answer.trim().to_lowercase();
+    message("confirm.yes").split('|').any(|yes| yes == answer)
+}
+
+/// Translated message: `tr!("fs.removed", path = path.display())`.
+#[macro_export]
+macro_rules! tr {
+    ($key:literal) => {
+        $crate::i18n::message($key).to_string()
+    };
+    ($key:literal, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::format($key, &[$((stringify!($name), $value.to_string())),+])
+    };
+}
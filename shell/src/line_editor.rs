@@ -0,0 +1,182 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use crossterm::{
+    cursor::{self, MoveTo},
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    queue,
+    style::Print,
+    terminal,
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::abbr;
+
+/// Raw mode for as long as a line is edited, turned off when dropped so an error or a panic doesn't
+/// leave the terminal without echo.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        _ = terminal::disable_raw_mode();
+    }
+}
+
+/// The line being typed after the prompt.
+struct Editor {
+    line: String,
+    /// Byte offset into `line`, always on a char boundary.
+    cursor: usize,
+    /// Where the line starts on the screen: the cell after the prompt.
+    start: (u16, u16),
+    /// Columns the line took when last drawn, so what's left of a longer one can be blanked.
+    drawn: usize,
+}
+
+impl Editor {
+    /// The screen cell `width` columns after the start of the line, wrapping at the terminal width.
+    fn cell(&self, width: usize) -> (u16, u16) {
+        let columns = terminal::size().map_or(80, |(columns, _)| columns.max(1)) as usize;
+        let offset = self.start.0 as usize + width;
+        ((offset % columns) as u16, self.start.1.saturating_add((offset / columns) as u16))
+    }
+
+    /// Draws the line over the last one, leaving anything to the right of it, such as a right prompt,
+    /// alone until the line reaches it.
+    fn draw(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let width = self.line.width();
+        let rows = terminal::size().map_or(u16::MAX, |(_, rows)| rows);
+        let (_, last_row) = self.cell(width.max(self.drawn));
+        if last_row >= rows {
+            // The terminal scrolled to fit the line, taking its start up with it.
+            self.start.1 = self.start.1.saturating_sub(last_row - rows + 1);
+        }
+
+        queue!(out, MoveTo(self.start.0, self.start.1), Print(&self.line))?;
+        if self.drawn > width {
+            queue!(out, Print(" ".repeat(self.drawn - width)))?;
+        }
+        self.drawn = width;
+        let (column, row) = self.cell(self.line[..self.cursor].width());
+        queue!(out, MoveTo(column, row))?;
+        out.flush()
+    }
+
+    fn insert(&mut self, text: &str) {
+        self.line.insert_str(self.cursor, text);
+        self.cursor += text.len();
+    }
+
+    fn previous_boundary(&self) -> usize {
+        self.line[..self.cursor].char_indices().next_back().map_or(0, |(i, _)| i)
+    }
+
+    fn next_boundary(&self) -> usize {
+        self.line[self.cursor..].chars().next().map_or(self.cursor, |c| self.cursor + c.len_utf8())
+    }
+
+    /// Expands an abbreviation the cursor is at the end of, if it is in command position, like fish
+    /// does on Space and Enter. The expansion stays editable, as it is part of the line from then on.
+    fn expand_abbreviation(&mut self) {
+        let before = &self.line[..self.cursor];
+        let word = before.trim_start();
+        let at_word_end = self.line[self.cursor..].chars().next().is_none_or(char::is_whitespace);
+        if word.is_empty() || word.contains(char::is_whitespace) || !at_word_end {
+            return;
+        }
+
+        if let Some(expanded) = abbr::expand(&self.line) {
+            let rest = self.line.len() - self.cursor;
+            self.line = expanded;
+            self.cursor = self.line.len() - rest;
+        }
+    }
+}
+
+/// Shows `prompt` and reads a line, expanding abbreviations in place as they are typed. Without a
+/// terminal the line is read as it is.
+pub fn read_line(prompt: &str) -> io::Result<Option<String>> {
+    let mut stdout = io::stdout();
+    write!(stdout, "{}", prompt)?;
+    stdout.flush()?;
+
+    if !io::stdin().is_terminal() || !stdout.is_terminal() {
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        return Ok(Some(line.trim_end_matches(['\n', '\r']).to_string()));
+    }
+
+    let _raw = RawMode::enable()?;
+    let mut editor = Editor { line: String::new(), cursor: 0, start: cursor::position()?, drawn: 0 };
+
+    loop {
+        let key = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            Event::Resize(..) => {
+                editor.draw(&mut stdout)?;
+                continue;
+            }
+            _ => continue,
+        };
+
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        match (key.code, control) {
+            (KeyCode::Enter, _) => {
+                editor.expand_abbreviation();
+                editor.cursor = editor.line.len();
+                editor.draw(&mut stdout)?;
+                write!(stdout, "\r\n")?;
+                return Ok(Some(editor.line));
+            }
+            (KeyCode::Char('c'), true) => {
+                // Like other shells, Ctrl-C drops the line and starts over on a new prompt.
+                write!(stdout, "^C\r\n")?;
+                return Ok(Some(String::new()));
+            }
+            (KeyCode::Char('d'), true) if editor.line.is_empty() => {
+                write!(stdout, "\r\n")?;
+                return Ok(None);
+            }
+            (KeyCode::Char('d'), true) | (KeyCode::Delete, _) => {
+                let end = editor.next_boundary();
+                editor.line.drain(editor.cursor..end);
+            }
+            (KeyCode::Char('a'), true) | (KeyCode::Home, _) => editor.cursor = 0,
+            (KeyCode::Char('e'), true) | (KeyCode::End, _) => editor.cursor = editor.line.len(),
+            (KeyCode::Char('u'), true) => {
+                editor.line.drain(..editor.cursor);
+                editor.cursor = 0;
+            }
+            (KeyCode::Char('k'), true) => editor.line.truncate(editor.cursor),
+            (KeyCode::Char('w'), true) => {
+                let before = editor.line[..editor.cursor].trim_end();
+                let start = before.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+                editor.line.drain(start..editor.cursor);
+                editor.cursor = start;
+            }
+            (KeyCode::Char(' '), false) if !alt => {
+                editor.expand_abbreviation();
+                editor.insert(" ");
+            }
+            (KeyCode::Char(c), false) if !alt => editor.insert(c.encode_utf8(&mut [0; 4])),
+            (KeyCode::Backspace, _) => {
+                let start = editor.previous_boundary();
+                editor.line.drain(start..editor.cursor);
+                editor.cursor = start;
+            }
+            (KeyCode::Left, _) => editor.cursor = editor.previous_boundary(),
+            (KeyCode::Right, _) => editor.cursor = editor.next_boundary(),
+            _ => continue,
+        }
+        editor.draw(&mut stdout)?;
+    }
+}
@@ -0,0 +1,343 @@
+use std::{fs::{self, File, OpenOptions}, io::{self, Write}, path::{Path, PathBuf}, sync::{Mutex, Once, RwLock}};
+
+use chrono::Local;
+use command_core::{output, CommandError};
+use env_logger::Builder;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::Deserialize;
+
+use crate::{config, deterministic, recording, theme::{self, Role}, transcript};
+
+/// How much the shell reports besides command output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verbosity {
+    /// Warnings and errors only.
+    Quiet,
+    /// Informational messages such as "Created directory".
+    Normal,
+    Verbose,
+    Trace,
+}
+
+impl Verbosity {
+    pub const ALL: [Verbosity; 4] = [Verbosity::Quiet, Verbosity::Normal, Verbosity::Verbose, Verbosity::Trace];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Verbosity::Quiet => "quiet",
+            Verbosity::Normal => "normal",
+            Verbosity::Verbose => "verbose",
+            Verbosity::Trace => "trace",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Verbosity> {
+        Verbosity::ALL.into_iter().find(|verbosity| verbosity.name() == name)
+    }
+
+    /// The verbosity selected by `-q`, `-v` and `-vv` among the shell's arguments.
+    pub fn from_args(args: &[String]) -> Verbosity {
+        let verbose: usize = args.iter()
+            .map(|arg| match arg.as_str() {
+                "-v" | "--verbose" => 1,
+                "-vv" => 2,
+                _ => 0,
+            })
+            .sum();
+
+        match verbose {
+            0 if args.iter().any(|arg| arg == "-q" || arg == "--quiet") => Verbosity::Quiet,
+            0 => Verbosity::Normal,
+            1 => Verbosity::Verbose,
+            _ => Verbosity::Trace,
+        }
+    }
+
+    fn level_filter(self) -> LevelFilter {
+        match self {
+            Verbosity::Quiet => LevelFilter::Warn,
+            Verbosity::Normal => LevelFilter::Info,
+            Verbosity::Verbose => LevelFilter::Debug,
+            Verbosity::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+/// `[log]` section of the configuration file.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// Also write log records to `~/.shell/shell.log`.
+    pub file: bool,
+    /// Size in bytes after which the log file is rotated.
+    pub max_size: u64,
+    /// Number of rotated files kept next to the current one.
+    pub keep: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            file: false,
+            max_size: 1024 * 1024,
+            keep: 3,
+        }
+    }
+}
+
+/// Active log filter: a default level plus per-target overrides, as in `RUST_LOG`.
+struct Filter {
+    default: LevelFilter,
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+    fn level_for(&self, target: &str) -> LevelFilter {
+        // The longest matching module prefix wins.
+        self.targets.iter()
+            .filter(|(prefix, _)| target == prefix || target.starts_with(&format!("{}::", prefix)))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.targets.iter()
+            .map(|&(_, level)| level)
+            .fold(self.default, Ord::max)
+    }
+
+    fn spec(&self) -> String {
+        std::iter::once(self.default.to_string().to_lowercase())
+            .chain(self.targets.iter().map(|(target, level)| format!("{}={}", target, level.to_string().to_lowercase())))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+static FILTER: RwLock<Filter> = RwLock::new(Filter { default: LevelFilter::Info, targets: Vec::new() });
+
+/// Log file that is renamed to `.1`, `.2`, ... once it grows past `max_size`.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    keep: usize,
+}
+
+impl RotatingFile {
+    pub fn open(path: PathBuf, max_size: u64, keep: usize) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path, file, size, max_size, keep })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        rotated_path(&self.path, index)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep == 0 {
+            self.file.set_len(0)?;
+        } else {
+            _ = fs::remove_file(self.rotated_path(self.keep));
+            for index in (1..self.keep).rev() {
+                _ = fs::rename(self.rotated_path(index), self.rotated_path(index + 1));
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+            self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        }
+
+        self.size = 0;
+        Ok(())
+    }
+
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 + 1 > self.max_size {
+            self.rotate()?;
+        }
+
+        writeln!(self.file, "{}", line)?;
+        self.size += line.len() as u64 + 1;
+        Ok(())
+    }
+}
+
+/// `path` with `.<index>` appended, where [`RotatingFile`] moves older lines.
+pub fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+static LOG_FILE: Mutex<Option<RotatingFile>> = Mutex::new(None);
+static LOG_FILE_OPENED: Once = Once::new();
+
+/// Opens the log file if the configuration asks for one, returning why it could not be.
+fn open_log_file(config: &LogConfig) -> Result<(), String> {
+    if !config.file {
+        return Ok(());
+    }
+
+    match config::data_dir().map(|dir| RotatingFile::open(dir.join("shell.log"), config.max_size, config.keep)) {
+        Some(Ok(file)) => {
+            *LOG_FILE.lock().unwrap_or_else(|e| e.into_inner()) = Some(file);
+            Ok(())
+        }
+        Some(Err(e)) => Err(format!("could not open the log file: {}", e)),
+        None => Err("could not determine the home directory, not logging to a file".to_string()),
+    }
+}
+
+/// Filters records through [`FILTER`] and tees them to the log file, if one is open.
+struct ShellLogger {
+    terminal: env_logger::Logger,
+}
+
+impl Log for ShellLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= FILTER.read().unwrap_or_else(|e| e.into_inner()).level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        // A captured command's records belong to its output, uncolored like anything else captured.
+        if output::is_capturing() {
+            _ = output::print_error(&format!("{}\n", format_record(record)));
+        } else {
+            self.terminal.log(record);
+            // The terminal logger writes to stderr itself, bypassing the output tee.
+            if recording::is_active() || transcript::is_active() {
+                let text = format!("{}\n", format_record(record));
+                recording::record_output(text.as_bytes());
+                transcript::record_output(text.as_bytes());
+            }
+        }
+
+        // The file is opened by the first record after the configuration was loaded, so startup
+        // neither waits for it nor forces the configuration to be parsed early. Loading the
+        // configuration here instead would log from inside the logger.
+        if let Some(config) = config::try_get() {
+            let mut failure = None;
+            LOG_FILE_OPENED.call_once(|| failure = open_log_file(&config.log).err());
+            if let Some(failure) = failure {
+                self.terminal.log(&Record::builder()
+                    .args(format_args!("{}", failure))
+                    .level(Level::Warn)
+                    .target(module_path!())
+                    .build());
+            }
+        }
+
+        let mut log_file = LOG_FILE.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(file) = log_file.as_mut() {
+            let line = format!(
+                "{} {:<5} {}: {}",
+                Local::now().format("%Y-%m-%d %H:%M:%S"),
+                record.level(),
+                record.target(),
+                record.args()
+            );
+
+            if file.write_line(&line).is_err() {
+                // Stop writing rather than failing on every record.
+                *log_file = None;
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.terminal.flush();
+        if let Some(file) = LOG_FILE.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            _ = file.file.flush();
+        }
+    }
+}
+
+/// `[12:00:00 | WARN]: message`, as records are shown on the terminal.
+fn format_record(record: &Record) -> String {
+    format!("[{} | {}]: {}", deterministic::now().format("%H:%M:%S"), record.level(), record.args())
+}
+
+/// Installs the logger. Records are filtered by [`set_verbosity`] and [`set_filter`] afterwards.
+pub fn init(verbosity: Verbosity) {
+    let terminal = Builder::new()
+        .filter(None, LevelFilter::Trace)
+        .format(|buf, record| {
+            let log_line = format_record(record);
+
+            let role = match record.level() {
+                Level::Error => Role::Error,
+                Level::Warn => Role::Warn,
+                Level::Info => Role::Info,
+                Level::Debug => Role::Debug,
+                Level::Trace => Role::Trace,
+            };
+
+            writeln!(buf, "{}", theme::paint(role, &log_line))
+        })
+        .build();
+
+    if log::set_boxed_logger(Box::new(ShellLogger { terminal })).is_err() {
+        return;
+    }
+    set_verbosity(verbosity);
+}
+
+fn update_filter(update: impl FnOnce(&mut Filter)) {
+    let mut filter = FILTER.write().unwrap_or_else(|e| e.into_inner());
+    update(&mut filter);
+    log::set_max_level(filter.max_level());
+}
+
+/// Sets the default level, keeping per-target levels set with [`set_filter`].
+pub fn set_verbosity(verbosity: Verbosity) {
+    update_filter(|filter| filter.default = verbosity.level_filter());
+}
+
+pub fn verbosity() -> Verbosity {
+    match FILTER.read().unwrap_or_else(|e| e.into_inner()).default {
+        LevelFilter::Off | LevelFilter::Error | LevelFilter::Warn => Verbosity::Quiet,
+        LevelFilter::Info => Verbosity::Normal,
+        LevelFilter::Debug => Verbosity::Verbose,
+        LevelFilter::Trace => Verbosity::Trace,
+    }
+}
+
+/// Replaces the filter with `spec`, e.g. `warn,shell::file_commands=debug`.
+/// Without a bare level the current default level is kept.
+pub fn set_filter(spec: &str) -> Result<(), CommandError> {
+    let parse_level = |level: &str| level.parse::<LevelFilter>().map_err(|_| CommandError::InvalidArguments(format!(
+        "Unknown log level '{}', expected 'off', 'error', 'warn', 'info', 'debug' or 'trace'",
+        level
+    )));
+
+    let mut default = None;
+    let mut targets = Vec::new();
+    for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+        match directive.split_once('=') {
+            Some((target, level)) => targets.push((target.to_string(), parse_level(level)?)),
+            None => default = Some(parse_level(directive)?),
+        }
+    }
+
+    update_filter(|filter| {
+        if let Some(default) = default {
+            filter.default = default;
+        }
+        filter.targets = targets;
+    });
+    Ok(())
+}
+
+/// The active filter in the format accepted by [`set_filter`].
+pub fn filter() -> String {
+    FILTER.read().unwrap_or_else(|e| e.into_inner()).spec()
+}
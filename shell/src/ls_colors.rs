@@ -0,0 +1,74 @@
+use std::{collections::HashMap, env, fs::Metadata, path::Path, sync::OnceLock};
+
+use crate::{metadata::is_executable, theme};
+
+const DEFAULT_LS_COLORS: &str = "di=01;34:ln=01;36:ex=01;32:\
+    *.zip=01;31:*.tar=01;31:*.gz=01;31:*.tgz=01;31:*.bz2=01;31:*.xz=01;31:*.7z=01;31:*.rar=01;31";
+
+/// File-type colors in `LS_COLORS` format, falling back to a small built-in set.
+pub struct LsColors {
+    types: HashMap<String, String>,
+    extensions: HashMap<String, String>,
+}
+
+impl LsColors {
+    fn parse(spec: &str) -> Self {
+        let mut colors = LsColors {
+            types: HashMap::new(),
+            extensions: HashMap::new(),
+        };
+
+        for (key, code) in spec.split(':').filter_map(|entry| entry.split_once('=')) {
+            match key.strip_prefix("*.") {
+                Some(extension) => colors.extensions.insert(extension.to_lowercase(), code.to_string()),
+                None => colors.types.insert(key.to_string(), code.to_string()),
+            };
+        }
+
+        colors
+    }
+
+    /// The loaded colors, or `None` when output should not be colored (`NO_COLOR`, not a terminal).
+    pub fn get() -> Option<&'static LsColors> {
+        static COLORS: OnceLock<LsColors> = OnceLock::new();
+
+        if !theme::colors_enabled() {
+            return None;
+        }
+
+        Some(COLORS.get_or_init(|| {
+            let spec = env::var("LS_COLORS").unwrap_or_else(|_| DEFAULT_LS_COLORS.to_string());
+            LsColors::parse(&spec)
+        }))
+    }
+
+    fn code_for(&self, path: &Path, metadata: &Metadata) -> Option<&str> {
+        let file_type = metadata.file_type();
+        let kind = if file_type.is_symlink() {
+            "ln"
+        } else if file_type.is_dir() {
+            "di"
+        } else if is_executable(path, metadata) {
+            "ex"
+        } else {
+            "fi"
+        };
+
+        if kind == "fi" || kind == "ex" {
+            let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+            if let Some(code) = extension.and_then(|e| self.extensions.get(&e)) {
+                return Some(code.as_str());
+            }
+        }
+
+        self.types.get(kind).map(String::as_str)
+    }
+
+    /// Wraps `name` in the escape sequence for the entry at `path`, if it has a color.
+    pub fn paint(&self, name: &str, path: &Path, metadata: &Metadata) -> String {
+        match self.code_for(path, metadata) {
+            Some(code) if !code.is_empty() => format!("\x1b[{}m{}\x1b[0m", code, name),
+            _ => name.to_string(),
+        }
+    }
+}
@@ -1,35 +1,92 @@
-use chrono::Local;
-use command_core::{CommandError, CommandHandler, CommandInfo, CommandRegistry, ParseArgument, COMMANDS};
+use std::ffi::OsStr;
 
-use colored::*;
+use command_core::{executables, output::{self, JsonFlagMiddleware, OutputFormat}, vfs, CancellationToken, CommandError, CommandHandler, CommandInfo, CommandRegistry, ParseArgument, Shell, COMMANDS};
 
-use env_logger::Builder;
-use log::{error, Level, LevelFilter};
+use log::{error, warn};
 
+mod abbr;
+mod audit;
+mod autocorrect;
+mod checksum_commands;
+mod columns;
+mod command_macros;
+mod config;
+mod copy;
 mod default_commands;
+mod deterministic;
+mod dry_run;
+mod encoding;
+mod external_sort;
 mod file_commands;
+mod file_index;
+mod finder;
+mod glob;
+mod highlight;
+mod history;
+mod i18n;
+mod line_editor;
+mod logging;
+mod ls_colors;
+mod mapped;
+mod metadata;
+mod not_found;
+mod notify;
+mod onchange;
+mod pager;
+mod paths;
+mod permissions;
+mod processes;
+mod progress;
+mod project;
+mod prompt;
+mod prompt_cache;
+mod recording;
+mod remote;
+mod remove;
+mod restrict;
+mod safety;
+mod scheduler;
+mod search_commands;
+mod session;
+mod startup;
+mod stats;
+mod theme;
+mod top;
+mod transcript;
+mod trash;
+mod walk;
+
+#[cfg(test)]
+mod tests;
 
 pub fn get_current_user() -> String {
-    whoami::username()
+    deterministic::user(whoami::username())
 }
 
 #[macro_export]
 macro_rules! print_current_user {
     () => {
-        print!("{}", get_current_user().purple())
+        print!("{}", $crate::theme::paint($crate::theme::Role::User, &get_current_user()))
     };
 }
 #[macro_export]
 macro_rules! println_current_user {
     () => {
-        println!("{}", get_current_user().purple())
+        println!("{}", $crate::theme::paint($crate::theme::Role::User, &get_current_user()))
     };
 }
 #[macro_export]
 macro_rules! print_current_dir {
     () => {
         std::env::current_dir()
-            .map(|path| print!("{} is in {}", get_current_user().purple(), path.to_str().unwrap_or_default().green()))
+            .map(|path| print!(
+                "{}",
+                $crate::tr!(
+                    "startup.location",
+                    user = $crate::theme::paint($crate::theme::Role::User, &get_current_user()),
+                    path = $crate::theme::paint($crate::theme::Role::Path, &$crate::paths::display(&path))
+                )
+            ))
             .unwrap_or_else(|e| error!("retrieving current directory: {}", e))
     };
 }
@@ -37,92 +94,249 @@ macro_rules! print_current_dir {
 macro_rules! println_current_dir {
     () => {
         std::env::current_dir()
-            .map(|path| println!("{} is in {}", get_current_user().purple(), path.to_str().unwrap_or_default().green()))
+            .map(|path| println!(
+                "{}",
+                $crate::tr!(
+                    "startup.location",
+                    user = $crate::theme::paint($crate::theme::Role::User, &get_current_user()),
+                    path = $crate::theme::paint($crate::theme::Role::Path, &$crate::paths::display(&path))
+                )
+            ))
             .unwrap_or_else(|e| error!("retrieving current directory: {}", e))
     };
 }
 
-pub fn call_executable(name: &str, args: &[&str]) -> Result<(), CommandError> {
+/// A `Command` that runs program `name` with `args`.
+pub fn executable_command(name: &str, args: &[&OsStr]) -> std::process::Command {
+    use std::{path::PathBuf, process::Command};
+
+    // Resolve through PATH and PATHEXT ourselves, so `build` finds `build.cmd`; if nothing matches,
+    // spawning the bare name still reports the operating system's reason.
+    let program = executables::find_executable(name).unwrap_or_else(|| PathBuf::from(name));
+    let mut command = if executables::is_batch_file(&program) {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(&program);
+        command
+    } else {
+        Command::new(&program)
+    };
+    command.args(args);
+    command
+}
+
+/// The error for a program `name` that couldn't be started.
+pub fn spawn_error(name: &str, e: std::io::Error) -> CommandError {
     use std::io::ErrorKind;
 
-    std::process::Command::new(name)
-        .args(args)
-        .spawn()
-        .map_err(|e| match e.kind() {
-            ErrorKind::NotFound => CommandError::CommandNotFound(format!("{}", name)),
-            ErrorKind::PermissionDenied => CommandError::CommandFailed(format!("Permission denied for '{}'", name)),
-            _ => CommandError::CommandFailed(format!("{}", e)),
-        })?
-        .wait()
+    match e.kind() {
+        ErrorKind::NotFound => CommandError::not_found(name),
+        ErrorKind::PermissionDenied => CommandError::CommandFailed(format!("Permission denied for '{}'", name)),
+        _ => CommandError::CommandFailed(format!("{}", e)),
+    }
+}
+
+/// Turns how program `name` exited into the result of running it.
+pub fn exit_result(name: &str, status: std::process::ExitStatus) -> Result<(), CommandError> {
+    if status.success() {
+        Ok(())
+    } else {
+        match status.code() {
+            Some(code) => Err(CommandError::CommandFailed(format!(
+                "Program '{}' exited with code: {}",
+                name, code
+            ))),
+            None => Err(CommandError::CommandFailed(format!(
+                "Program '{}' terminated by signal",
+                name
+            ))),
+        }
+    }
+}
+
+pub fn call_executable(name: &str, args: &[&OsStr]) -> Result<(), CommandError> {
+    use std::{io::Write, process::Stdio};
+
+    // A here-document is written to the program's standard input, which is closed after it.
+    let provided = command_core::input::provided();
+    let mut command = executable_command(name, args);
+    if provided.is_some() {
+        command.stdin(Stdio::piped());
+    }
+    let mut child = command.spawn().map_err(|e| spawn_error(name, e))?;
+    if let (Some(data), Some(mut stdin)) = (provided, child.stdin.take()) {
+        // A program that exits without reading all of it isn't an error of ours.
+        _ = stdin.write_all(&data);
+    }
+    child.wait()
         .map_err(CommandError::from)
-        .and_then(|status| {
-            if status.success() {
-                Ok(())
-            } else {
-                match status.code() {
-                    Some(code) => Err(CommandError::CommandFailed(format!(
-                        "Program '{}' exited with code: {}",
-                        name, code
-                    ))),
-                    None => Err(CommandError::CommandFailed(format!(
-                        "Program '{}' terminated by signal",
-                        name
-                    ))),
-                }
+        .and_then(|status| exit_result(name, status))
+}
+
+/// `--test [--update] <script|dir>...`: runs `.shtest` scripts, those in directories included, and
+/// returns the exit code. Only built with the `golden-tests` feature, e.g.
+/// `cargo run --features golden-tests -- --test shell/tests/golden`.
+#[cfg(feature = "golden-tests")]
+fn run_golden_tests(shell_args: &[String]) -> i32 {
+    use std::{fs, path::{self, Path, PathBuf}};
+
+    let update = shell_args.iter().any(|arg| arg == "--update");
+    let mut scripts = Vec::new();
+    for arg in shell_args.iter().filter(|arg| !arg.starts_with("--")) {
+        let path = PathBuf::from(arg);
+        match fs::read_dir(&path) {
+            Ok(entries) => {
+                let mut found: Vec<PathBuf> = entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.extension().is_some_and(|extension| extension == "shtest"))
+                    .collect();
+                found.sort();
+                scripts.extend(found);
             }
-        })
+            Err(_) => scripts.push(path),
+        }
+    }
+
+    // Scripts run in temporary directories, so relative paths are resolved first.
+    let scripts: Vec<PathBuf> = scripts.into_iter()
+        .map(|script| path::absolute(&script).unwrap_or(script))
+        .collect();
+    let scripts: Vec<&Path> = scripts.iter().map(PathBuf::as_path).collect();
+    if shell_test::golden::run(&scripts, update) { 0 } else { 1 }
+}
+
+#[cfg(not(feature = "golden-tests"))]
+fn run_golden_tests(_shell_args: &[String]) -> i32 {
+    eprintln!("This build can't run golden tests, build it with `--features golden-tests`");
+    2
 }
 
 fn main() {
-    use std::io::{self, Write};
-
-    _ = enable_ansi_support::enable_ansi_support();
-
-    Builder::new()
-        .filter(None, LevelFilter::Debug)
-        .format(|buf, record| {
-            let timestamp = Local::now().format("%H:%M:%S");
-
-            let log_line = format!(
-                "[{} | {}]: {}",
-                timestamp,
-                record.level(),
-                record.args()
-            );
-
-            let colored_line = match record.level() {
-                Level::Error => log_line.red().bold(),
-                Level::Warn => log_line.yellow().bold(),
-                Level::Info => log_line.blue(),
-                Level::Debug => log_line.green(),
-                Level::Trace => log_line.normal(),
-            };
-
-            writeln!(buf, "{}", colored_line)
-        })
-        .init();
+    let shell_args: Vec<String> = std::env::args().skip(1).collect();
+    let mut profile = startup::StartupProfile::new(shell_args.iter().any(|arg| arg == "--profile-startup"));
+    // Reading the umask briefly changes it, which is only safe while no other thread creates files.
+    permissions::umask();
 
-    println_current_dir!();
+    // Parsed on another thread while the terminal and logger are set up, as it means reading a file.
+    let config_loader = std::thread::spawn(|| _ = config::get());
 
-    loop {
-        print!("[sh]$ ");
-        io::stdout().flush().unwrap();
+    profile.phase("terminal", || _ = enable_ansi_support::enable_ansi_support());
+    // Golden-file scripts capture log records themselves, as part of the output they compare.
+    let test_mode = shell_args.iter().any(|arg| arg == "--test");
+    if test_mode || shell_args.iter().any(|arg| arg == "--deterministic") {
+        deterministic::enable();
+    }
+    if !test_mode {
+        profile.phase("logger", || logging::init(logging::Verbosity::from_args(&shell_args)));
+    }
 
-        let mut input = String::new();
-        if io::stdin().read_line(&mut input).is_err() {
-            continue;
-        }
+    if let Err(e) = ctrlc::set_handler(CancellationToken::cancel_current) {
+        error!("installing Ctrl-C handler: {}", e);
+    }
+
+    profile.phase("config", || _ = config_loader.join());
+    // Messages depend on the machine's language too, so deterministic output is in English.
+    let locale = if deterministic::is_enabled() { Some("en") } else { config::get().locale.as_deref() };
+    profile.phase("locale", || i18n::init(locale));
 
-        let mut parts = input.trim().split_whitespace();
-        if let Some(cmd) = parts.next() {
-            let args: Vec<&str> = parts.collect();
+    let no_color = deterministic::is_enabled() || shell_args.iter().any(|arg| arg == "--no-color");
+    profile.phase("theme", || theme::init(&config::get().theme, no_color));
 
-            _ = CommandRegistry::execute_command(cmd, &args)
-                .or_else(|e| match e {
-                    CommandError::CommandNotFound(_) => call_executable(cmd, &args),
-                    other => Err(other),
-                })
-                .map_err(|e| error!("{}", e));
+    if shell_args.iter().any(|arg| arg == "--json") {
+        output::set_format(OutputFormat::Json);
+    }
+    output::set_text_sink(pager::page);
+    // Restriction comes first, so safety and audit never measure or ask about paths outside the root.
+    let restrict_root = shell_args.iter()
+        .position(|arg| arg == "--restrict")
+        .and_then(|at| shell_args.get(at + 1))
+        .map(std::path::PathBuf::from)
+        .or_else(|| config::get().restrict.clone());
+    if let Some(root) = restrict_root {
+        if let Err(e) = restrict::init(&root) {
+            // Running unrestricted instead would defeat the point.
+            error!("{}", e);
+            std::process::exit(1);
         }
+        CommandRegistry::add_middleware(Box::new(restrict::RestrictMiddleware));
+        vfs::set(std::sync::Arc::new(restrict::RestrictedFs::new(vfs::current())));
+    }
+    CommandRegistry::add_middleware(Box::new(JsonFlagMiddleware));
+    CommandRegistry::add_middleware(Box::new(dry_run::DryRunMiddleware));
+    CommandRegistry::add_middleware(Box::new(safety::SafetyMiddleware));
+    // Golden scripts leave nothing in the user's stats and audit logs.
+    if config::get().stats.enabled && !test_mode {
+        CommandRegistry::add_middleware(Box::new(stats::StatsMiddleware));
+    }
+    if config::get().audit.enabled && !test_mode {
+        CommandRegistry::add_middleware(Box::new(audit::AuditMiddleware));
+    }
+
+    // After the restriction, so leaving dry-run mode puts the restricted filesystem back.
+    if shell_args.iter().any(|arg| arg == "--dry-run") {
+        dry_run::set_enabled(true);
+    }
+
+    CommandRegistry::set_lookup_options((&config::get().lookup).into());
+    CommandRegistry::set_precedence(config::get().lookup.precedence());
+    profile.phase("registry", CommandRegistry::index);
+    for collision in CommandRegistry::collisions() {
+        let (kept, shadowed) = (collision.kept, collision.shadowed);
+        warn!(
+            "'{}' is the {} of '{}' ({}) and the {} of '{}' ({}); only the first is reachable by it",
+            collision.key,
+            collision.role_of(kept), kept.name, kept.source,
+            collision.role_of(shadowed), shadowed.name, shadowed.source,
+        );
     }
+
+    // Golden scripts run through the same middleware and lookup as typed lines.
+    if test_mode {
+        std::process::exit(run_golden_tests(&shell_args));
+    }
+
+    // Nothing needs the PATH index before the first command, and a lookup before it is ready builds it itself.
+    profile.phase("PATH index", || _ = std::thread::spawn(executables::rehash));
+
+    if shell_args.iter().any(|arg| arg == "--remote") || config::get().remote.enabled {
+        remote::start(&config::get().remote);
+    }
+
+    session::init(&shell_args);
+
+    profile.report();
+    println_current_dir!();
+
+    let mut shell = Shell::builder()
+        .prompt(|last| {
+            project::refresh();
+            let prompt_config = project::prompt_config(prompt::config());
+            prompt::set_idle_title(&prompt_config);
+            // Catches the index up with the current directory while the user types.
+            file_index::refresh();
+            prompt::render(&prompt_config, last)
+        })
+        .read_line(line_editor::read_line)
+        .on_input(|prompt_text, line| {
+            prompt::collapse(&prompt::config(), prompt_text, line);
+            recording::command_started(line);
+            transcript::command_entered(prompt_text, line);
+            history::add(line);
+        })
+        .expand(history::expand)
+        .expand(|line| Ok(project::expand(line)))
+        .before_command(|name| prompt::set_running_title(&prompt::config(), name))
+        .after_command(|line, finished| {
+            recording::command_finished(finished);
+            command_macros::command_finished(line, finished);
+            notify::command_finished(&config::get().notify, line, finished);
+        })
+        .external(call_executable)
+        .on_not_found(not_found::forward)
+        .on_not_found(autocorrect::correct)
+        .on_not_found(not_found::run_handler)
+        .on_not_found(not_found::suggest_packages)
+        .on_error(|e| error!("{}", e))
+        .build();
+    shell.run();
+    session::exiting();
 }
@@ -0,0 +1,42 @@
+use std::{fs::File, io::{self, Read}, ops::Deref, path::Path};
+
+use memmap2::Mmap;
+
+/// Files from this size on are mapped rather than read, below it the mapping costs more than it saves.
+const MAP_THRESHOLD: u64 = 256 * 1024;
+
+/// Contents of a file, either mapped into memory or read into a buffer.
+pub enum Contents {
+    Mapped(Mmap),
+    Read(Vec<u8>),
+}
+
+impl Deref for Contents {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Contents::Mapped(map) => map,
+            Contents::Read(data) => data,
+        }
+    }
+}
+
+/// Contents of `path`, mapped when `allow_map` is set and the file is large enough, and read
+/// otherwise or when mapping fails, e.g. for pipes and some network filesystems.
+pub fn read(path: &Path, allow_map: bool) -> io::Result<Contents> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+
+    if allow_map && len >= MAP_THRESHOLD {
+        // Safety: the mapping is only read, and dropped before the command returns. Another process
+        // truncating the file meanwhile can still crash the read, the accepted cost of every mmap-based tool.
+        if let Ok(map) = unsafe { Mmap::map(&file) } {
+            return Ok(Contents::Mapped(map));
+        }
+    }
+
+    let mut data = Vec::with_capacity(len as usize);
+    file.read_to_end(&mut data)?;
+    Ok(Contents::Read(data))
+}
@@ -0,0 +1,213 @@
+use std::{ffi::OsStr, fs::{self, Metadata}, io, path::Path, time::SystemTime};
+
+use crate::restrict;
+
+/// Platform-independent view of the metadata shown by `ls -l`.
+pub struct FileDetails {
+    /// `drwxr-xr-x` style permissions on Unix, `darhsl` style attributes on Windows.
+    pub mode: String,
+    pub links: u64,
+    pub owner: String,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl FileDetails {
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        Self {
+            mode: imp::mode_string(metadata),
+            links: imp::link_count(metadata),
+            owner: imp::owner(metadata),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        }
+    }
+}
+
+/// Dotfiles everywhere, plus entries with the hidden attribute on Windows.
+pub fn is_hidden(name: &OsStr, metadata: &Metadata) -> bool {
+    name.to_string_lossy().starts_with('.') || imp::has_hidden_attribute(metadata)
+}
+
+/// Regular files with an execute bit on Unix, or an executable extension on Windows.
+pub fn is_executable(path: &Path, metadata: &Metadata) -> bool {
+    metadata.is_file() && imp::is_executable(path, metadata)
+}
+
+/// Whether commands look through symbolic links (`-L`), only through the ones named as arguments (`-H`),
+/// or treat links as themselves (`-P`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dereference {
+    #[default]
+    Never,
+    CommandLine,
+    Always,
+}
+
+impl Dereference {
+    /// The mode selected by `arg`, if it is one of the dereference flags.
+    pub fn from_flag(arg: &str) -> Option<Self> {
+        match arg {
+            "-L" | "--dereference" => Some(Dereference::Always),
+            "-H" | "--dereference-command-line" => Some(Dereference::CommandLine),
+            "-P" | "--no-dereference" => Some(Dereference::Never),
+            _ => None,
+        }
+    }
+
+    /// Whether a link at `path` is followed; `argument` is set for paths given on the command line.
+    /// In restricted mode only arguments are, as the middleware has checked where they lead, while a
+    /// link found while recursing could point outside the root.
+    pub fn follows(self, argument: bool) -> bool {
+        match self {
+            Dereference::Never => false,
+            Dereference::CommandLine => argument,
+            Dereference::Always => argument || restrict::root().is_none(),
+        }
+    }
+
+    /// Metadata of `path`, or of the link itself when it isn't followed.
+    pub fn metadata(self, path: &Path, argument: bool) -> io::Result<Metadata> {
+        if self.follows(argument) {
+            fs::metadata(path)
+        } else {
+            fs::symlink_metadata(path)
+        }
+    }
+
+    /// Whether `path` is a directory to descend into, which a link only is when followed.
+    pub fn is_dir(self, path: &Path, argument: bool) -> bool {
+        self.metadata(path, argument).is_ok_and(|metadata| metadata.is_dir())
+    }
+}
+
+/// Creates a link at `link` pointing where the link at `original` points.
+pub fn copy_symlink(original: &Path, link: &Path) -> io::Result<()> {
+    let target = fs::read_link(original)?;
+    imp::symlink(&target, link, fs::metadata(original).is_ok_and(|m| m.is_dir()))
+}
+
+/// Whether `a` and `b` name the same existing file, e.g. through different relative paths, links
+/// or hard links.
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    let (Ok(a_metadata), Ok(b_metadata)) = (fs::metadata(a), fs::metadata(b)) else {
+        return false;
+    };
+    match (imp::file_id(&a_metadata), imp::file_id(&b_metadata)) {
+        (Some(a), Some(b)) => a == b,
+        _ => matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b),
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::{fs::Metadata, io, os::unix::fs::MetadataExt, path::Path};
+
+    pub fn symlink(target: &Path, link: &Path, _is_dir: bool) -> io::Result<()> {
+        std::os::unix::fs::symlink(target, link)
+    }
+
+    pub fn mode_string(metadata: &Metadata) -> String {
+        let file_type = metadata.file_type();
+        let kind = if file_type.is_dir() {
+            'd'
+        } else if file_type.is_symlink() {
+            'l'
+        } else {
+            '-'
+        };
+
+        let mode = metadata.mode();
+        let bits = [
+            (0o400, 'r'), (0o200, 'w'), (0o100, 'x'),
+            (0o040, 'r'), (0o020, 'w'), (0o010, 'x'),
+            (0o004, 'r'), (0o002, 'w'), (0o001, 'x'),
+        ];
+
+        std::iter::once(kind)
+            .chain(bits.iter().map(|&(bit, c)| if mode & bit != 0 { c } else { '-' }))
+            .collect()
+    }
+
+    pub fn link_count(metadata: &Metadata) -> u64 {
+        metadata.nlink()
+    }
+
+    /// The device and inode, which hard links share.
+    pub fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+        Some((metadata.dev(), metadata.ino()))
+    }
+
+    pub fn has_hidden_attribute(_metadata: &Metadata) -> bool {
+        false
+    }
+
+    pub fn is_executable(_path: &Path, metadata: &Metadata) -> bool {
+        metadata.mode() & 0o111 != 0
+    }
+
+    pub fn owner(metadata: &Metadata) -> String {
+        uzers::get_user_by_uid(metadata.uid())
+            .map(|user| user.name().to_string_lossy().into_owned())
+            .unwrap_or_else(|| metadata.uid().to_string())
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::{fs::Metadata, io, os::windows::fs::MetadataExt, path::Path};
+
+    pub fn symlink(target: &Path, link: &Path, is_dir: bool) -> io::Result<()> {
+        if is_dir {
+            std::os::windows::fs::symlink_dir(target, link)
+        } else {
+            std::os::windows::fs::symlink_file(target, link)
+        }
+    }
+
+    const FILE_ATTRIBUTE_READONLY: u32 = 0x1;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+    const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x10;
+    const FILE_ATTRIBUTE_ARCHIVE: u32 = 0x20;
+    const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+
+    pub fn mode_string(metadata: &Metadata) -> String {
+        let attributes = metadata.file_attributes();
+        let flags = [
+            (FILE_ATTRIBUTE_DIRECTORY, 'd'),
+            (FILE_ATTRIBUTE_ARCHIVE, 'a'),
+            (FILE_ATTRIBUTE_READONLY, 'r'),
+            (FILE_ATTRIBUTE_HIDDEN, 'h'),
+            (FILE_ATTRIBUTE_SYSTEM, 's'),
+            (FILE_ATTRIBUTE_REPARSE_POINT, 'l'),
+        ];
+
+        flags.iter()
+            .map(|&(flag, c)| if attributes & flag != 0 { c } else { '-' })
+            .collect()
+    }
+
+    pub fn link_count(_metadata: &Metadata) -> u64 {
+        1
+    }
+
+    /// Not available from std's metadata on Windows, where paths are compared instead.
+    pub fn file_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+        None
+    }
+
+    pub fn has_hidden_attribute(metadata: &Metadata) -> bool {
+        metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0
+    }
+
+    pub fn is_executable(path: &Path, _metadata: &Metadata) -> bool {
+        path.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .is_some_and(|ext| matches!(ext.as_str(), "exe" | "bat" | "cmd" | "com" | "ps1"))
+    }
+
+    pub fn owner(_metadata: &Metadata) -> String {
+        "-".to_string()
+    }
+}
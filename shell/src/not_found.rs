@@ -0,0 +1,83 @@
+use std::{collections::BTreeMap, ffi::OsStr, process::Command};
+
+use command_core::{output, CommandError, CommandRegistry};
+use log::{debug, warn};
+use serde::Deserialize;
+
+use crate::{call_executable, config};
+
+/// `[command_not_found]` section of the configuration file.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct NotFoundConfig {
+    /// Names starting with a prefix run with another tool: `"gh-" = "gh"` runs `gh-pr list` as
+    /// `gh pr list`. The longest matching prefix wins.
+    pub forward: BTreeMap<String, String>,
+    /// A program run with the name and arguments instead, e.g. a script that installs what's missing.
+    /// What it returns is what the command returns.
+    pub handler: Option<String>,
+    /// Print the packages that provide the program, from `winget`, `brew` or Ubuntu's `command-not-found`.
+    pub suggest_packages: bool,
+}
+
+/// Runs `name` with the tool its longest `forward` prefix names.
+pub fn forward(name: &str, args: &[&OsStr]) -> Option<Result<(), CommandError>> {
+    let (prefix, tool) = config::get().command_not_found.forward.iter()
+        .filter(|(prefix, _)| name.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())?;
+    let rest = &name[prefix.len()..];
+    let forwarded: Vec<&OsStr> = Some(OsStr::new(rest))
+        .filter(|rest| !rest.is_empty())
+        .into_iter()
+        .chain(args.iter().copied())
+        .collect();
+    Some(CommandRegistry::execute_or_else(tool, &forwarded, call_executable))
+}
+
+/// Leaves `name` to the configured `handler` program.
+pub fn run_handler(name: &str, args: &[&OsStr]) -> Option<Result<(), CommandError>> {
+    let handler = config::get().command_not_found.handler.as_deref()?;
+    let handler_args: Vec<&OsStr> = std::iter::once(OsStr::new(name)).chain(args.iter().copied()).collect();
+    match call_executable(handler, &handler_args) {
+        Err(CommandError::CommandNotFound(..)) => {
+            warn!("The command_not_found handler '{}' was not found", handler);
+            None
+        }
+        result => Some(result),
+    }
+}
+
+/// The package manager query listing packages that provide `name`.
+fn package_query(name: &str) -> Command {
+    let mut command;
+    if cfg!(windows) {
+        command = Command::new("winget");
+        command.args(["search", "--command", name]);
+    } else if cfg!(target_os = "macos") {
+        command = Command::new("brew");
+        command.args(["which-formula", name]);
+    } else {
+        command = Command::new("/usr/lib/command-not-found");
+        command.args(["--", name]);
+    }
+    command
+}
+
+/// Prints the packages that provide `name`, then lets the not-found error stand.
+pub fn suggest_packages(name: &str, _args: &[&OsStr]) -> Option<Result<(), CommandError>> {
+    if !config::get().command_not_found.suggest_packages {
+        return None;
+    }
+
+    match package_query(name).output() {
+        // Ubuntu's helper answers on stderr and fails, the others answer on stdout.
+        Ok(found) => {
+            let text = if found.stdout.is_empty() { found.stderr } else { found.stdout };
+            if let Err(e) = output::print_error(&String::from_utf8_lossy(&text)) {
+                return Some(Err(e));
+            }
+        }
+        Err(e) => debug!("could not look up packages providing '{}': {}", name, e),
+    }
+    None
+}
@@ -0,0 +1,66 @@
+use std::{io::{self, IsTerminal, Write}, time::Duration};
+
+use command_core::LastCommand;
+use log::debug;
+use serde::Deserialize;
+
+use crate::prompt::format_duration;
+
+/// `[notify]` section of the configuration file.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct NotifyConfig {
+    /// Seconds a command must run before its completion is announced.
+    pub after: f64,
+    /// Ring the terminal bell.
+    pub bell: bool,
+    /// Send a desktop notification; needs the `desktop-notifications` feature.
+    pub desktop: bool,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            after: 10.0,
+            bell: true,
+            desktop: false,
+        }
+    }
+}
+
+/// Announces that `command_line` finished, if it ran longer than the configured threshold.
+pub fn command_finished(config: &NotifyConfig, command_line: &str, result: &LastCommand) {
+    if result.duration < Duration::from_secs_f64(config.after.max(0.0)) || !io::stdout().is_terminal() {
+        return;
+    }
+
+    if config.bell {
+        let mut stdout = io::stdout();
+        _ = stdout.write_all(b"\x07");
+        _ = stdout.flush();
+    }
+
+    if config.desktop {
+        let summary = match result.exit_code {
+            0 => "Command finished".to_string(),
+            code => format!("Command failed with exit code {}", code),
+        };
+        show_desktop(&summary, &format!("{}\n{}", command_line, format_duration(result.duration)));
+    }
+}
+
+#[cfg(feature = "desktop-notifications")]
+fn show_desktop(summary: &str, body: &str) {
+    let shown = notify_rust::Notification::new()
+        .summary(summary)
+        .body(body)
+        .show();
+    if let Err(e) = shown {
+        debug!("could not send a desktop notification: {}", e);
+    }
+}
+
+#[cfg(not(feature = "desktop-notifications"))]
+fn show_desktop(_summary: &str, _body: &str) {
+    debug!("desktop notifications need the shell built with the 'desktop-notifications' feature");
+}
@@ -0,0 +1,188 @@
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    process::Child,
+    sync::mpsc::{self, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+use command_core::{output, CancellationToken, CommandError, CommandRegistry};
+use command_macro::command;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+use crate::{call_executable, executable_command, exit_result, spawn_error, theme::{self, Role}};
+
+const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// How often the loop wakes up without changes, to notice Ctrl+C and a run that finished.
+const POLL: Duration = Duration::from_millis(50);
+
+/// A run of the command: a program runs in the background so a change can stop it, a builtin has
+/// already finished by the time it is returned.
+enum Running {
+    Program(Child),
+    Done,
+}
+
+fn start(name: &str, args: &[&OsStr]) -> Result<Running, CommandError> {
+    output::print(&format!("{}\n", theme::paint(Role::Muted, &format!("[onchange] running '{}'", name))))?;
+    if CommandRegistry::resolve(name)?.is_some() {
+        report(name, CommandRegistry::execute_or_else(name, args, call_executable))?;
+        return Ok(Running::Done);
+    }
+    executable_command(name, args)
+        .spawn()
+        .map(Running::Program)
+        .map_err(|e| spawn_error(name, e))
+}
+
+/// Shows how a run ended; a failed one doesn't stop the watching.
+fn report(name: &str, result: Result<(), CommandError>) -> Result<(), CommandError> {
+    let text = match result {
+        Ok(()) => theme::paint(Role::Success, &format!("[onchange] '{}' finished", name)),
+        Err(e) => theme::paint(Role::Failure, &format!("[onchange] {}", e)),
+    };
+    output::print(&format!("{}\n", text))
+}
+
+/// The `.gitignore` and `.ignore` rules of `watched` and of the directories above it up to the root
+/// of its repository, one matcher per directory, so what a build writes to e.g. `target` doesn't
+/// trigger another run. Git's own directory is always ignored.
+fn ignore_rules(watched: &Path) -> Vec<Gitignore> {
+    let Ok(path) = watched.canonicalize() else {
+        return Vec::new();
+    };
+    let dir = if path.is_dir() { path.as_path() } else { path.parent().unwrap_or(&path) };
+    let repository = dir.ancestors().find(|ancestor| ancestor.join(".git").exists());
+    let dirs: Vec<&Path> = match repository {
+        Some(root) => dir.ancestors().take_while(|ancestor| ancestor.starts_with(root)).collect(),
+        None => vec![dir],
+    };
+
+    dirs.into_iter()
+        .filter_map(|dir| {
+            let mut builder = GitignoreBuilder::new(dir);
+            for name in [".gitignore", ".ignore"] {
+                let file = dir.join(name);
+                if file.is_file() {
+                    _ = builder.add(file);
+                }
+            }
+            _ = builder.add_line(None, ".git/");
+            builder.build().ok()
+        })
+        .collect()
+}
+
+/// Whether all paths of `event` are ignored; an event without paths counts as a change.
+fn is_ignored(event: &Event, rules: &[Gitignore]) -> bool {
+    let ignored = |path: &PathBuf| {
+        // Watching a relative path reports relative ones.
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+        rules.iter().any(|rules| {
+            path.starts_with(rules.path()) && rules.matched_path_or_any_parents(&path, path.is_dir()).is_ignore()
+        })
+    };
+    !event.paths.is_empty() && event.paths.iter().all(ignored)
+}
+
+fn stop(running: &mut Running) {
+    if let Running::Program(child) = running {
+        // It may have exited in the meantime, which is just as good.
+        _ = child.kill();
+        _ = child.wait();
+    }
+    *running = Running::Done;
+}
+
+#[command(
+    name = "onchange",
+    description = "Run a command, and run it again whenever the watched paths change, stopping a run still in progress; changes to paths .gitignore lists don't count",
+    usage = "onchange [--debounce <ms>] <path...> -- <command...>",
+    examples = ["onchange src -- cargo build", "onchange --debounce 500 docs -- make html"]
+)]
+pub fn cmd_onchange(args: Vec<&OsStr>) -> Result<(), CommandError> {
+    let usage = || CommandError::InvalidArguments("Usage: onchange [--debounce <ms>] <path...> -- <command...>".to_string());
+    let separator = args.iter().position(|arg| *arg == "--").ok_or_else(usage)?;
+    let (options, command) = (&args[..separator], &args[separator + 1..]);
+
+    let mut debounce = DEFAULT_DEBOUNCE;
+    let mut watched = Vec::new();
+    let mut options = options.iter();
+    while let Some(arg) = options.next() {
+        match arg.to_str() {
+            Some("--debounce") => {
+                let ms = options.next()
+                    .and_then(|value| value.to_str())
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(|| CommandError::InvalidArguments("--debounce needs a number of milliseconds".to_string()))?;
+                debounce = Duration::from_millis(ms);
+            }
+            _ => watched.push(Path::new(arg)),
+        }
+    }
+    let [name, command_args @ ..] = command else {
+        return Err(usage());
+    };
+    if watched.is_empty() {
+        return Err(usage());
+    }
+    let name = name.to_string_lossy();
+
+    let (sender, changes) = mpsc::channel();
+    let rules: Vec<Gitignore> = watched.iter().flat_map(|path| ignore_rules(path)).collect();
+    let mut watcher = notify::recommended_watcher(sender)
+        .map_err(|e| CommandError::CommandFailed(format!("Could not watch for changes: {}", e)))?;
+    for path in &watched {
+        watcher.watch(path, RecursiveMode::Recursive)
+            .map_err(|e| CommandError::CommandFailed(format!("Could not watch '{}': {}", path.display(), e)))?;
+    }
+
+    let token = CancellationToken::current();
+    let mut running = start(&name, command_args)?;
+    // When the last change of the current burst arrived.
+    let mut changed: Option<Instant> = None;
+    loop {
+        if token.is_cancelled() {
+            stop(&mut running);
+            return token.check();
+        }
+
+        match changes.recv_timeout(POLL) {
+            Ok(Ok(event)) if !matches!(event.kind, EventKind::Access(_)) && !is_ignored(&event, &rules) => {
+                changed = Some(Instant::now());
+            }
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => output::print_error(&format!("[onchange] {}\n", e))?,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(CommandError::CommandFailed("Stopped receiving changes".to_string()));
+            }
+        }
+
+        if let Running::Program(child) = &mut running {
+            if let Some(status) = child.try_wait()? {
+                report(&name, exit_result(&name, status))?;
+                running = Running::Done;
+            }
+        }
+
+        if changed.is_some_and(|at| at.elapsed() >= debounce) {
+            changed = None;
+            stop(&mut running);
+            // A run that couldn't start, e.g. as the program is being rebuilt, may on the next change.
+            running = match start(&name, command_args) {
+                Ok(started) => started,
+                Err(e) => {
+                    report(&name, Err(e))?;
+                    Running::Done
+                }
+            };
+            // Builtins run in place, so changes they made themselves are in the queue by now.
+            if matches!(running, Running::Done) {
+                while changes.try_recv().is_ok() {}
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+    process::{Command, Stdio},
+};
+
+use crossterm::{
+    cursor, event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, Clear, ClearType, DisableLineWrap, EnableLineWrap, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use log::warn;
+
+/// Prints `text`, paging it when stdout is a terminal and the text is taller than the window.
+/// Uses `$PAGER` when set, otherwise the built-in pager.
+pub fn page(text: &str) -> io::Result<()> {
+    let rows = terminal::size().map(|(_, rows)| rows as usize).unwrap_or(usize::MAX);
+    if !io::stdout().is_terminal() || text.lines().count() < rows {
+        print!("{}", text);
+        return io::stdout().flush();
+    }
+
+    if let Some(pager) = env::var("PAGER").ok().filter(|p| !p.trim().is_empty()) {
+        match run_external(&pager, text) {
+            Ok(()) => return Ok(()),
+            Err(e) => warn!("could not run pager '{}': {}, using the built-in pager", pager, e),
+        }
+    }
+
+    run_internal(text)
+}
+
+fn run_external(pager: &str, text: &str) -> io::Result<()> {
+    let mut parts = pager.split_whitespace();
+    let program = parts.next().unwrap_or_default();
+
+    let mut command = Command::new(program);
+    command.args(parts).stdin(Stdio::piped());
+    if env::var_os("LESS").is_none() {
+        // Keep colors and quit when the text fits after all.
+        command.env("LESS", "FRX");
+    }
+
+    let mut child = command.spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager may exit before reading everything, e.g. on `q`.
+        _ = stdin.write_all(text.as_bytes());
+    }
+    child.wait().map(|_| ())
+}
+
+/// Raw mode on the alternate screen, restored when dropped, so an error or a panic in a full-screen
+/// view doesn't leave the terminal unusable.
+pub(crate) struct FullScreen;
+
+impl FullScreen {
+    pub(crate) fn enter(stdout: &mut impl Write) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let screen = FullScreen;
+        execute!(stdout, EnterAlternateScreen, DisableLineWrap, cursor::Hide)?;
+        Ok(screen)
+    }
+}
+
+impl Drop for FullScreen {
+    fn drop(&mut self) {
+        _ = execute!(io::stdout(), cursor::Show, EnableLineWrap, LeaveAlternateScreen);
+        _ = terminal::disable_raw_mode();
+    }
+}
+
+fn run_internal(text: &str) -> io::Result<()> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut stdout = io::stdout();
+
+    let _screen = FullScreen::enter(&mut stdout)?;
+    Pager::new(&lines).run(&mut stdout)
+}
+
+struct Pager<'a> {
+    lines: &'a [&'a str],
+    top: usize,
+    query: Option<String>,
+    message: Option<String>,
+}
+
+impl<'a> Pager<'a> {
+    fn new(lines: &'a [&'a str]) -> Self {
+        Self {
+            lines,
+            top: 0,
+            query: None,
+            message: None,
+        }
+    }
+
+    fn run(&mut self, out: &mut impl Write) -> io::Result<()> {
+        loop {
+            let (_, rows) = terminal::size()?;
+            let page = (rows as usize).saturating_sub(1).max(1);
+            let max_top = self.lines.len().saturating_sub(page);
+            self.top = self.top.min(max_top);
+
+            self.draw(out, page)?;
+
+            let Event::Key(KeyEvent { code, modifiers, kind: KeyEventKind::Press, .. }) = event::read()? else {
+                continue;
+            };
+            self.message = None;
+
+            match code {
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Down | KeyCode::Enter | KeyCode::Char('j') => self.top += 1,
+                KeyCode::Up | KeyCode::Char('k') => self.top = self.top.saturating_sub(1),
+                KeyCode::PageDown | KeyCode::Char(' ') | KeyCode::Char('f') => self.top += page,
+                KeyCode::PageUp | KeyCode::Char('b') => self.top = self.top.saturating_sub(page),
+                KeyCode::Home | KeyCode::Char('g') => self.top = 0,
+                KeyCode::End | KeyCode::Char('G') => self.top = max_top,
+                KeyCode::Char('/') => {
+                    self.query = read_query(out, page as u16)?;
+                    self.search(true);
+                }
+                KeyCode::Char('n') => self.search(true),
+                KeyCode::Char('N') => self.search(false),
+                _ => {}
+            }
+        }
+    }
+
+    /// Moves to the next (or previous) line containing the current query.
+    fn search(&mut self, forward: bool) {
+        let Some(query) = self.query.as_deref().filter(|q| !q.is_empty()) else {
+            return;
+        };
+
+        let found = if forward {
+            (self.top + 1..self.lines.len()).find(|&i| self.lines[i].contains(query))
+        } else {
+            (0..self.top).rev().find(|&i| self.lines[i].contains(query))
+        };
+
+        match found {
+            Some(line) => self.top = line,
+            None => self.message = Some(format!("Pattern not found: {}", query)),
+        }
+    }
+
+    fn draw(&self, out: &mut impl Write, page: usize) -> io::Result<()> {
+        queue!(out, Clear(ClearType::All))?;
+
+        for (row, line) in self.lines.iter().skip(self.top).take(page).enumerate() {
+            queue!(out, cursor::MoveTo(0, row as u16), Print(line), SetAttribute(Attribute::Reset))?;
+        }
+
+        let last = (self.top + page).min(self.lines.len());
+        let status = match &self.message {
+            Some(message) => message.clone(),
+            None => format!(
+                "lines {}-{} of {} (q quit, / search, n/N next/previous)",
+                self.top + 1,
+                last,
+                self.lines.len()
+            ),
+        };
+        queue!(
+            out,
+            cursor::MoveTo(0, page as u16),
+            SetAttribute(Attribute::Reverse),
+            Print(status),
+            SetAttribute(Attribute::Reset)
+        )?;
+
+        out.flush()
+    }
+}
+
+/// Reads a search query on the status line. Returns `None` when cancelled with Esc.
+fn read_query(out: &mut impl Write, row: u16) -> io::Result<Option<String>> {
+    let mut query = String::new();
+
+    loop {
+        queue!(out, cursor::MoveTo(0, row), Clear(ClearType::CurrentLine), Print('/'), Print(&query))?;
+        out.flush()?;
+
+        let Event::Key(KeyEvent { code, kind: KeyEventKind::Press, .. }) = event::read()? else {
+            continue;
+        };
+
+        match code {
+            KeyCode::Enter => return Ok(Some(query)),
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Backspace => {
+                query.pop();
+            }
+            KeyCode::Char(c) => query.push(c),
+            _ => {}
+        }
+    }
+}
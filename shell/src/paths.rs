@@ -0,0 +1,45 @@
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR};
+
+use crate::config;
+
+/// `path` with `.` removed and `..` folded into the preceding component, without touching the filesystem.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                // `..` at the root stays at the root; leading `..` of a relative path is kept.
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(".."),
+            },
+            component => normalized.push(component),
+        }
+    }
+
+    if normalized.as_os_str().is_empty() {
+        normalized.push(".");
+    }
+    normalized
+}
+
+/// `path` as shown to the user: normalized, with the platform's separators and the home directory as `~`.
+pub fn display(path: &Path) -> String {
+    let path = normalize(path);
+    let home = config::home_dir().map(|home| normalize(&home));
+
+    let shown = match home.as_deref().and_then(|home| path.strip_prefix(home).ok()) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => format!("~{}{}", MAIN_SEPARATOR, rest.display()),
+        None => path.display().to_string(),
+    };
+
+    if cfg!(windows) {
+        shown.replace('/', "\\")
+    } else {
+        shown
+    }
+}
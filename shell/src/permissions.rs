@@ -0,0 +1,303 @@
+use std::{fs::{self, Metadata}, io, path::Path};
+
+use command_core::CommandError;
+
+/// Permission bits together with the Windows hidden attribute, which `chmod` changes through one syntax.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mode {
+    pub bits: u32,
+    pub hidden: bool,
+}
+
+impl Mode {
+    /// The mode of the file `metadata` belongs to. On Windows the bits only reflect the readonly attribute.
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        imp::mode(metadata)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Add,
+    Remove,
+    Set,
+}
+
+/// One `who op perms` part of a symbolic mode, e.g. `go-w`.
+#[derive(Clone, Debug)]
+pub struct Clause {
+    /// Bits of the users named, or `None` for none named, which means everyone minus the umask.
+    who: Option<u32>,
+    op: Op,
+    perms: String,
+}
+
+/// A mode as given to `chmod` or `mkdir -m`: octal like `755`, or symbolic like `u+x,go-w` or `+h`.
+#[derive(Clone, Debug)]
+pub enum ModeChange {
+    Octal(u32),
+    Symbolic(Vec<Clause>),
+}
+
+const USER: u32 = 0o4700;
+const GROUP: u32 = 0o2070;
+const OTHER: u32 = 0o1007;
+const ALL: u32 = 0o7777;
+
+impl ModeChange {
+    pub fn parse(text: &str) -> Result<Self, CommandError> {
+        if text.chars().all(|c| c.is_ascii_digit()) {
+            return parse_octal(text).map(ModeChange::Octal);
+        }
+
+        let invalid = || CommandError::InvalidArguments(format!("Invalid mode '{}'", text));
+        let mut clauses = Vec::new();
+
+        for part in text.split(',') {
+            let op_at = part.find(['+', '-', '=']).ok_or_else(invalid)?;
+            let (who, rest) = part.split_at(op_at);
+
+            let who = if who.is_empty() {
+                None
+            } else {
+                Some(who.chars().try_fold(0, |mask, c| match c {
+                    'u' => Ok(mask | USER),
+                    'g' => Ok(mask | GROUP),
+                    'o' => Ok(mask | OTHER),
+                    'a' => Ok(mask | ALL),
+                    _ => Err(invalid()),
+                })?)
+            };
+
+            // `u+x-w` is two changes for the same users.
+            let mut chars = rest.chars().peekable();
+            while let Some(op) = chars.next() {
+                let op = match op {
+                    '+' => Op::Add,
+                    '-' => Op::Remove,
+                    '=' => Op::Set,
+                    _ => return Err(invalid()),
+                };
+
+                let mut perms = String::new();
+                while let Some(&c) = chars.peek().filter(|c| !matches!(c, '+' | '-' | '=')) {
+                    if !"rwxXsth".contains(c) {
+                        return Err(invalid());
+                    }
+                    if c == 'h' && !cfg!(windows) {
+                        return Err(CommandError::InvalidArguments("The hidden attribute only exists on Windows".to_string()));
+                    }
+                    perms.push(c);
+                    chars.next();
+                }
+                clauses.push(Clause { who, op, perms });
+            }
+        }
+
+        Ok(ModeChange::Symbolic(clauses))
+    }
+
+    /// `mode` after the change, for a directory when `is_dir`, which decides what `X` means.
+    pub fn apply(&self, mode: Mode, is_dir: bool) -> Mode {
+        let clauses = match self {
+            ModeChange::Octal(bits) => return Mode { bits: *bits, hidden: mode.hidden },
+            ModeChange::Symbolic(clauses) => clauses,
+        };
+
+        let mut mode = mode;
+        for clause in clauses {
+            let who = clause.who.unwrap_or(ALL & !umask());
+            let bits = clause.perms.chars().fold(0, |bits, c| bits | match c {
+                'r' => 0o444,
+                'w' => 0o222,
+                'x' => 0o111,
+                'X' if is_dir || mode.bits & 0o111 != 0 => 0o111,
+                's' => 0o6000,
+                't' => 0o1000,
+                _ => 0,
+            }) & who;
+            let hidden = clause.perms.contains('h');
+
+            match clause.op {
+                Op::Add => mode.bits |= bits,
+                Op::Remove => mode.bits &= !bits,
+                Op::Set => mode.bits = (mode.bits & !who) | bits,
+            }
+            if hidden {
+                mode.hidden = clause.op != Op::Remove;
+            }
+        }
+        mode
+    }
+}
+
+/// Parses an octal mode such as `755` or `0644`.
+pub fn parse_octal(text: &str) -> Result<u32, CommandError> {
+    u32::from_str_radix(text, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+        .ok_or_else(|| CommandError::InvalidArguments(format!("Invalid octal mode '{}'", text)))
+}
+
+/// `u=rwx,g=rx,o=rx` style description of the permissions in `mode`.
+pub fn symbolic(mode: u32) -> String {
+    [('u', 6), ('g', 3), ('o', 0)]
+        .iter()
+        .map(|&(who, shift)| {
+            let bits = (mode >> shift) & 0o7;
+            let perms: String = [(0o4, 'r'), (0o2, 'w'), (0o1, 'x')]
+                .iter()
+                .filter(|&&(bit, _)| bits & bit != 0)
+                .map(|&(_, c)| c)
+                .collect();
+            format!("{}={}", who, perms)
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Permission bits removed from files and directories created by builtins.
+pub fn umask() -> u32 {
+    imp::umask()
+}
+
+pub fn set_umask(mask: u32) {
+    imp::set_umask(mask & 0o777);
+}
+
+/// Sets the permissions and hidden attribute of `path`. On Windows only the owner write bit is kept,
+/// as the readonly attribute.
+pub fn set(path: &Path, mode: Mode) -> io::Result<()> {
+    imp::set(path, mode)
+}
+
+/// Applies the umask to `path`, which a builtin just created. The OS already does so on Unix.
+pub fn apply_umask(path: &Path) -> io::Result<()> {
+    imp::apply_umask(path)
+}
+
+/// Creates `dir`, and its missing parents with `parents`, giving the last one `mode` if set.
+pub fn create_dir(dir: &Path, parents: bool, mode: Option<&ModeChange>) -> io::Result<()> {
+    if parents {
+        fs::create_dir_all(dir)?;
+    } else {
+        fs::create_dir(dir)?;
+    }
+
+    match mode {
+        // Applied to `a=rwx` and set exactly, like `mkdir -m`, rather than filtered through the umask.
+        Some(change) => set(dir, change.apply(Mode { bits: 0o777, hidden: false }, true)),
+        None => apply_umask(dir),
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::{fs::{self, Metadata}, io, os::unix::fs::PermissionsExt, path::Path, sync::Mutex};
+
+    use super::Mode;
+
+    /// The umask as last read or set, so it is only read from the OS once.
+    static UMASK: Mutex<Option<u32>> = Mutex::new(None);
+
+    pub fn mode(metadata: &Metadata) -> Mode {
+        Mode {
+            bits: metadata.permissions().mode() & 0o7777,
+            hidden: false,
+        }
+    }
+
+    pub fn umask() -> u32 {
+        let mut cached = UMASK.lock().unwrap_or_else(|e| e.into_inner());
+        *cached.get_or_insert_with(|| {
+            // SAFETY: umask(2) only swaps the process's file mode creation mask and can't fail.
+            // There is no way to read it without setting it, so a file another thread creates
+            // between the two calls would get 022; `main` reads it before starting any threads,
+            // and later reads come from `UMASK`.
+            unsafe {
+                let mask = libc::umask(0o022);
+                libc::umask(mask);
+                mask as u32
+            }
+        })
+    }
+
+    pub fn set_umask(mask: u32) {
+        let mut cached = UMASK.lock().unwrap_or_else(|e| e.into_inner());
+        // SAFETY: umask(2) only sets the process's file mode creation mask and can't fail.
+        unsafe {
+            libc::umask(mask as libc::mode_t);
+        }
+        *cached = Some(mask);
+    }
+
+    pub fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+    }
+
+    pub fn set(path: &Path, mode: Mode) -> io::Result<()> {
+        set_mode(path, mode.bits)
+    }
+
+    pub fn apply_umask(_path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::{fs::{self, Metadata}, io, os::windows::fs::MetadataExt, path::Path, sync::RwLock};
+
+    use windows::{core::HSTRING, Win32::Storage::FileSystem::{SetFileAttributesW, FILE_FLAGS_AND_ATTRIBUTES}};
+
+    use super::Mode;
+
+    const FILE_ATTRIBUTE_READONLY: u32 = 0x1;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+
+    static UMASK: RwLock<u32> = RwLock::new(0o022);
+
+    pub fn mode(metadata: &Metadata) -> Mode {
+        let attributes = metadata.file_attributes();
+        Mode {
+            bits: if attributes & FILE_ATTRIBUTE_READONLY != 0 { 0o555 } else { 0o777 },
+            hidden: attributes & FILE_ATTRIBUTE_HIDDEN != 0,
+        }
+    }
+
+    pub fn umask() -> u32 {
+        *UMASK.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_umask(mask: u32) {
+        *UMASK.write().unwrap_or_else(|e| e.into_inner()) = mask;
+    }
+
+    pub fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+        let mut permissions = fs::metadata(path)?.permissions();
+        permissions.set_readonly(mode & 0o200 == 0);
+        fs::set_permissions(path, permissions)
+    }
+
+    pub fn set(path: &Path, mode: Mode) -> io::Result<()> {
+        let mut attributes = fs::metadata(path)?.file_attributes();
+        for (flag, on) in [(FILE_ATTRIBUTE_READONLY, mode.bits & 0o200 == 0), (FILE_ATTRIBUTE_HIDDEN, mode.hidden)] {
+            if on {
+                attributes |= flag;
+            } else {
+                attributes &= !flag;
+            }
+        }
+
+        unsafe { SetFileAttributesW(&HSTRING::from(path.as_os_str()), FILE_FLAGS_AND_ATTRIBUTES(attributes)) }
+            .map_err(io::Error::from)
+    }
+
+    pub fn apply_umask(path: &Path) -> io::Result<()> {
+        if umask() & 0o200 != 0 {
+            set_mode(path, 0o444)
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use sysinfo::{MemoryRefreshKind, Pid, ProcessRefreshKind, RefreshKind, System};
+
+/// A running process as listed by `top`.
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub name: String,
+    /// Percent of one core since the previous refresh, so above 100 for a process using several.
+    pub cpu: f32,
+    /// Resident memory in bytes.
+    pub memory: u64,
+}
+
+/// The processes of the machine, refreshed on demand. CPU usage is measured between two refreshes,
+/// so the first listing shows none.
+pub struct Processes {
+    system: System,
+}
+
+impl Processes {
+    pub fn new() -> Self {
+        let refresh = RefreshKind::new()
+            .with_processes(ProcessRefreshKind::new().with_cpu().with_memory())
+            .with_memory(MemoryRefreshKind::new().with_ram())
+            .with_cpu(Default::default());
+        Self { system: System::new_with_specifics(refresh) }
+    }
+
+    pub fn refresh(&mut self) {
+        self.system.refresh_processes_specifics(ProcessRefreshKind::new().with_cpu().with_memory());
+        self.system.refresh_memory();
+        self.system.refresh_cpu_usage();
+    }
+
+    pub fn list(&self) -> Vec<ProcessInfo> {
+        self.system.processes().values()
+            .map(|process| ProcessInfo {
+                pid: process.pid().as_u32(),
+                name: process.name().to_string(),
+                cpu: process.cpu_usage(),
+                memory: process.memory(),
+            })
+            .collect()
+    }
+
+    /// Average CPU usage over all cores, in percent.
+    pub fn cpu_usage(&self) -> f32 {
+        self.system.global_cpu_info().cpu_usage()
+    }
+
+    /// Used and total memory in bytes.
+    pub fn memory(&self) -> (u64, u64) {
+        (self.system.used_memory(), self.system.total_memory())
+    }
+
+    /// Asks process `pid` to terminate, or kills it where that can't be asked. Returns whether the
+    /// signal was delivered.
+    pub fn kill(&self, pid: u32) -> bool {
+        let Some(process) = self.system.process(Pid::from_u32(pid)) else {
+            return false;
+        };
+        process.kill_with(sysinfo::Signal::Term).unwrap_or_else(|| process.kill())
+    }
+}
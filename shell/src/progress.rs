@@ -0,0 +1,51 @@
+use std::{io::{self, IsTerminal}, time::Duration};
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Progress reporting for long-running operations, drawn on stderr.
+/// Hidden automatically when stderr is not a terminal.
+pub struct Progress(ProgressBar);
+
+impl Progress {
+    fn new(total: Option<u64>, template: &str, message: impl Into<String>) -> Self {
+        if !io::stderr().is_terminal() {
+            return Progress(ProgressBar::hidden());
+        }
+
+        let style = ProgressStyle::with_template(template)
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+            .progress_chars("=> ");
+
+        let bar = total.map_or_else(ProgressBar::no_length, ProgressBar::new)
+            .with_style(style)
+            .with_message(message.into());
+        bar.enable_steady_tick(Duration::from_millis(100));
+
+        Progress(bar)
+    }
+
+    /// Progress over a number of files or other items.
+    pub fn items(total: u64, message: impl Into<String>) -> Self {
+        Self::new(Some(total), "{msg} [{bar:30}] {pos}/{len} items, {per_sec}, eta {eta}", message)
+    }
+
+    /// Progress over a number of bytes.
+    pub fn bytes(total: u64, message: impl Into<String>) -> Self {
+        Self::new(Some(total), "{msg} [{bar:30}] {bytes}/{total_bytes}, {bytes_per_sec}, eta {eta}", message)
+    }
+
+    /// Progress over items whose number isn't known upfront.
+    pub fn count(message: impl Into<String>) -> Self {
+        Self::new(None, "{spinner} {msg}: {pos} items, {per_sec}", message)
+    }
+
+    pub fn inc(&self, delta: u64) {
+        self.0.inc(delta);
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.0.finish_and_clear();
+    }
+}
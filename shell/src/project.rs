@@ -0,0 +1,271 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    ffi::OsString,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use command_core::{output, CommandError};
+use command_macro::command;
+use log::{error, info, warn};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{abbr, config, paths, prompt::{PromptConfig, Segment}};
+
+/// Name of the file that makes a directory and everything below it a project.
+const PROJECT_FILE: &str = ".shellproject";
+
+/// `[project]` section of the configuration file.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ProjectConfig {
+    /// Load the `.shellproject` of the directory the shell is in, or of the nearest one above it.
+    pub enabled: bool,
+}
+
+impl Default for ProjectConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Changes to the prompt while inside the project; what's left out stays as configured.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ProjectPrompt {
+    symbol: Option<String>,
+    left: Option<Vec<Segment>>,
+    right: Option<Vec<Segment>>,
+    colors: HashMap<String, String>,
+}
+
+/// Contents of a `.shellproject` file.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ProjectFile {
+    /// Abbreviations that only expand inside the project, ahead of the global ones.
+    aliases: BTreeMap<String, String>,
+    env: BTreeMap<String, String>,
+    prompt: ProjectPrompt,
+}
+
+/// A loaded project, with what it replaced so leaving it can put that back.
+struct Active {
+    file: PathBuf,
+    project: ProjectFile,
+    previous_env: Vec<(String, Option<OsString>)>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Where the project was last looked for; the search is repeated once the directory changes.
+    cwd: Option<PathBuf>,
+    active: Option<Active>,
+    /// A project file found but not allowed, so the warning isn't repeated for every directory in it.
+    not_allowed: Option<PathBuf>,
+}
+
+static STATE: Mutex<Option<State>> = Mutex::new(None);
+
+fn with_state<R>(f: impl FnOnce(&mut State) -> R) -> R {
+    let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    f(state.get_or_insert_with(State::default))
+}
+
+/// The project file in `dir` or the nearest directory above it.
+fn find(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(PROJECT_FILE))
+        .find(|file| file.is_file())
+}
+
+/// `~/.shell/allowed_projects`: one hash per allowed project file, see [`fingerprint`].
+fn allowed_path() -> Option<PathBuf> {
+    config::data_dir().map(|dir| dir.join("allowed_projects"))
+}
+
+/// Covers the path and the contents, so an allowed file that changes has to be allowed again.
+fn fingerprint(file: &Path, contents: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(file.to_string_lossy().as_bytes());
+    hasher.update([0]);
+    hasher.update(contents.as_bytes());
+    hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn is_allowed(fingerprint: &str) -> bool {
+    allowed_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .is_some_and(|allowed| allowed.lines().any(|line| line == fingerprint))
+}
+
+/// Reads and applies `file`, or returns `None` if it hasn't been allowed.
+fn load(file: &Path) -> Result<Option<Active>, CommandError> {
+    let contents = fs::read_to_string(file).map_err(|e| CommandError::FileReadError(file.to_path_buf(), e))?;
+    if !is_allowed(&fingerprint(file, &contents)) {
+        return Ok(None);
+    }
+    let project: ProjectFile = toml::from_str(&contents)
+        .map_err(|e| CommandError::CommandFailed(format!("Error parsing '{}': {}", file.display(), e)))?;
+
+    let previous_env = project.env.iter()
+        .map(|(name, value)| {
+            let previous = env::var_os(name);
+            env::set_var(name, value);
+            (name.clone(), previous)
+        })
+        .collect();
+    Ok(Some(Active { file: file.to_path_buf(), project, previous_env }))
+}
+
+fn unload(active: Active) {
+    for (name, previous) in active.previous_env {
+        match previous {
+            Some(value) => env::set_var(&name, value),
+            None => env::remove_var(&name),
+        }
+    }
+    info!("Left project '{}'", paths::display(active.file.parent().unwrap_or(&active.file)));
+}
+
+/// Loads the project of the current directory and unloads the one it left; called before each prompt.
+pub fn refresh() {
+    if !config::get().project.enabled {
+        return;
+    }
+    let Ok(cwd) = env::current_dir() else {
+        return;
+    };
+
+    with_state(|state| {
+        if state.cwd.as_ref() == Some(&cwd) {
+            return;
+        }
+        state.cwd = Some(cwd.clone());
+
+        let found = find(&cwd);
+        if state.active.as_ref().map(|active| &active.file) == found.as_ref() {
+            return;
+        }
+        if let Some(active) = state.active.take() {
+            unload(active);
+        }
+        let Some(file) = found else {
+            state.not_allowed = None;
+            return;
+        };
+
+        match load(&file) {
+            Ok(Some(active)) => {
+                info!("Loaded project '{}'", paths::display(file.parent().unwrap_or(&file)));
+                state.active = Some(active);
+                state.not_allowed = None;
+            }
+            Ok(None) if state.not_allowed.as_ref() == Some(&file) => {}
+            Ok(None) => {
+                warn!("'{}' isn't allowed to load; review it, then run `project allow`", paths::display(&file));
+                state.not_allowed = Some(file);
+            }
+            Err(e) => error!("{}", e),
+        }
+    });
+}
+
+/// `line` with a project alias in command position replaced by what it stands for.
+pub fn expand(line: &str) -> Option<String> {
+    with_state(|state| {
+        let aliases = &state.active.as_ref()?.project.aliases;
+        abbr::replace_command(line, |word| aliases.get(word).cloned())
+    })
+}
+
+/// `config` with the active project's prompt changes applied.
+pub fn prompt_config(config: Arc<PromptConfig>) -> Arc<PromptConfig> {
+    with_state(|state| {
+        let Some(prompt) = state.active.as_ref().map(|active| &active.project.prompt) else {
+            return Arc::clone(&config);
+        };
+        let mut changed = PromptConfig::clone(&config);
+        if let Some(symbol) = &prompt.symbol {
+            changed.symbol = symbol.clone();
+        }
+        if let Some(left) = &prompt.left {
+            changed.left = left.clone();
+        }
+        if let Some(right) = &prompt.right {
+            changed.right = right.clone();
+        }
+        changed.colors.extend(prompt.colors.iter().map(|(name, sgr)| (name.clone(), sgr.clone())));
+        Arc::new(changed)
+    })
+}
+
+/// Records the project file of the current directory as allowed, then loads it.
+fn allow() -> Result<(), CommandError> {
+    let cwd = env::current_dir()?;
+    let file = find(&cwd)
+        .ok_or_else(|| CommandError::CommandFailed(format!("No {} here or in a directory above", PROJECT_FILE)))?;
+    let contents = fs::read_to_string(&file).map_err(|e| CommandError::FileReadError(file.clone(), e))?;
+    let fingerprint = fingerprint(&file, &contents);
+
+    if !is_allowed(&fingerprint) {
+        let path = allowed_path()
+            .ok_or_else(|| CommandError::CommandFailed("Could not determine the home directory to save allowed projects".to_string()))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut allowed = OpenOptions::new().create(true).append(true).open(&path)?;
+        writeln!(allowed, "{}", fingerprint)?;
+    }
+    reload();
+    Ok(())
+}
+
+/// Unloads the project and looks for it again, e.g. after its file changed.
+fn reload() {
+    with_state(|state| {
+        if let Some(active) = state.active.take() {
+            unload(active);
+        }
+        state.cwd = None;
+        state.not_allowed = None;
+    });
+    refresh();
+}
+
+#[command(
+    name = "project",
+    description = "Show, allow or reload the .shellproject of the current directory",
+    usage = "project [allow|reload]",
+    examples = ["project", "project allow", "project reload"]
+)]
+pub fn cmd_project(action: Option<&str>) -> Result<(), CommandError> {
+    match action {
+        None => {
+            let text = with_state(|state| match &state.active {
+                Some(active) => {
+                    let mut text = format!("{}\n", paths::display(&active.file));
+                    for (name, expansion) in &active.project.aliases {
+                        text.push_str(&format!("  alias {} = {}\n", name, expansion));
+                    }
+                    for (name, value) in &active.project.env {
+                        text.push_str(&format!("  env {}={}\n", name, value));
+                    }
+                    text
+                }
+                None => "No project is loaded\n".to_string(),
+            });
+            output::print(&text)
+        }
+        Some("allow") => allow(),
+        Some("reload") => {
+            reload();
+            Ok(())
+        }
+        Some(other) => Err(CommandError::InvalidArguments(format!("Unknown action '{}', expected 'allow' or 'reload'", other))),
+    }
+}
@@ -0,0 +1,228 @@
+use std::{collections::HashMap, env, fs, io::{self, IsTerminal, Write}, path::PathBuf, sync::{Arc, RwLock}, time::Duration};
+
+use command_core::{table::visible_width, CommandError, LastCommand};
+use crossterm::{execute, terminal::SetTitle};
+use humansize::{format_size, DECIMAL};
+use serde::Deserialize;
+
+use crate::{columns, config, deterministic, get_current_user, paths, prompt_cache, theme::{self, Role}};
+
+/// A piece of information the prompt can show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Segment {
+    Clock,
+    Status,
+    Duration,
+    Git,
+    User,
+    Cwd,
+    Disk,
+}
+
+impl Segment {
+    fn name(self) -> &'static str {
+        match self {
+            Segment::Clock => "clock",
+            Segment::Status => "status",
+            Segment::Duration => "duration",
+            Segment::Git => "git",
+            Segment::User => "user",
+            Segment::Cwd => "cwd",
+            Segment::Disk => "disk",
+        }
+    }
+}
+
+/// Prompt layout, from `~/.shell/prompt.toml` or else the `[prompt]` section of the configuration file.
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct PromptConfig {
+    /// Segments drawn before the prompt symbol.
+    pub left: Vec<Segment>,
+    /// Segments drawn at the right edge of the terminal, like zsh's `RPROMPT`.
+    pub right: Vec<Segment>,
+    /// Text between two segments.
+    pub separator: String,
+    /// Text the input is typed after.
+    pub symbol: String,
+    /// SGR parameters per segment name, replacing the theme's color, e.g. `git = "1;35"`.
+    pub colors: HashMap<String, String>,
+    /// Seconds a command must take before its duration is shown.
+    pub min_duration: f64,
+    /// Set the terminal title to `user@host: cwd`, or to the running command.
+    pub title: bool,
+    /// Replace the full prompt of a submitted line with `$ ` to keep scrollback compact.
+    pub transient: bool,
+    /// Run `git status` for the `git` segment, marking a branch with changes with `*`.
+    pub git_status: bool,
+    /// Seconds the `git` and `disk` segments are shown from cache before being gathered again in the background.
+    pub cache_seconds: f64,
+}
+
+impl Default for PromptConfig {
+    fn default() -> Self {
+        Self {
+            left: vec![Segment::Status, Segment::Duration],
+            right: Vec::new(),
+            separator: " ".to_string(),
+            symbol: "[sh]$ ".to_string(),
+            colors: HashMap::new(),
+            min_duration: 2.0,
+            title: true,
+            transient: false,
+            git_status: true,
+            cache_seconds: 5.0,
+        }
+    }
+}
+
+static PROMPT: RwLock<Option<Arc<PromptConfig>>> = RwLock::new(None);
+
+pub fn config_path() -> Option<PathBuf> {
+    config::data_dir().map(|dir| dir.join("prompt.toml"))
+}
+
+fn load() -> Result<PromptConfig, CommandError> {
+    let Some(path) = config_path().filter(|path| path.exists()) else {
+        return Ok(config::get().prompt.clone());
+    };
+
+    let contents = fs::read_to_string(&path).map_err(CommandError::from)?;
+    toml::from_str(&contents)
+        .map_err(|e| CommandError::CommandFailed(format!("Error parsing '{}': {}", path.display(), e)))
+}
+
+/// The active prompt configuration, loaded on first access.
+pub fn config() -> Arc<PromptConfig> {
+    if let Some(config) = PROMPT.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return Arc::clone(config);
+    }
+
+    let config = Arc::new(load().unwrap_or_else(|e| {
+        log::error!("{}", e);
+        PromptConfig::default()
+    }));
+    *PROMPT.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::clone(&config));
+    config
+}
+
+/// Re-reads the prompt configuration, keeping the current one if it is invalid.
+pub fn reload() -> Result<(), CommandError> {
+    let config = load()?;
+    *PROMPT.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(config));
+    Ok(())
+}
+
+/// `850ms`, `3.2s`, `1m 5s` or `2h 3m`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h {}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m {}s", secs / 60, secs % 60)
+    } else if secs >= 1 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
+
+fn cache_age(config: &PromptConfig) -> Duration {
+    Duration::from_secs_f64(config.cache_seconds.max(0.0))
+}
+
+fn render_segment(segment: Segment, config: &PromptConfig, last: Option<&LastCommand>) -> Option<String> {
+    let (role, text) = match segment {
+        Segment::Clock => (Role::Muted, deterministic::now().format("%H:%M:%S").to_string()),
+        Segment::Status => match last?.exit_code {
+            0 => (Role::Success, "✓".to_string()),
+            code => (Role::Failure, format!("✗ {}", code)),
+        },
+        Segment::Duration => {
+            let last = last.filter(|last| last.duration.as_secs_f64() >= config.min_duration)?;
+            (Role::Muted, format_duration(last.duration))
+        }
+        Segment::Git => {
+            let git = prompt_cache::git(&env::current_dir().ok()?, config.git_status, cache_age(config))?;
+            (Role::Heading, if git.dirty { format!("{}*", git.head) } else { git.head })
+        }
+        Segment::User => (Role::User, get_current_user()),
+        Segment::Cwd => (Role::Path, paths::display(&env::current_dir().ok()?)),
+        Segment::Disk => {
+            let free = prompt_cache::free_space(&env::current_dir().ok()?, cache_age(config))?;
+            (Role::Muted, format!("{} free", format_size(free, DECIMAL)))
+        }
+    };
+
+    Some(match config.colors.get(segment.name()) {
+        Some(code) if theme::colors_enabled() && !code.is_empty() => format!("\x1b[{}m{}\x1b[0m", code, text),
+        Some(_) => text,
+        None => theme::paint(role, &text),
+    })
+}
+
+fn render_segments(segments: &[Segment], config: &PromptConfig, last: Option<&LastCommand>) -> Vec<String> {
+    segments.iter()
+        .filter_map(|&segment| render_segment(segment, config, last))
+        .collect()
+}
+
+/// The prompt printed before reading a line.
+pub fn render(config: &PromptConfig, last: Option<&LastCommand>) -> String {
+    let mut segments = render_segments(&config.left, config, last);
+    segments.push(config.symbol.clone());
+    let left = segments.join(&config.separator);
+
+    let right = render_segments(&config.right, config, last).join(&config.separator);
+    if right.is_empty() || !io::stdout().is_terminal() {
+        return left;
+    }
+
+    // Leave at least one column between both sides, and drop the right side rather than wrap.
+    let width = columns::terminal_width();
+    let right_width = visible_width(&right);
+    if visible_width(&left) + right_width + 1 > width {
+        return left;
+    }
+
+    // Save the cursor, draw the right side at the edge, then restore the cursor for the left side.
+    format!("\x1b7\x1b[{}G{}\x1b8{}", width - right_width + 1, right, left)
+}
+
+/// Redraws the submitted `line` behind a minimal marker in place of the full `prompt` it was typed after.
+pub fn collapse(config: &PromptConfig, prompt: &str, line: &str) {
+    if !config.transient || !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return;
+    }
+
+    // Rows the prompt and the echoed line took up, counting wrapped lines.
+    let width = columns::terminal_width().max(1);
+    let left = prompt.rsplit("\x1b8").next().unwrap_or(prompt);
+    let rows = (visible_width(left) + visible_width(line)).max(1).div_ceil(width);
+
+    let mut stdout = io::stdout();
+    _ = write!(stdout, "\x1b[{}A\r\x1b[J{}{}\n", rows, theme::paint(Role::Muted, "$ "), line);
+    _ = stdout.flush();
+}
+
+fn set_title(config: &PromptConfig, title: &str) {
+    if config.title && io::stdout().is_terminal() {
+        _ = execute!(io::stdout(), SetTitle(title));
+    }
+}
+
+/// Title shown while waiting for input: `user@host: cwd`.
+pub fn set_idle_title(config: &PromptConfig) {
+    let host = deterministic::host(whoami::fallible::hostname().unwrap_or_else(|_| whoami::devicename()));
+    let cwd = env::current_dir()
+        .map(|dir| paths::display(&dir))
+        .unwrap_or_default();
+
+    set_title(config, &format!("{}@{}: {}", get_current_user(), host, cwd));
+}
+
+/// Title shown while `command` runs; the next prompt sets the idle title again.
+pub fn set_running_title(config: &PromptConfig, command: &str) {
+    set_title(config, command);
+}
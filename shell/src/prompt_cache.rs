@@ -0,0 +1,155 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{mpsc, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How long a prompt waits for data about a directory it has none for yet, before drawing without it.
+const FIRST_WAIT: Duration = Duration::from_millis(50);
+
+/// Prompt data about one directory, gathered on a background thread.
+struct Cached<V> {
+    cwd: PathBuf,
+    value: Option<V>,
+    fetched: Instant,
+    refreshing: bool,
+}
+
+/// The cached value for `cwd`. A stale or invalid one is still returned while a background thread
+/// replaces it, so drawing the prompt never waits on a slow repository or network drive.
+fn lookup<V: Clone + Send + 'static>(
+    slot: &'static Mutex<Option<Cached<V>>>,
+    cwd: &Path,
+    max_age: Duration,
+    is_valid: impl Fn(&V) -> bool,
+    fetch: impl FnOnce(&Path) -> Option<V> + Send + 'static,
+) -> Option<V> {
+    let (done, wait) = mpsc::channel();
+    let first = {
+        let mut cached = slot.lock().unwrap_or_else(|e| e.into_inner());
+        match cached.as_mut() {
+            Some(entry) if entry.cwd == cwd => {
+                let stale = entry.fetched.elapsed() > max_age || entry.value.as_ref().is_some_and(|value| !is_valid(value));
+                if !stale || entry.refreshing {
+                    return entry.value.clone();
+                }
+                entry.refreshing = true;
+                false
+            }
+            _ => {
+                *cached = Some(Cached { cwd: cwd.to_path_buf(), value: None, fetched: Instant::now(), refreshing: true });
+                true
+            }
+        }
+    };
+
+    let cwd = cwd.to_path_buf();
+    thread::spawn(move || {
+        let value = fetch(&cwd);
+        let mut cached = slot.lock().unwrap_or_else(|e| e.into_inner());
+        // The directory may have changed while fetching; the newer lookup then owns the slot.
+        if let Some(entry) = cached.as_mut().filter(|entry| entry.cwd == cwd) {
+            *entry = Cached { cwd, value, fetched: Instant::now(), refreshing: false };
+        }
+        _ = done.send(());
+    });
+
+    // Most repositories answer quickly, so the first prompt in a directory usually has its data after all.
+    if first {
+        _ = wait.recv_timeout(FIRST_WAIT);
+    }
+    slot.lock().unwrap_or_else(|e| e.into_inner()).as_ref().and_then(|entry| entry.value.clone())
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// What the `git` segment shows, and what it was read from.
+#[derive(Clone)]
+pub struct GitStatus {
+    git_dir: PathBuf,
+    head_modified: Option<SystemTime>,
+    index_modified: Option<SystemTime>,
+    /// Branch checked out, or the short commit id when detached.
+    pub head: String,
+    /// Whether `git status` reported changes; `false` when it wasn't run.
+    pub dirty: bool,
+}
+
+impl GitStatus {
+    /// Checking out or committing rewrites `HEAD` or the index, so the status is read again right away.
+    fn is_current(&self) -> bool {
+        modified(&self.git_dir.join("HEAD")) == self.head_modified
+            && modified(&self.git_dir.join("index")) == self.index_modified
+    }
+
+    fn read(cwd: &Path, run_status: bool) -> Option<Self> {
+        let git_dir = cwd.ancestors()
+            .map(|dir| dir.join(".git"))
+            .find(|dir| dir.is_dir())?;
+
+        let head_modified = modified(&git_dir.join("HEAD"));
+        let index_modified = modified(&git_dir.join("index"));
+        let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+        let head = head.trim();
+        let head = match head.strip_prefix("ref: ") {
+            Some(reference) => Path::new(reference).strip_prefix("refs/heads").ok()?.display().to_string(),
+            None => head.chars().take(7).collect(),
+        };
+
+        // `--no-optional-locks` keeps the background status from racing the user's own git commands.
+        let dirty = run_status && Command::new("git")
+            .args(["--no-optional-locks", "status", "--porcelain"])
+            .current_dir(cwd)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .is_ok_and(|output| output.status.success() && !output.stdout.is_empty());
+
+        Some(Self { git_dir, head_modified, index_modified, head, dirty })
+    }
+}
+
+static GIT: Mutex<Option<Cached<GitStatus>>> = Mutex::new(None);
+static DISK: Mutex<Option<Cached<u64>>> = Mutex::new(None);
+
+/// Git status of the repository containing `cwd`, if it is in one.
+pub fn git(cwd: &Path, run_status: bool, max_age: Duration) -> Option<GitStatus> {
+    lookup(&GIT, cwd, max_age, GitStatus::is_current, move |cwd| GitStatus::read(cwd, run_status))
+}
+
+/// Bytes available to the user on the volume holding `cwd`.
+pub fn free_space(cwd: &Path, max_age: Duration) -> Option<u64> {
+    lookup(&DISK, cwd, max_age, |_| true, imp::free_space)
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt, path::Path};
+
+    pub fn free_space(path: &Path) -> Option<u64> {
+        let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+            return None;
+        }
+        Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::path::Path;
+
+    use windows::{core::HSTRING, Win32::Storage::FileSystem::GetDiskFreeSpaceExW};
+
+    pub fn free_space(path: &Path) -> Option<u64> {
+        let mut available = 0u64;
+        unsafe { GetDiskFreeSpaceExW(&HSTRING::from(path), Some(&mut available), None, None) }.ok()?;
+        Some(available)
+    }
+}
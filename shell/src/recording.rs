@@ -0,0 +1,273 @@
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use command_core::{output, parse_line, CancellationToken, CommandError, CommandRegistry, LastCommand};
+use command_macro::command;
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::call_executable;
+
+/// The slowest `replay --speed`; slower factors stretch a recording past any useful length.
+const MIN_SPEED: f64 = 0.01;
+
+/// One line of a recording, with the milliseconds since recording started.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event {
+    Command { at_ms: u64, line: String },
+    Output { at_ms: u64, text: String },
+    Finished { at_ms: u64, exit_code: i32, duration_ms: u64 },
+}
+
+impl Event {
+    fn at_ms(&self) -> u64 {
+        match self {
+            Event::Command { at_ms, .. } | Event::Output { at_ms, .. } | Event::Finished { at_ms, .. } => *at_ms,
+        }
+    }
+}
+
+struct Recorder {
+    path: PathBuf,
+    file: BufWriter<File>,
+    started: Instant,
+    /// The line being run, written once it produces output or finishes, so the `record` line
+    /// stopping a recording is left out of it.
+    pending: Option<(u64, String)>,
+    /// Whether a line noted while recording is running, i.e. the next `Finished` belongs to it.
+    running: bool,
+}
+
+impl Recorder {
+    fn elapsed_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    fn write(&mut self, event: &Event) -> std::io::Result<()> {
+        if let Some((at_ms, line)) = self.pending.take() {
+            self.write_event(&Event::Command { at_ms, line })?;
+        }
+        self.write_event(event)
+    }
+
+    fn write_event(&mut self, event: &Event) -> std::io::Result<()> {
+        serde_json::to_writer(&mut self.file, event)?;
+        self.file.write_all(b"\n")?;
+        self.file.flush()
+    }
+}
+
+static RECORDER: Mutex<Option<Recorder>> = Mutex::new(None);
+
+/// Applies `f` to the recorder, if a session is being recorded. A recording that can't be written
+/// anymore is stopped; nothing is logged, since that would be recorded as well.
+fn with_recorder(f: impl FnOnce(&mut Recorder) -> std::io::Result<()>) {
+    let mut recorder = RECORDER.lock().unwrap_or_else(|e| e.into_inner());
+    let failed = recorder.as_mut().is_some_and(|active| f(active).is_err());
+    if failed {
+        *recorder = None;
+        drop(recorder);
+        output::set_tee("record", None);
+    }
+}
+
+pub fn is_active() -> bool {
+    RECORDER.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+fn create(path: &Path) -> Result<Recorder, CommandError> {
+    if let Some(active) = RECORDER.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return Err(CommandError::CommandFailed(format!("Already recording to '{}'", active.path.display())));
+    }
+
+    let file = File::create(path)
+        .map_err(|e| CommandError::CommandFailed(format!("Could not create '{}': {}", path.display(), e)))?;
+    Ok(Recorder {
+        path: path.to_path_buf(),
+        file: BufWriter::new(file),
+        started: Instant::now(),
+        pending: None,
+        running: false,
+    })
+}
+
+fn start(recorder: Recorder) {
+    *RECORDER.lock().unwrap_or_else(|e| e.into_inner()) = Some(recorder);
+    output::set_tee("record", Some(record_output));
+}
+
+fn stop() -> Option<PathBuf> {
+    output::set_tee("record", None);
+    RECORDER.lock().unwrap_or_else(|e| e.into_inner()).take().map(|recorder| recorder.path)
+}
+
+/// Notes the line about to run.
+pub fn command_started(line: &str) {
+    if line.trim().is_empty() {
+        return;
+    }
+    with_recorder(|recorder| {
+        recorder.pending = Some((recorder.elapsed_ms(), line.to_string()));
+        recorder.running = true;
+        Ok(())
+    });
+}
+
+pub fn command_finished(finished: &LastCommand) {
+    with_recorder(|recorder| {
+        // The `record` line starting the recording ran before there was anything to note.
+        if !std::mem::take(&mut recorder.running) {
+            return Ok(());
+        }
+        let event = Event::Finished {
+            at_ms: recorder.elapsed_ms(),
+            exit_code: finished.exit_code,
+            duration_ms: finished.duration.as_millis() as u64,
+        };
+        recorder.write(&event)
+    });
+}
+
+/// Records what was printed; installed as the output tee while recording. External programs write
+/// to the terminal directly, so their output isn't part of a recording.
+pub fn record_output(data: &[u8]) {
+    with_recorder(|recorder| {
+        let event = Event::Output {
+            at_ms: recorder.elapsed_ms(),
+            text: String::from_utf8_lossy(data).into_owned(),
+        };
+        recorder.write(&event)
+    });
+}
+
+#[command(
+    name = "record",
+    description = "Record command lines, the output of builtins and timing to a file, or stop recording; external programs' output isn't recorded",
+    usage = "record [file]",
+    examples = ["record demo.rec", "record"]
+)]
+pub fn cmd_record(path: Option<PathBuf>) -> Result<(), CommandError> {
+    match path {
+        Some(path) => {
+            let recorder = create(&path)?;
+            // Logged before starting, so the recording doesn't open with it.
+            info!("Recording to '{}', run 'record' again to stop", path.display());
+            start(recorder);
+            Ok(())
+        }
+        None => match stop() {
+            Some(path) => {
+                info!("Recording saved to '{}'", path.display());
+                Ok(())
+            }
+            None => Err(CommandError::CommandFailed("Not recording".to_string())),
+        },
+    }
+}
+
+fn read_events(path: &Path) -> Result<Vec<Event>, CommandError> {
+    let file = File::open(path)
+        .map_err(|e| CommandError::CommandFailed(format!("Could not open '{}': {}", path.display(), e)))?;
+    BufReader::new(file).lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|(number, line)| {
+            let line = line.map_err(|e| CommandError::FileReadError(path.to_path_buf(), e))?;
+            serde_json::from_str(&line).map_err(|e| CommandError::CommandFailed(format!(
+                "'{}' line {}: {}",
+                path.display(),
+                number + 1,
+                e
+            )))
+        })
+        .collect()
+}
+
+/// Waits until `at` has passed, checking for Ctrl+C in between.
+fn wait_until(at: Instant, token: &CancellationToken) -> Result<(), CommandError> {
+    loop {
+        token.check()?;
+        let remaining = at.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(());
+        }
+        std::thread::sleep(remaining.min(Duration::from_millis(50)));
+    }
+}
+
+#[command(
+    name = "replay",
+    description = "Show a recorded session again with its original timing, or run its commands again",
+    usage = "replay [--run] [--speed <factor>] <file>",
+    examples = ["replay demo.rec", "replay --speed 2 demo.rec", "replay --run demo.rec"]
+)]
+pub fn cmd_replay(args: Vec<&OsStr>) -> Result<(), CommandError> {
+    let mut run = false;
+    let mut speed = 1.0;
+    let mut path = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--run") => run = true,
+            Some("--speed") => {
+                speed = args.next()
+                    .and_then(OsStr::to_str)
+                    .and_then(|value| value.parse::<f64>().ok())
+                    .filter(|speed| speed.is_finite() && *speed >= MIN_SPEED)
+                    .ok_or_else(|| CommandError::InvalidArguments(format!("--speed needs a number of at least {}", MIN_SPEED)))?;
+            }
+            _ if path.is_none() => path = Some(PathBuf::from(arg)),
+            _ => return Err(CommandError::InvalidArguments("Usage: replay [--run] [--speed <factor>] <file>".to_string())),
+        }
+    }
+    let Some(path) = path else {
+        return Err(CommandError::InvalidArguments("Usage: replay [--run] [--speed <factor>] <file>".to_string()));
+    };
+
+    let events = read_events(&path)?;
+    let token = CancellationToken::current();
+
+    if run {
+        for event in events {
+            let Event::Command { line, .. } = event else {
+                continue;
+            };
+            token.check()?;
+            output::print(&format!("$ {}\n", line))?;
+            let Some(parsed) = parse_line(&line) else {
+                continue;
+            };
+            let args: Vec<&OsStr> = parsed.args.iter().map(OsStr::new).collect();
+            if let Err(e) = CommandRegistry::execute_or_else(&parsed.name, &args, call_executable) {
+                log::error!("{}", e);
+            }
+        }
+        return Ok(());
+    }
+
+    let started = Instant::now();
+    for event in events {
+        let at = Duration::try_from_secs_f64(event.at_ms() as f64 / 1000.0 / speed).ok()
+            .and_then(|offset| started.checked_add(offset))
+            .ok_or_else(|| CommandError::CommandFailed(format!(
+                "'{}' has an event too far into the recording: {} ms",
+                path.display(),
+                event.at_ms()
+            )))?;
+        wait_until(at, &token)?;
+        match event {
+            Event::Command { line, .. } => output::print(&format!("$ {}\n", line))?,
+            Event::Output { text, .. } => output::print(&text)?,
+            Event::Finished { .. } => {}
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    path::PathBuf,
+    sync::Mutex,
+    thread,
+    time::Instant,
+};
+
+use command_core::{output, CommandError, Shell};
+use interprocess::local_socket::{prelude::*, ListenerOptions, Stream};
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::call_executable;
+
+/// Set for programs started from the shell, so e.g. an editor's terminal can find its socket.
+pub const ENV_VAR: &str = "SHELL_REMOTE";
+
+/// `[remote]` section of the configuration file.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct RemoteConfig {
+    /// Accept command lines from other programs, as `--remote` does.
+    pub enabled: bool,
+    /// Socket path on Unix; by default one per shell in `XDG_RUNTIME_DIR` or the temporary directory.
+    /// Windows always uses the named pipe `shell-<pid>`.
+    pub path: Option<PathBuf>,
+}
+
+/// One request: a command line, terminated by a newline.
+#[derive(Deserialize)]
+struct Request {
+    line: String,
+}
+
+/// The reply to a [`Request`], also one JSON object per line.
+#[derive(Serialize)]
+struct Response {
+    exit_code: i32,
+    duration_ms: f64,
+    stdout: String,
+    stderr: String,
+    error: Option<String>,
+}
+
+impl Response {
+    fn invalid(message: String) -> Self {
+        Response {
+            exit_code: 2,
+            duration_ms: 0.0,
+            stdout: String::new(),
+            stderr: String::new(),
+            error: Some(message),
+        }
+    }
+}
+
+/// Remote lines run one at a time, as they share the working directory and settings.
+static RUNNING: Mutex<()> = Mutex::new(());
+
+fn execute(shell: &mut Shell, request: &str) -> Response {
+    let request: Request = match serde_json::from_str(request) {
+        Ok(request) => request,
+        Err(e) => return Response::invalid(format!("Invalid request, expected {{\"line\": \"...\"}}: {}", e)),
+    };
+
+    let _running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+    let started = Instant::now();
+    let (result, captured) = output::capture(|| shell.eval(&request.line));
+    Response {
+        exit_code: result.as_ref().map_or_else(CommandError::exit_code, |_| 0),
+        duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+        stdout: captured.stdout_text().into_owned(),
+        stderr: captured.stderr_text().into_owned(),
+        error: result.err().map(|e| e.to_string()),
+    }
+}
+
+/// Answers the requests of one client until it disconnects.
+fn serve(stream: Stream) -> io::Result<()> {
+    let mut shell = Shell::builder().external(call_executable).build();
+    let mut stream = BufReader::new(stream);
+    let mut request = String::new();
+    loop {
+        request.clear();
+        if stream.read_line(&mut request)? == 0 {
+            return Ok(());
+        }
+        if request.trim().is_empty() {
+            continue;
+        }
+
+        let response = execute(&mut shell, request.trim_end());
+        let mut reply = serde_json::to_vec(&response).map_err(io::Error::other)?;
+        reply.push(b'\n');
+        stream.get_mut().write_all(&reply)?;
+    }
+}
+
+#[cfg(unix)]
+fn listen(config: &RemoteConfig) -> io::Result<(interprocess::local_socket::Listener, String)> {
+    use std::{fs, os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt}};
+
+    use interprocess::local_socket::GenericFilePath;
+
+    let path = config.path.clone().unwrap_or_else(|| {
+        std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir)
+            .join(format!("shell-{}.sock", std::process::id()))
+    });
+    match fs::symlink_metadata(&path) {
+        // Left behind by a shell that exited; the process id makes the default name unique otherwise.
+        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(&path)?,
+        Ok(_) => return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("'{}' exists and is not a socket", path.display()),
+        )),
+        Err(_) => {}
+    }
+
+    // Whoever can connect can run commands as this user, so the socket is created in a directory
+    // only this user can enter and made private before it moves to where clients look for it.
+    let private = path.with_file_name(format!(".shell-{}.remote", std::process::id()));
+    fs::DirBuilder::new().mode(0o700).create(&private)?;
+    let bound = private.join("socket");
+    let listener = ListenerOptions::new()
+        .name(bound.as_path().to_fs_name::<GenericFilePath>()?)
+        .create_sync()
+        .and_then(|listener| {
+            fs::set_permissions(&bound, fs::Permissions::from_mode(0o600))?;
+            fs::rename(&bound, &path)?;
+            Ok(listener)
+        });
+    _ = fs::remove_file(&bound);
+    _ = fs::remove_dir(&private);
+    Ok((listener?, path.display().to_string()))
+}
+
+#[cfg(windows)]
+fn listen(_config: &RemoteConfig) -> io::Result<(interprocess::local_socket::Listener, String)> {
+    use interprocess::local_socket::GenericNamespaced;
+
+    let name = format!("shell-{}", std::process::id());
+    let listener = ListenerOptions::new().name(name.as_str().to_ns_name::<GenericNamespaced>()?).create_sync()?;
+    Ok((listener, format!(r"\\.\pipe\{}", name)))
+}
+
+/// Starts accepting clients in the background. Each sends `{"line": "..."}` per line and gets a
+/// [`Response`] back for each. Builtins' output is returned; external programs write to the terminal.
+pub fn start(config: &RemoteConfig) {
+    let (listener, name) = match listen(config) {
+        Ok(listening) => listening,
+        Err(e) => {
+            error!("Could not start the remote control socket: {}", e);
+            return;
+        }
+    };
+    info!("Accepting commands on '{}'", name);
+    std::env::set_var(ENV_VAR, &name);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => _ = thread::spawn(move || {
+                    if let Err(e) = serve(stream) {
+                        debug!("remote client disconnected: {}", e);
+                    }
+                }),
+                Err(e) => debug!("accepting a remote client: {}", e),
+            }
+        }
+    });
+}
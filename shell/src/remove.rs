@@ -0,0 +1,116 @@
+use std::{fs, io, path::{Path, PathBuf}, thread, time::Duration};
+
+use command_core::{vfs::{self, Fs}, CancellationToken};
+use rayon::prelude::*;
+
+use crate::progress::Progress;
+
+/// Attempts at removing an entry another process still has open, waiting twice as long after each.
+const ATTEMPTS: u32 = 8;
+const FIRST_DELAY: Duration = Duration::from_millis(5);
+
+/// Whether removing failed only because the entry is briefly in use, as on Windows while a virus
+/// scanner or indexer holds a file, or while a removed file is still pending deletion in its directory.
+fn is_transient(e: &io::Error) -> bool {
+    #[cfg(windows)]
+    {
+        const ERROR_ACCESS_DENIED: i32 = 5;
+        const ERROR_SHARING_VIOLATION: i32 = 32;
+        const ERROR_DIR_NOT_EMPTY: i32 = 145;
+        matches!(e.raw_os_error(), Some(ERROR_ACCESS_DENIED | ERROR_SHARING_VIOLATION | ERROR_DIR_NOT_EMPTY))
+    }
+    #[cfg(not(windows))]
+    {
+        _ = e;
+        false
+    }
+}
+
+/// Runs `remove` until it succeeds or fails for a reason waiting won't fix.
+fn with_retry(path: &Path, remove: impl Fn(&Path) -> io::Result<()>) -> io::Result<()> {
+    let mut delay = FIRST_DELAY;
+    for attempt in 1.. {
+        match remove(path) {
+            Err(e) if is_transient(&e) && attempt < ATTEMPTS => {
+                // Windows refuses to delete read-only files, which is what `node_modules` often holds.
+                if cfg!(windows) && e.kind() == io::ErrorKind::PermissionDenied {
+                    clear_readonly(path);
+                }
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+    unreachable!()
+}
+
+#[allow(clippy::permissions_set_readonly_false)]
+fn clear_readonly(path: &Path) {
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        let mut permissions = metadata.permissions();
+        if permissions.readonly() {
+            permissions.set_readonly(false);
+            _ = fs::set_permissions(path, permissions);
+        }
+    }
+}
+
+fn interrupted() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "interrupted")
+}
+
+/// Empties `dir` bottom-up: everything in it is removed before it, subdirectories in parallel when `parallel` is set.
+fn remove_contents(
+    filesystem: &dyn Fs,
+    dir: &Path,
+    parallel: bool,
+    token: &CancellationToken,
+    progress: &Progress,
+) -> io::Result<()> {
+    let mut subdirs = Vec::new();
+    let mut others = Vec::new();
+    for entry in filesystem.read_dir(dir)? {
+        // Only real directories are descended into; links to them are removed as links.
+        if filesystem.metadata(&entry)?.is_dir() {
+            subdirs.push(entry);
+        } else {
+            others.push(entry);
+        }
+    }
+
+    let remove_other = |path: &PathBuf| -> io::Result<()> {
+        if token.is_cancelled() {
+            return Err(interrupted());
+        }
+        with_retry(path, |path| filesystem.remove(path))?;
+        progress.inc(1);
+        Ok(())
+    };
+    let remove_subdir = |path: &PathBuf| -> io::Result<()> {
+        remove_contents(filesystem, path, parallel, token, progress)?;
+        with_retry(path, |path| filesystem.remove(path))?;
+        progress.inc(1);
+        Ok(())
+    };
+
+    if parallel {
+        others.par_iter().try_for_each(remove_other)?;
+        subdirs.par_iter().try_for_each(remove_subdir)
+    } else {
+        others.iter().try_for_each(remove_other)?;
+        subdirs.iter().try_for_each(remove_subdir)
+    }
+}
+
+/// `remove_dir_all` through [`vfs::current`] with a progress bar, stopping early when `token` is
+/// cancelled. With `parallel`, directories are read and emptied on several threads, which is much
+/// faster for huge trees.
+pub fn remove_tree(dir: &Path, parallel: bool, token: &CancellationToken) -> io::Result<()> {
+    let filesystem = vfs::current();
+    let progress = Progress::count(format!("Removing {}", dir.display()));
+    remove_contents(&*filesystem, dir, parallel, token, &progress)?;
+    with_retry(dir, |path| filesystem.remove(path))?;
+    progress.inc(1);
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use std::{env, io::{self, Read, Write}, path::{Path, PathBuf}, sync::{Arc, OnceLock}};
+
+use command_core::{vfs::{Fs, FsMetadata}, CommandError, CommandMiddleware, Invocation, Next};
+
+use crate::paths;
+
+/// The directory builtins are confined to, canonicalized, when the shell runs restricted.
+static ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Confines the shell to `root` and moves into it.
+pub fn init(root: &Path) -> Result<(), CommandError> {
+    let root = root.canonicalize()
+        .map_err(|e| CommandError::CommandFailed(format!("Cannot restrict to '{}': {}", root.display(), e)))?;
+    env::set_current_dir(&root)?;
+    _ = ROOT.set(root);
+    Ok(())
+}
+
+pub fn root() -> Option<&'static Path> {
+    ROOT.get().map(PathBuf::as_path)
+}
+
+/// Where `path` really points: links are resolved as far as the path exists, and the rest is
+/// normalized on top of that.
+fn resolve(path: &Path) -> Result<PathBuf, CommandError> {
+    let absolute = env::current_dir()?.join(path);
+
+    let mut existing = absolute.as_path();
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(resolved) = existing.canonicalize() {
+            let full = rest.iter().rev().fold(resolved, |full, name| full.join(name));
+            return Ok(paths::normalize(&full));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                existing = parent;
+            }
+            _ => return Ok(paths::normalize(&absolute)),
+        }
+    }
+}
+
+/// Fails when the shell is restricted and `path` leads outside the root, through `..` or a link.
+pub fn check(path: &Path) -> Result<(), CommandError> {
+    let Some(root) = root() else {
+        return Ok(());
+    };
+
+    if resolve(path)?.starts_with(root) {
+        Ok(())
+    } else {
+        Err(CommandError::CommandFailed(format!(
+            "'{}' is outside the restricted directory '{}'",
+            path.display(),
+            paths::display(root)
+        )))
+    }
+}
+
+/// Rejects external programs and any builtin argument that would reach outside the root.
+pub struct RestrictMiddleware;
+
+impl CommandMiddleware for RestrictMiddleware {
+    fn handle(&self, invocation: &Invocation, next: Next) -> Result<(), CommandError> {
+        if root().is_none() {
+            return next.run(invocation);
+        }
+        if !invocation.is_builtin() {
+            return Err(CommandError::CommandFailed(format!(
+                "'{}': external programs cannot be run in restricted mode",
+                invocation.name
+            )));
+        }
+
+        // Arguments are not known to be paths, but anything else resolves inside the current directory.
+        for arg in invocation.args.iter().filter(|arg| !arg.to_string_lossy().starts_with('-')) {
+            check(Path::new(arg))?;
+        }
+        next.run(invocation)
+    }
+}
+
+/// Checks every path a file command touches against the root, including those it found itself
+/// while recursing, which the middleware can't see.
+pub struct RestrictedFs {
+    inner: Arc<dyn Fs>,
+}
+
+impl RestrictedFs {
+    pub fn new(inner: Arc<dyn Fs>) -> Self {
+        Self { inner }
+    }
+
+    fn check(&self, path: &Path) -> io::Result<()> {
+        check(path).map_err(|e| io::Error::new(io::ErrorKind::PermissionDenied, e.to_string()))
+    }
+}
+
+impl Fs for RestrictedFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        self.check(path)?;
+        self.inner.read_dir(path)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FsMetadata> {
+        self.check(path)?;
+        self.inner.metadata(path)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        self.check(path)?;
+        self.inner.remove(path)
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        self.check(path)?;
+        self.inner.create_dir(path)
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + Send>> {
+        self.check(path)?;
+        self.inner.create(path)
+    }
+
+    fn append(&self, path: &Path) -> io::Result<Box<dyn Write + Send>> {
+        self.check(path)?;
+        self.inner.append(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.check(from)?;
+        self.check(to)?;
+        self.inner.rename(from, to)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        self.check(path)?;
+        self.inner.open(path)
+    }
+}
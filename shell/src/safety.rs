@@ -0,0 +1,159 @@
+use std::{
+    collections::HashMap,
+    env,
+    ffi::OsStr,
+    fs,
+    io::{self, IsTerminal, Write},
+    path::Path,
+};
+
+use command_core::{CancellationToken, CommandError, CommandMiddleware, Invocation, Next};
+use serde::Deserialize;
+
+use crate::{audit::{self, Action}, config, deterministic, dry_run, glob, i18n, paths, tr};
+
+/// How readily destructive builtins ask before they run.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Policy {
+    /// Never ask.
+    Off,
+    /// Ask before touching a root or the home directory, a large tree, or many glob matches.
+    #[default]
+    Normal,
+    /// Ask before removing or overwriting anything.
+    Paranoid,
+}
+
+/// `[safety]` section of the configuration file.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SafetyConfig {
+    pub policy: Policy,
+    /// Policies for single commands, e.g. `{ cp = "off", rm = "paranoid" }`.
+    pub commands: HashMap<String, Policy>,
+    /// Files a directory may hold before removing it asks first.
+    pub large_tree: u64,
+    /// Paths a glob may match before acting on them asks first.
+    pub glob_matches: usize,
+}
+
+impl Default for SafetyConfig {
+    fn default() -> Self {
+        Self {
+            policy: Policy::Normal,
+            commands: HashMap::new(),
+            large_tree: 1000,
+            glob_matches: 100,
+        }
+    }
+}
+
+impl SafetyConfig {
+    pub fn policy_for(&self, command: &str) -> Policy {
+        self.commands.get(command).copied().unwrap_or(self.policy)
+    }
+}
+
+/// Whether `path` is `/`, a drive root such as `C:\`, or the home directory itself.
+fn is_root(path: &Path) -> bool {
+    let Ok(cwd) = env::current_dir() else {
+        return false;
+    };
+    let absolute = paths::normalize(&cwd.join(path));
+    absolute.parent().is_none() || config::home_dir().is_some_and(|home| paths::normalize(&home) == absolute)
+}
+
+/// Whether `dir` holds more than `limit` files, counting no further than that. Links aren't followed.
+fn holds_more_than(dir: &Path, limit: u64) -> bool {
+    let mut files = 0;
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+            if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                pending.push(entry.path());
+                continue;
+            }
+            files += 1;
+            if files > limit {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Why running `command` with `args` needs confirming under `policy`, if it does.
+fn reason(command: &str, args: &[&OsStr], policy: Policy, config: &SafetyConfig) -> Option<String> {
+    if policy == Policy::Off {
+        return None;
+    }
+
+    let candidates = audit::candidates(command, args);
+    for (action, path) in &candidates {
+        if is_root(path) {
+            return Some(tr!("safety.root", path = paths::display(path)));
+        }
+        let is_dir = fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir());
+        if *action == Action::Removed && is_dir && holds_more_than(path, config.large_tree) {
+            return Some(tr!("safety.large_tree", path = paths::display(path), count = config.large_tree));
+        }
+    }
+
+    if command == "rm" || command == "rmdir" {
+        let patterns = args.iter().filter(|arg| !arg.to_string_lossy().starts_with('-') && glob::is_pattern(arg));
+        for pattern in patterns {
+            let count = audit::expand(pattern).len();
+            if count > config.glob_matches {
+                return Some(tr!("safety.glob", pattern = pattern.to_string_lossy(), count = count));
+            }
+        }
+    }
+
+    if policy != Policy::Paranoid {
+        return None;
+    }
+    candidates.iter()
+        // Appending keeps what was there.
+        .find(|(action, path)| *action != Action::Appended && fs::symlink_metadata(path).is_ok())
+        .map(|(_, path)| tr!("safety.paranoid", command = command, path = paths::display(path)))
+}
+
+/// Asks `question` on the terminal; without one there is nobody to ask, so the answer is no. So it
+/// is for commands running in the background, e.g. scheduled or sent remotely, which would otherwise
+/// prompt in the middle of the session and take the next line typed as the answer, and for
+/// deterministic runs, which are scripted.
+pub fn confirm(question: &str) -> Result<bool, CommandError> {
+    if !CancellationToken::is_foreground() || deterministic::is_enabled() || !io::stdin().is_terminal() {
+        return Ok(false);
+    }
+
+    print!("{}", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(i18n::is_yes(&answer))
+}
+
+/// Asks before `rm`, `rmdir` and `cp` do something the configured [`Policy`] considers dangerous.
+pub struct SafetyMiddleware;
+
+impl CommandMiddleware for SafetyMiddleware {
+    fn handle(&self, invocation: &Invocation, next: Next) -> Result<(), CommandError> {
+        let Some(info) = invocation.info else {
+            return next.run(invocation);
+        };
+        // Nothing is changed in dry-run mode, so there is nothing to confirm.
+        if dry_run::is_enabled() || invocation.args.iter().any(|arg| *arg == "--dry-run") {
+            return next.run(invocation);
+        }
+
+        let config = &config::get().safety;
+        if let Some(reason) = reason(info.name, invocation.args, config.policy_for(info.name), config) {
+            if !confirm(&tr!("confirm.dangerous", reason = &reason))? {
+                return Err(CommandError::CommandFailed(format!("{}; not confirmed, nothing was changed", reason)));
+            }
+        }
+        next.run(invocation)
+    }
+}
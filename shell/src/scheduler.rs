@@ -0,0 +1,346 @@
+use std::{
+    collections::VecDeque,
+    ffi::OsStr,
+    fmt,
+    process::Stdio,
+    sync::{atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering}, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use chrono::{DateTime, Days, Local, NaiveTime};
+use command_core::{output, parse_line, CommandError, CommandOutput, CommandRegistry, Shell, Table};
+use command_macro::command;
+use log::debug;
+use serde::Serialize;
+
+use crate::{executable_command, exit_result, spawn_error, theme::{self, Role}};
+
+/// Runs kept in the output buffer; older ones are dropped.
+const MAX_RUNS: usize = 100;
+
+/// Builtins jobs can't run: the working directory belongs to the whole process, so a job changing
+/// it would move the interactive shell as well.
+const DIRECTORY_COMMANDS: [&str; 3] = ["cd", "pushd", "popd"];
+
+#[derive(Clone, Copy)]
+enum When {
+    Every(Duration),
+    At(NaiveTime),
+}
+
+impl fmt::Display for When {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            When::Every(interval) => write!(f, "every {}", format_interval(*interval)),
+            When::At(time) => write!(f, "at {}", time.format("%H:%M:%S")),
+        }
+    }
+}
+
+struct Job {
+    id: u32,
+    line: String,
+    when: When,
+    /// Next run as a Unix timestamp in milliseconds.
+    next: AtomicU64,
+    runs: AtomicU32,
+    cancelled: AtomicBool,
+}
+
+/// One run of a job, with everything it wrote.
+#[derive(Clone, Serialize)]
+struct Run {
+    job: u32,
+    line: String,
+    at: String,
+    exit_code: i32,
+    output: String,
+}
+
+static JOBS: Mutex<Vec<Arc<Job>>> = Mutex::new(Vec::new());
+static RUNS: Mutex<VecDeque<Run>> = Mutex::new(VecDeque::new());
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+
+/// `90s`, `5m`, `1h30m` or `2d`; a bare number is seconds.
+fn parse_interval(text: &str) -> Result<Duration, CommandError> {
+    let invalid = || CommandError::InvalidArguments(format!("'{}' is not an interval, e.g. 30s, 5m or 1h30m", text));
+    let mut seconds = 0u64;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        let value: u64 = number.parse().map_err(|_| invalid())?;
+        seconds = value.checked_mul(unit).and_then(|value| seconds.checked_add(value)).ok_or_else(invalid)?;
+        number.clear();
+    }
+    if !number.is_empty() {
+        seconds += number.parse::<u64>().map_err(|_| invalid())?;
+    }
+    if seconds == 0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+fn format_interval(interval: Duration) -> String {
+    let mut seconds = interval.as_secs();
+    let mut text = String::new();
+    for (unit, size) in [("d", 86_400), ("h", 3_600), ("m", 60), ("s", 1)] {
+        if seconds >= size {
+            text.push_str(&format!("{}{}", seconds / size, unit));
+            seconds %= size;
+        }
+    }
+    text
+}
+
+/// The next time the clock shows `time`, today or tomorrow.
+fn next_at(time: NaiveTime) -> DateTime<Local> {
+    let now = Local::now();
+    let mut date = now.date_naive();
+    loop {
+        // A time skipped by a daylight saving change comes round again the next day.
+        if let Some(at) = date.and_time(time).and_local_timezone(Local).earliest().filter(|at| *at > now) {
+            return at;
+        }
+        date = date + Days::new(1);
+    }
+}
+
+fn now_millis() -> u64 {
+    Local::now().timestamp_millis().max(0) as u64
+}
+
+/// Runs an external program with its output collected instead of shown, as jobs have no terminal.
+fn call_captured(name: &str, args: &[&OsStr]) -> Result<(), CommandError> {
+    let ran = executable_command(name, args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| spawn_error(name, e))?;
+    output::print(&String::from_utf8_lossy(&ran.stdout))?;
+    output::print_error(&String::from_utf8_lossy(&ran.stderr))?;
+    exit_result(name, ran.status)
+}
+
+fn record(job: &Job, shell: &mut Shell) {
+    let at = Local::now().to_rfc3339();
+    let (result, captured) = output::capture(|| shell.eval(&job.line));
+    let mut text = captured.stdout_text().into_owned();
+    text.push_str(&captured.stderr_text());
+    if let Err(e) = &result {
+        text.push_str(&format!("{}\n", e));
+    }
+    debug!("scheduled job {} ran '{}'", job.id, job.line);
+
+    let mut runs = RUNS.lock().unwrap_or_else(|e| e.into_inner());
+    if runs.len() == MAX_RUNS {
+        runs.pop_front();
+    }
+    runs.push_back(Run {
+        job: job.id,
+        line: job.line.clone(),
+        at,
+        exit_code: result.as_ref().map_or_else(CommandError::exit_code, |_| 0),
+        output: text,
+    });
+}
+
+/// Sleeps until `job` is due, waking up regularly to notice it being cancelled. Returns whether it is still wanted.
+fn wait(job: &Job) -> bool {
+    loop {
+        if job.cancelled.load(Ordering::Relaxed) {
+            return false;
+        }
+        let remaining = job.next.load(Ordering::Relaxed).saturating_sub(now_millis());
+        if remaining == 0 {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(remaining).min(Duration::from_millis(500)));
+    }
+}
+
+fn schedule(line: String, when: When) -> Result<(), CommandError> {
+    let Some(parsed) = parse_line(&line) else {
+        return Err(CommandError::InvalidArguments("Missing the command to run".to_string()));
+    };
+    if let Some(info) = CommandRegistry::resolve(&parsed.name)?.filter(|info| DIRECTORY_COMMANDS.contains(&info.name)) {
+        return Err(CommandError::InvalidArguments(format!(
+            "Jobs can't run '{}', they share the shell's working directory",
+            info.name
+        )));
+    }
+    let first = match when {
+        When::Every(interval) => now_millis() + interval.as_millis() as u64,
+        When::At(time) => next_at(time).timestamp_millis().max(0) as u64,
+    };
+    let job = Arc::new(Job {
+        id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        line,
+        when,
+        next: AtomicU64::new(first),
+        runs: AtomicU32::new(0),
+        cancelled: AtomicBool::new(false),
+    });
+    JOBS.lock().unwrap_or_else(|e| e.into_inner()).push(Arc::clone(&job));
+    output::print(&format!("Scheduled job {}: '{}' {}\n", job.id, job.line, job.when))?;
+
+    // A job keeps to the limit of the shell that scheduled it.
+    let allowed = CommandRegistry::allowed();
+    thread::spawn(move || CommandRegistry::with_allowed(allowed, || {
+        // Limited to the other builtins as well, for lines that reach `cd` in another way.
+        let mut shell = Shell::builder()
+            .commands(CommandRegistry::all().map(|info| info.name).filter(|name| !DIRECTORY_COMMANDS.contains(name)))
+            .external(call_captured)
+            .build();
+        while wait(&job) {
+            record(&job, &mut shell);
+            job.runs.fetch_add(1, Ordering::Relaxed);
+            match job.when {
+                When::Every(interval) => {
+                    job.next.store(now_millis() + interval.as_millis() as u64, Ordering::Relaxed);
+                }
+                When::At(_) => break,
+            }
+        }
+        JOBS.lock().unwrap_or_else(|e| e.into_inner()).retain(|other| other.id != job.id);
+    }));
+    Ok(())
+}
+
+#[command(
+    name = "every",
+    description = "Run a command line in the background at an interval, see `schedule`",
+    usage = "every <interval> <command...>",
+    examples = ["every 5m git fetch", "every 30s ls -l build"]
+)]
+pub fn cmd_every(args: Vec<&str>) -> Result<(), CommandError> {
+    let [interval, command @ ..] = args.as_slice() else {
+        return Err(CommandError::InvalidArguments("Usage: every <interval> <command...>".to_string()));
+    };
+    schedule(command.join(" "), When::Every(parse_interval(interval)?))
+}
+
+#[command(
+    name = "at",
+    description = "Run a command line in the background the next time the clock shows a time, see `schedule`",
+    usage = "at <HH:MM[:SS]> <command...>",
+    examples = ["at 14:00 backup.sh", "at 9:30:00 git pull"]
+)]
+pub fn cmd_at(args: Vec<&str>) -> Result<(), CommandError> {
+    let [time, command @ ..] = args.as_slice() else {
+        return Err(CommandError::InvalidArguments("Usage: at <HH:MM[:SS]> <command...>".to_string()));
+    };
+    let time = NaiveTime::parse_from_str(time, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+        .map_err(|_| CommandError::InvalidArguments(format!("'{}' is not a time, e.g. 14:00", time)))?;
+    schedule(command.join(" "), When::At(time))
+}
+
+#[derive(Serialize)]
+struct JobEntry {
+    id: u32,
+    schedule: String,
+    next: String,
+    runs: u32,
+    line: String,
+}
+
+#[derive(Serialize)]
+struct Jobs(Vec<JobEntry>);
+
+impl CommandOutput for Jobs {
+    fn to_text(&self) -> String {
+        if self.0.is_empty() {
+            return "No jobs are scheduled\n".to_string();
+        }
+        let mut table = Table::new().headers(["id", "schedule", "next run", "runs", "command"]);
+        for job in &self.0 {
+            table.push_row([
+                job.id.to_string(),
+                job.schedule.clone(),
+                theme::paint(Role::Muted, &job.next),
+                job.runs.to_string(),
+                job.line.clone(),
+            ]);
+        }
+        table.render()
+    }
+}
+
+#[derive(Serialize)]
+struct Runs(Vec<Run>);
+
+impl CommandOutput for Runs {
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+        for run in &self.0 {
+            let role = if run.exit_code == 0 { Role::Success } else { Role::Failure };
+            text.push_str(&format!(
+                "{} {} {}\n",
+                theme::paint(Role::Muted, &run.at),
+                theme::paint(role, &format!("[{}]", run.job)),
+                run.line
+            ));
+            text.push_str(&run.output);
+        }
+        text
+    }
+}
+
+#[command(
+    name = "schedule",
+    description = "List or cancel jobs scheduled with `every` and `at`, or show what they wrote",
+    usage = "schedule [list] | schedule cancel <id> | schedule output [id]",
+    examples = ["schedule", "schedule cancel 2", "schedule output 1"],
+    json
+)]
+pub fn cmd_schedule(args: Vec<&str>) -> Result<(), CommandError> {
+    let parse_id = |id: &str| id.parse::<u32>()
+        .map_err(|_| CommandError::InvalidArguments(format!("'{}' is not a job id", id)));
+    match args.as_slice() {
+        [] | ["list"] => {
+            let jobs = JOBS.lock().unwrap_or_else(|e| e.into_inner()).iter()
+                .map(|job| JobEntry {
+                    id: job.id,
+                    schedule: job.when.to_string(),
+                    next: DateTime::from_timestamp_millis(job.next.load(Ordering::Relaxed) as i64)
+                        .map(|next| next.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string())
+                        .unwrap_or_default(),
+                    runs: job.runs.load(Ordering::Relaxed),
+                    line: job.line.clone(),
+                })
+                .collect();
+            output::emit(&Jobs(jobs))
+        }
+        ["cancel", id] => {
+            let id = parse_id(id)?;
+            let mut jobs = JOBS.lock().unwrap_or_else(|e| e.into_inner());
+            let at = jobs.iter().position(|job| job.id == id)
+                .ok_or_else(|| CommandError::CommandFailed(format!("No job {}", id)))?;
+            jobs.remove(at).cancelled.store(true, Ordering::Relaxed);
+            Ok(())
+        }
+        ["output"] => output::emit(&Runs(RUNS.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect())),
+        ["output", id] => {
+            let id = parse_id(id)?;
+            let runs = RUNS.lock().unwrap_or_else(|e| e.into_inner()).iter()
+                .filter(|run| run.job == id)
+                .cloned()
+                .collect();
+            output::emit(&Runs(runs))
+        }
+        _ => Err(CommandError::InvalidArguments(
+            "Usage: schedule [list] | schedule cancel <id> | schedule output [id]".to_string(),
+        )),
+    }
+}
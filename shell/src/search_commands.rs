@@ -0,0 +1,293 @@
+use std::{ffi::OsStr, io::Read, path::{Path, PathBuf}, sync::Mutex};
+
+use command_core::{input, output, CancellationToken, CommandError};
+use command_macro::command;
+use log::warn;
+use memchr::{memchr, memmem, memrchr};
+use rayon::prelude::*;
+use regex::bytes::{Regex, RegexBuilder};
+
+use crate::{file_index, finder, history, mapped, theme::{self, Role}, walk::Walk};
+
+/// How much of a file is checked for NUL bytes to tell binary files apart.
+const BINARY_CHECK_SIZE: usize = 8 * 1024;
+
+/// Finds where a match starts. Literal patterns go through memchr's substring search, which is much
+/// faster than running the regex engine over every line.
+enum Matcher {
+    Literal(Box<memmem::Finder<'static>>),
+    Regex(Regex),
+}
+
+impl Matcher {
+    fn new(pattern: &str, fixed: bool, ignore_case: bool) -> Result<Self, CommandError> {
+        let is_literal = fixed || !pattern.contains(['\\', '.', '+', '*', '?', '(', ')', '|', '[', ']', '{', '}', '^', '$']);
+        if is_literal && !ignore_case {
+            return Ok(Matcher::Literal(Box::new(memmem::Finder::new(pattern.as_bytes()).into_owned())));
+        }
+
+        let pattern = if fixed { regex::escape(pattern) } else { pattern.to_string() };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(ignore_case)
+            .multi_line(true)
+            .build()
+            .map(Matcher::Regex)
+            .map_err(|e| CommandError::InvalidArguments(format!("Invalid pattern: {}", e)))
+    }
+
+    /// Start of the first match in `data` at or after `from`.
+    fn find_at(&self, data: &[u8], from: usize) -> Option<usize> {
+        match self {
+            Matcher::Literal(finder) => finder.find(&data[from..]).map(|at| from + at),
+            Matcher::Regex(regex) => regex.find_at(data, from).map(|found| found.start()),
+        }
+    }
+
+    /// Whether `line` matches on its own, as a regex match found in the whole buffer may span lines.
+    fn is_match(&self, line: &[u8]) -> bool {
+        match self {
+            Matcher::Literal(_) => true,
+            Matcher::Regex(regex) => regex.is_match(line),
+        }
+    }
+}
+
+/// Lines of `data` containing a match, with their 1-based line numbers.
+fn matching_lines<'a>(matcher: &Matcher, data: &'a [u8]) -> Vec<(usize, &'a [u8])> {
+    let mut lines = Vec::new();
+    let (mut offset, mut line_number, mut counted_to) = (0, 1, 0);
+
+    // Searching the whole buffer and only then finding the line around a match skips splitting
+    // the file into lines, which is what most of the time goes into otherwise.
+    while offset < data.len() {
+        let Some(at) = matcher.find_at(data, offset) else {
+            break;
+        };
+        let start = memrchr(b'\n', &data[..at]).map_or(0, |i| i + 1);
+        let end = memchr(b'\n', &data[at..]).map_or(data.len(), |i| at + i);
+
+        line_number += memchr::memchr_iter(b'\n', &data[counted_to..start]).count();
+        counted_to = start;
+
+        let line = &data[start..end];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if matcher.is_match(line) {
+            lines.push((line_number, line));
+        }
+        offset = end + 1;
+    }
+
+    lines
+}
+
+struct GrepOptions {
+    line_numbers: bool,
+    files_only: bool,
+    with_names: bool,
+}
+
+/// Matches in one input, formatted the way they are printed.
+fn search(name: &str, data: &[u8], matcher: &Matcher, options: &GrepOptions) -> String {
+    let lines = matching_lines(matcher, data);
+    if lines.is_empty() {
+        return String::new();
+    }
+
+    let name = theme::paint(Role::Path, name);
+    if options.files_only {
+        return format!("{}\n", name);
+    }
+    if memchr(0, &data[..data.len().min(BINARY_CHECK_SIZE)]).is_some() {
+        return format!("Binary file {} matches\n", name);
+    }
+
+    lines.iter()
+        .map(|(number, line)| {
+            let mut text = String::new();
+            if options.with_names {
+                text.push_str(&format!("{}:", name));
+            }
+            if options.line_numbers {
+                text.push_str(&format!("{}:", theme::paint(Role::Muted, &number.to_string())));
+            }
+            text.push_str(&String::from_utf8_lossy(line));
+            text.push('\n');
+            text
+        })
+        .collect()
+}
+
+#[command(
+    name = "grep",
+    description = "Print lines matching a pattern, searching files in parallel",
+    usage = "grep [-i] [-n] [-r] [-F] [-l] [--ignore] [--max-depth <n>] [--sequential] [--no-mmap] <pattern> [path...]",
+    examples = ["grep TODO src/main.rs", "grep -rn main src", "grep -F -i error shell.log", "grep -rl unwrap .", "grep -r --ignore TODO ."]
+)]
+pub fn cmd_grep(args: Vec<&OsStr>) -> Result<(), CommandError> {
+    let mut ignore_case = false;
+    let mut line_numbers = false;
+    let mut recursive = false;
+    let mut fixed = false;
+    let mut files_only = false;
+    let mut parallel = true;
+    let mut allow_map = true;
+    let mut use_ignore = false;
+    let mut max_depth = None;
+    let mut pattern = None;
+    let mut targets = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--max-depth") => {
+                let depth = args.next()
+                    .and_then(|value| value.to_str())
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(|| CommandError::InvalidArguments("--max-depth needs a number".to_string()))?;
+                max_depth = Some(depth);
+            }
+            Some("--ignore-case") => ignore_case = true,
+            Some("--line-number") => line_numbers = true,
+            Some("--recursive") => recursive = true,
+            Some("--fixed-strings") => fixed = true,
+            Some("--files-with-matches") => files_only = true,
+            Some("--sequential") => parallel = false,
+            Some("--no-mmap") => allow_map = false,
+            Some("--ignore") => use_ignore = true,
+            Some(flags) if flags.len() > 1 && flags.starts_with('-') && flags[1..].chars().all(|c| "inrRFl".contains(c)) => {
+                for flag in flags[1..].chars() {
+                    match flag {
+                        'i' => ignore_case = true,
+                        'n' => line_numbers = true,
+                        'r' | 'R' => recursive = true,
+                        'F' => fixed = true,
+                        _ => files_only = true,
+                    }
+                }
+            }
+            Some(text) if pattern.is_none() => pattern = Some(text),
+            _ if pattern.is_none() => {
+                return Err(CommandError::InvalidArguments("The pattern must be valid UTF-8".to_string()));
+            }
+            _ => targets.push(Path::new(arg)),
+        }
+    }
+
+    let Some(pattern) = pattern else {
+        return Err(CommandError::InvalidArguments("Usage: grep [options] <pattern> [path...]".to_string()));
+    };
+    let matcher = Matcher::new(pattern, fixed, ignore_case)?;
+
+    if targets.is_empty() {
+        let mut data = Vec::new();
+        input::stdin().read_to_end(&mut data)?;
+        let options = GrepOptions { line_numbers, files_only, with_names: false };
+        return output::print(&search("(standard input)", &data, &matcher, &options));
+    }
+
+    let mut files = Vec::new();
+    for target in &targets {
+        if target.is_dir() {
+            if recursive {
+                // `--ignore` skips what `.gitignore` and `.ignore` files list, and hidden entries, like ripgrep.
+                let found = Mutex::new(Vec::new());
+                Walk::new()
+                    .sorted(true)
+                    .max_depth(max_depth)
+                    .hidden(!use_ignore)
+                    .ignore_files(use_ignore)
+                    .run(target, |entry| {
+                        if entry.metadata.is_file() {
+                            found.lock().unwrap_or_else(|e| e.into_inner()).push(entry.path);
+                        }
+                        Ok(())
+                    })?;
+                files.extend(found.into_inner().unwrap_or_else(|e| e.into_inner()));
+            } else {
+                warn!("'{}' is a directory", target.display());
+            }
+        } else {
+            files.push(target.to_path_buf());
+        }
+    }
+
+    let options = GrepOptions {
+        line_numbers,
+        files_only,
+        with_names: recursive || files.len() > 1,
+    };
+    let token = CancellationToken::current();
+    let search_file = |path: &PathBuf| -> Result<String, CommandError> {
+        token.check()?;
+        match mapped::read(path, allow_map) {
+            Ok(data) => Ok(search(&path.display().to_string(), &data, &matcher, &options)),
+            Err(e) => {
+                warn!("cannot read '{}': {}", path.display(), e);
+                Ok(String::new())
+            }
+        }
+    };
+
+    // Collecting keeps the input order, so output is the same however the files were scheduled.
+    let results: Vec<String> = if parallel {
+        files.par_iter().map(search_file).collect::<Result<_, _>>()?
+    } else {
+        files.iter().map(search_file).collect::<Result<_, _>>()?
+    };
+
+    output::print(&results.concat())
+}
+
+#[command(
+    name = "ff",
+    description = "Fuzzy-find files below the current directory, or pick one or a history entry interactively",
+    usage = "ff [-n <count>] <query> | ff -i|--interactive [-H|--history] [query]",
+    examples = ["ff mainrs", "ff -n 5 cmdcore", "ff -i", "ff -H git"]
+)]
+pub fn cmd_ff(args: Vec<&OsStr>) -> Result<(), CommandError> {
+    let usage = || CommandError::InvalidArguments("Usage: ff [-n <count>] <query> | ff -i|--interactive [-H|--history] [query]".to_string());
+    let mut limit = 20;
+    let mut query = None;
+    let mut interactive = false;
+    let mut history = false;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("-n") => {
+                limit = args.next()
+                    .and_then(|value| value.to_str())
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(|| CommandError::InvalidArguments("-n needs a number".to_string()))?;
+            }
+            Some("-i" | "--interactive") => interactive = true,
+            Some("-H" | "--history") => {
+                interactive = true;
+                history = true;
+            }
+            Some(text) if query.is_none() => query = Some(text),
+            _ => return Err(usage()),
+        }
+    }
+
+    // Without a query there is nothing to list, so the finder opens to type one.
+    if interactive || query.is_none() {
+        let items = if history {
+            history::recent()
+        } else {
+            file_index::search("", usize::MAX).iter().map(|path| path.display().to_string()).collect()
+        };
+        // There is no line editor to insert into, so the pick is printed, ready to copy or pipe.
+        return match finder::select(&items, query.unwrap_or_default())? {
+            Some(selected) => output::print(&format!("{}\n", selected)),
+            None => Ok(()),
+        };
+    }
+    let query = query.ok_or_else(usage)?;
+
+    let text: String = file_index::search(query, limit)
+        .iter()
+        .map(|path| format!("{}\n", theme::paint(Role::Path, &path.display().to_string())))
+        .collect();
+    output::print(&text)
+}
@@ -0,0 +1,188 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use chrono::Local;
+use command_core::{output, CommandError, CommandOutput, Table};
+use command_macro::command;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{config, default_commands::SHELL_OPTIONS, deterministic, file_commands, paths, theme::{self, Role}};
+
+/// `[session]` section of the configuration file.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct SessionConfig {
+    /// Save the session when the shell exits, under the name it was restored from or `default`.
+    pub save_on_exit: bool,
+    /// Restore the `default` session on startup, as `--restore` does.
+    pub restore: bool,
+}
+
+/// The name the session is saved under on exit: the one it was restored from, or `default`.
+static CURRENT: Mutex<Option<String>> = Mutex::new(None);
+
+const DEFAULT_NAME: &str = "default";
+
+/// What a session brings back: where it was and how it was set up. Environment variables aren't
+/// part of it: those of a `.shellproject` are set again once its directory is restored, and
+/// abbreviations are saved by `abbr` for every session.
+#[derive(Serialize, Deserialize)]
+struct Session {
+    saved_at: String,
+    cwd: PathBuf,
+    #[serde(default)]
+    dir_stack: Vec<PathBuf>,
+    /// `set` options by name.
+    #[serde(default)]
+    options: BTreeMap<String, bool>,
+}
+
+fn sessions_dir() -> Result<PathBuf, CommandError> {
+    config::data_dir()
+        .map(|dir| dir.join("sessions"))
+        .ok_or_else(|| CommandError::CommandFailed("Could not determine the home directory for sessions".to_string()))
+}
+
+fn session_path(name: &str) -> Result<PathBuf, CommandError> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(CommandError::InvalidArguments(format!("'{}' is not a valid session name", name)));
+    }
+    Ok(sessions_dir()?.join(format!("{}.json", name)))
+}
+
+fn current_name() -> String {
+    CURRENT.lock().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_else(|| DEFAULT_NAME.to_string())
+}
+
+pub fn save(name: &str) -> Result<(), CommandError> {
+    let session = Session {
+        saved_at: Local::now().to_rfc3339(),
+        cwd: env::current_dir()?,
+        dir_stack: file_commands::dir_stack(),
+        options: SHELL_OPTIONS.iter().map(|(option, get, _)| (option.to_string(), get())).collect(),
+    };
+    let path = session_path(name)?;
+    let json = serde_json::to_string_pretty(&session).map_err(|e| CommandError::CommandFailed(e.to_string()))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, json)?;
+    *CURRENT.lock().unwrap_or_else(|e| e.into_inner()) = Some(name.to_string());
+    Ok(())
+}
+
+/// Brings back session `name`; it is saved under that name from then on.
+pub fn restore(name: &str) -> Result<(), CommandError> {
+    let path = session_path(name)?;
+    let contents = fs::read_to_string(&path).map_err(|e| CommandError::FileReadError(path.clone(), e))?;
+    let session: Session = serde_json::from_str(&contents)
+        .map_err(|e| CommandError::CommandFailed(format!("Error parsing '{}': {}", path.display(), e)))?;
+
+    // A directory that has gone since is skipped rather than failing the whole restore.
+    if let Err(e) = env::set_current_dir(&session.cwd) {
+        warn!("Could not return to '{}': {}", paths::display(&session.cwd), e);
+    }
+    file_commands::set_dir_stack(session.dir_stack);
+    for (option, _, set) in SHELL_OPTIONS {
+        if let Some(enabled) = session.options.get(option) {
+            set(*enabled);
+        }
+    }
+    *CURRENT.lock().unwrap_or_else(|e| e.into_inner()) = Some(name.to_string());
+    Ok(())
+}
+
+/// Restores the session `--restore [name]` or `[session] restore` asks for, if any.
+pub fn init(shell_args: &[String]) {
+    let requested = match shell_args.iter().position(|arg| arg == "--restore") {
+        Some(at) => Some(shell_args.get(at + 1).filter(|name| !name.starts_with('-')).map_or(DEFAULT_NAME, String::as_str)),
+        None => config::get().session.restore.then_some(DEFAULT_NAME),
+    };
+    let Some(name) = requested else {
+        return;
+    };
+    match restore(name) {
+        Ok(()) => info!("Restored session '{}'", name),
+        Err(e) => error!("{}", e),
+    }
+}
+
+/// Saves the session if `[session] save_on_exit` asks for it; called right before the shell exits.
+pub fn exiting() {
+    if !config::get().session.save_on_exit {
+        return;
+    }
+    if let Err(e) = save(&current_name()) {
+        error!("{}", e);
+    }
+}
+
+#[derive(Serialize)]
+struct SessionEntry {
+    name: String,
+    saved_at: String,
+    cwd: PathBuf,
+}
+
+#[derive(Serialize)]
+struct Sessions(Vec<SessionEntry>);
+
+impl CommandOutput for Sessions {
+    fn to_text(&self) -> String {
+        let current = current_name();
+        let mut table = Table::new();
+        for entry in &self.0 {
+            let name = if entry.name == current { theme::paint(Role::Heading, &entry.name) } else { entry.name.clone() };
+            table.push_row([name, theme::paint(Role::Muted, &entry.saved_at), paths::display(&entry.cwd)]);
+        }
+        table.render()
+    }
+}
+
+fn list() -> Result<Sessions, CommandError> {
+    let dir = sessions_dir()?;
+    let mut entries: Vec<SessionEntry> = fs::read_dir(&dir).into_iter().flatten().flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let name = path.file_stem()?.to_str()?.to_string();
+            let session: Session = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+            let saved_at = deterministic::recorded(&session.saved_at).map_or(session.saved_at, |at| at.to_rfc3339());
+            Some(SessionEntry { name, saved_at, cwd: session.cwd })
+        })
+        .collect();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(Sessions(entries))
+}
+
+#[command(
+    name = "session",
+    description = "Save, restore, list or delete named sessions: the directory, directory stack and options; project variables return with the directory",
+    usage = "session [list] | session save [name] | session restore [name] | session delete <name>",
+    examples = ["session save work", "session restore work", "session list"],
+    json
+)]
+pub fn cmd_session(args: Vec<&str>) -> Result<(), CommandError> {
+    match args.as_slice() {
+        [] | ["list"] => output::emit(&list()?),
+        ["save"] => save(&current_name()),
+        ["save", name] => save(name),
+        ["restore"] => restore(DEFAULT_NAME),
+        ["restore", name] => restore(name),
+        ["delete", name] => {
+            let path = session_path(name)?;
+            fs::remove_file(&path).map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => CommandError::CommandFailed(format!("No session '{}'", name)),
+                _ => CommandError::from(e),
+            })
+        }
+        _ => Err(CommandError::InvalidArguments(
+            "Usage: session [list] | session save [name] | session restore [name] | session delete <name>".to_string(),
+        )),
+    }
+}
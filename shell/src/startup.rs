@@ -0,0 +1,50 @@
+use std::time::{Duration, Instant};
+
+use command_core::{Align, Table};
+
+/// Time spent in each phase of startup, printed with `--profile-startup`.
+pub struct StartupProfile {
+    enabled: bool,
+    started: Instant,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl StartupProfile {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            started: Instant::now(),
+            phases: Vec::new(),
+        }
+    }
+
+    /// Runs `f` as the phase `name`.
+    pub fn phase<R>(&mut self, name: &'static str, f: impl FnOnce() -> R) -> R {
+        let started = Instant::now();
+        let result = f();
+        self.phases.push((name, started.elapsed()));
+        result
+    }
+
+    /// Prints the breakdown, if profiling was asked for.
+    pub fn report(&self) {
+        if !self.enabled {
+            return;
+        }
+
+        let total = self.started.elapsed();
+        let millis = |duration: Duration| format!("{:.2} ms", duration.as_secs_f64() * 1000.0);
+
+        let mut table = Table::new()
+            .headers(["phase", "time", "share"])
+            .align(1, Align::Right)
+            .align(2, Align::Right);
+        for (name, duration) in &self.phases {
+            let share = duration.as_secs_f64() / total.as_secs_f64().max(f64::EPSILON) * 100.0;
+            table.push_row([name.to_string(), millis(*duration), format!("{:.1}%", share)]);
+        }
+        table.push_row(["total".to_string(), millis(total), String::new()]);
+
+        println!("{}", table.render());
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::{Mutex, Once},
+    time::Instant,
+};
+
+use chrono::Local;
+use command_core::{output, Align, CommandError, CommandMiddleware, CommandOutput, Invocation, Next, Table};
+use command_macro::command;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::{config, deterministic, logging::{self, RotatingFile}, prompt::format_duration, theme::{self, Role}};
+
+/// `[stats]` section of the configuration file.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct StatsConfig {
+    /// Record every command to `~/.shell/stats.log` for the `stats` builtin. Off unless turned on,
+    /// as records keep the error messages of failed commands, which may name files and hosts.
+    pub enabled: bool,
+    /// Size in bytes after which the file is rotated; `stats` reads the rotated files too.
+    pub max_size: u64,
+    pub keep: usize,
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_size: 1024 * 1024,
+            keep: 1,
+        }
+    }
+}
+
+/// One executed command, a line of the stats file.
+#[derive(Serialize, Deserialize)]
+struct Execution {
+    at: String,
+    command: String,
+    args: usize,
+    exit_code: i32,
+    duration_ms: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+fn stats_path() -> Option<PathBuf> {
+    config::data_dir().map(|dir| dir.join("stats.log"))
+}
+
+static FILE: Mutex<Option<RotatingFile>> = Mutex::new(None);
+static FILE_OPENED: Once = Once::new();
+
+fn append(execution: &Execution) {
+    FILE_OPENED.call_once(|| {
+        let config = &config::get().stats;
+        match stats_path().map(|path| RotatingFile::open(path, config.max_size, config.keep)) {
+            Some(Ok(file)) => *FILE.lock().unwrap_or_else(|e| e.into_inner()) = Some(file),
+            Some(Err(e)) => debug!("could not open the stats file: {}", e),
+            None => debug!("could not determine the home directory, not recording stats"),
+        }
+    });
+
+    let mut file = FILE.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(open) = file.as_mut() else {
+        return;
+    };
+    let written = serde_json::to_string(execution)
+        .map_err(std::io::Error::other)
+        .and_then(|line| open.write_line(&line));
+    if let Err(e) = written {
+        // Stop writing rather than failing on every command.
+        debug!("could not write the stats file: {}", e);
+        *file = None;
+    }
+}
+
+/// Records the duration, exit code and argument count of every command it wraps.
+pub struct StatsMiddleware;
+
+impl CommandMiddleware for StatsMiddleware {
+    fn handle(&self, invocation: &Invocation, next: Next) -> Result<(), CommandError> {
+        let started = Instant::now();
+        let result = next.run(invocation);
+
+        append(&Execution {
+            at: Local::now().to_rfc3339(),
+            // Aliases and abbreviations count towards the builtin they resolve to.
+            command: invocation.info.map_or(invocation.name, |info| info.name).to_string(),
+            args: invocation.args.len(),
+            exit_code: result.as_ref().map_or_else(CommandError::exit_code, |_| 0),
+            duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+            error: result.as_ref().err().map(|e| e.to_string()),
+        });
+        result
+    }
+}
+
+/// Every recorded execution, oldest first, skipping lines that can't be read or have an impossible
+/// duration.
+fn read_executions() -> Vec<Execution> {
+    let Some(path) = stats_path() else {
+        return Vec::new();
+    };
+    let files = (1..=config::get().stats.keep).rev()
+        .map(|index| logging::rotated_path(&path, index))
+        .chain(std::iter::once(path.clone()));
+
+    files
+        .filter_map(|file| fs::read_to_string(file).ok())
+        .flat_map(|contents| contents.lines().filter_map(|line| serde_json::from_str(line).ok()).collect::<Vec<_>>())
+        .filter(|execution: &Execution| execution.duration_ms.is_finite() && execution.duration_ms >= 0.0)
+        .collect()
+}
+
+#[derive(Serialize)]
+struct CommandStats {
+    command: String,
+    runs: u64,
+    failures: u64,
+    mean_ms: f64,
+    max_ms: f64,
+    mean_args: f64,
+}
+
+#[derive(Serialize)]
+struct Failure {
+    at: String,
+    command: String,
+    exit_code: i32,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Stats {
+    commands: Vec<CommandStats>,
+    recent_failures: Vec<Failure>,
+}
+
+fn millis(ms: f64) -> String {
+    std::time::Duration::try_from_secs_f64(ms / 1000.0).map_or_else(|_| "-".to_string(), format_duration)
+}
+
+impl CommandOutput for Stats {
+    fn to_text(&self) -> String {
+        if self.commands.is_empty() && !config::get().stats.enabled {
+            return "No commands recorded; set `enabled = true` under [stats] in the configuration file to record them\n".to_string();
+        }
+        if self.commands.is_empty() {
+            return "No commands recorded yet\n".to_string();
+        }
+
+        let mut table = Table::new().headers(["COMMAND", "RUNS", "FAILED", "MEAN", "MAX", "ARGS"]);
+        for column in 1..=5 {
+            table = table.align(column, Align::Right);
+        }
+        for stats in &self.commands {
+            table.push_row([
+                stats.command.clone(),
+                stats.runs.to_string(),
+                stats.failures.to_string(),
+                millis(stats.mean_ms),
+                millis(stats.max_ms),
+                format!("{:.1}", stats.mean_args),
+            ]);
+        }
+        let mut text = table.render();
+
+        if !self.recent_failures.is_empty() {
+            text.push_str("\nRecent failures:\n");
+            let mut failures = Table::new();
+            for failure in &self.recent_failures {
+                failures.push_row([
+                    theme::paint(Role::Muted, &failure.at),
+                    failure.command.clone(),
+                    theme::paint(Role::Failure, &failure.exit_code.to_string()),
+                    failure.error.clone().unwrap_or_default(),
+                ]);
+            }
+            text.push_str(&failures.render());
+        }
+        text
+    }
+}
+
+#[command(
+    name = "stats",
+    description = "Show the most used commands, their durations and recent failures",
+    usage = "stats [-n <count>] [--clear]",
+    examples = ["stats", "stats -n 20", "stats --clear"],
+    json
+)]
+pub fn cmd_stats(args: Vec<&str>) -> Result<(), CommandError> {
+    let mut count = 10;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg {
+            "-n" => {
+                count = args.next()
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(|| CommandError::InvalidArguments("-n needs a number".to_string()))?;
+            }
+            "--clear" => return clear(),
+            _ => return Err(CommandError::InvalidArguments(format!("Unknown option '{}'", arg))),
+        }
+    }
+
+    let executions = read_executions();
+    let mut by_command: HashMap<&str, CommandStats> = HashMap::new();
+    for execution in &executions {
+        let stats = by_command.entry(&execution.command).or_insert_with(|| CommandStats {
+            command: execution.command.clone(),
+            runs: 0,
+            failures: 0,
+            mean_ms: 0.0,
+            max_ms: 0.0,
+            mean_args: 0.0,
+        });
+        stats.runs += 1;
+        stats.failures += u64::from(execution.exit_code != 0);
+        // Running means, updated per execution.
+        stats.mean_ms += (execution.duration_ms - stats.mean_ms) / stats.runs as f64;
+        stats.mean_args += (execution.args as f64 - stats.mean_args) / stats.runs as f64;
+        stats.max_ms = stats.max_ms.max(execution.duration_ms);
+    }
+
+    let mut commands: Vec<CommandStats> = by_command.into_values().collect();
+    commands.sort_by(|a, b| b.runs.cmp(&a.runs).then_with(|| a.command.cmp(&b.command)));
+    commands.truncate(count);
+
+    let mut recent_failures: Vec<Failure> = executions.iter()
+        .rev()
+        .filter(|execution| execution.exit_code != 0)
+        .take(5)
+        .map(|execution| Failure {
+            at: deterministic::recorded(&execution.at).map_or_else(|| execution.at.clone(), |at| at.to_rfc3339()),
+            command: execution.command.clone(),
+            exit_code: execution.exit_code,
+            error: execution.error.clone(),
+        })
+        .collect();
+    recent_failures.reverse();
+
+    output::emit(&Stats { commands, recent_failures })
+}
+
+fn clear() -> Result<(), CommandError> {
+    let Some(path) = stats_path() else {
+        return Ok(());
+    };
+    let mut file = FILE.lock().unwrap_or_else(|e| e.into_inner());
+    for index in 1..=config::get().stats.keep {
+        _ = fs::remove_file(logging::rotated_path(&path, index));
+    }
+    match fs::remove_file(&path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(CommandError::CommandFailed(format!("Could not remove '{}': {}", path.display(), e))),
+    }
+    // The open handle still points at the removed file.
+    if let Some(open) = file.as_mut() {
+        *open = RotatingFile::open(path, config::get().stats.max_size, config::get().stats.keep)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,109 @@
+//! End-to-end tests of builtins through [`shell_test::TestShell`]; only the commands of this crate
+//! are registered in its test binary. The middleware `main` installs isn't, see the golden scripts
+//! in `tests/golden` for that.
+
+use std::sync::Arc;
+
+use command_core::vfs::MemoryFs;
+use shell_test::TestShell;
+
+#[test]
+fn rm_removes_a_file_and_reports_a_missing_one() {
+    let mut shell = TestShell::new();
+    shell.write("notes.txt", "hello");
+
+    shell.run("rm notes.txt").assert_ok();
+    assert!(!shell.exists("notes.txt"));
+    shell.run("rm notes.txt").assert_err("doesn't exist");
+}
+
+#[test]
+fn rm_needs_r_for_a_directory() {
+    let mut shell = TestShell::new();
+    shell.mkdir("build/nested");
+    shell.write("build/nested/out.o", "");
+
+    shell.run("rm build").assert_err("is a directory");
+    assert!(shell.is_dir("build"));
+    shell.run("rm -r build").assert_ok();
+    assert!(!shell.exists("build"));
+}
+
+#[test]
+fn rmdir_keeps_a_directory_that_is_not_empty() {
+    let mut shell = TestShell::new();
+    shell.write("build/out.o", "");
+
+    shell.run("rmdir build").assert_err("build");
+    assert!(shell.exists("build/out.o"));
+}
+
+#[test]
+fn cat_writes_a_heredoc_to_a_file() {
+    let mut shell = TestShell::new();
+
+    shell.run("cat > notes.txt <<EOF\nfirst\nsecond\nEOF").assert_ok();
+    assert_eq!(shell.read("notes.txt"), "first\nsecond\n");
+    shell.run("cat >> notes.txt <<EOF\nthird\nEOF").assert_ok();
+    assert_eq!(shell.read("notes.txt"), "first\nsecond\nthird\n");
+}
+
+#[test]
+fn rm_goes_through_the_virtual_filesystem() {
+    let mut shell = TestShell::new();
+    let memory = Arc::new(MemoryFs::new());
+    memory.add_file("notes.txt", "hello");
+    shell.use_fs(memory.clone());
+
+    shell.run("rm notes.txt").assert_ok();
+    assert_eq!(memory.contents("notes.txt"), None);
+}
+
+#[test]
+fn dry_run_commands_document_the_flag() {
+    for info in command_core::CommandRegistry::all().filter(|info| info.dry_run) {
+        assert!(info.usage.contains("--dry-run"), "'{}' takes --dry-run without documenting it", info.name);
+    }
+}
+
+#[test]
+fn unknown_commands_are_not_found() {
+    let mut shell = TestShell::new();
+    shell.run("no-such-command").assert_err("not found");
+}
+
+/// A name that isn't valid UTF-8, which Unix file systems allow.
+#[cfg(unix)]
+fn non_utf8_name() -> &'static std::ffi::OsStr {
+    use std::os::unix::ffi::OsStrExt;
+    std::ffi::OsStr::from_bytes(b"bad\xffname.txt")
+}
+
+#[cfg(unix)]
+#[test]
+fn ls_shows_a_non_utf8_name_lossily() {
+    let mut shell = TestShell::new();
+    shell.write(non_utf8_name(), "hello");
+
+    shell.run("ls").assert_ok().assert_stdout_contains("bad\u{FFFD}name.txt");
+}
+
+#[cfg(unix)]
+#[test]
+fn rm_removes_a_non_utf8_name_it_matched() {
+    let mut shell = TestShell::new();
+    shell.write(non_utf8_name(), "hello");
+
+    shell.run("rm bad*").assert_ok();
+    assert!(!shell.exists(non_utf8_name()));
+}
+
+#[cfg(unix)]
+#[test]
+fn rm_r_removes_a_directory_holding_a_non_utf8_name() {
+    let mut shell = TestShell::new();
+    shell.write(std::path::Path::new("build").join(non_utf8_name()), "");
+
+    shell.run("rm -r build").assert_ok();
+    assert!(!shell.exists("build"));
+}
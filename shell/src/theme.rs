@@ -0,0 +1,199 @@
+use std::{collections::HashMap, env, sync::RwLock};
+
+use command_core::CommandError;
+use log::warn;
+use serde::Deserialize;
+
+/// What a piece of colored output is, so the active theme can decide how it looks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Role {
+    User,
+    Path,
+    Heading,
+    Enabled,
+    Disabled,
+    Success,
+    Failure,
+    Muted,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Role {
+    pub const ALL: [Role; 13] = [
+        Role::User, Role::Path, Role::Heading, Role::Enabled, Role::Disabled,
+        Role::Success, Role::Failure, Role::Muted,
+        Role::Error, Role::Warn, Role::Info, Role::Debug, Role::Trace,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Path => "path",
+            Role::Heading => "heading",
+            Role::Enabled => "enabled",
+            Role::Disabled => "disabled",
+            Role::Success => "success",
+            Role::Failure => "failure",
+            Role::Muted => "muted",
+            Role::Error => "error",
+            Role::Warn => "warn",
+            Role::Info => "info",
+            Role::Debug => "debug",
+            Role::Trace => "trace",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Role> {
+        Role::ALL.into_iter().find(|role| role.name() == name)
+    }
+}
+
+/// Built-in themes as SGR parameters per role; roles left out are printed plain.
+const THEMES: &[(&str, &[(Role, &str)])] = &[
+    ("default", &[
+        (Role::User, "35"),
+        (Role::Path, "32"),
+        (Role::Heading, "34"),
+        (Role::Enabled, "32"),
+        (Role::Disabled, "31"),
+        (Role::Success, "32"),
+        (Role::Failure, "1;31"),
+        (Role::Muted, "90"),
+        (Role::Error, "1;31"),
+        (Role::Warn, "1;33"),
+        (Role::Info, "34"),
+        (Role::Debug, "32"),
+    ]),
+    ("bright", &[
+        (Role::User, "1;95"),
+        (Role::Path, "1;92"),
+        (Role::Heading, "1;94"),
+        (Role::Enabled, "92"),
+        (Role::Disabled, "91"),
+        (Role::Success, "1;92"),
+        (Role::Failure, "1;91"),
+        (Role::Muted, "37"),
+        (Role::Error, "1;91"),
+        (Role::Warn, "1;93"),
+        (Role::Info, "94"),
+        (Role::Debug, "96"),
+        (Role::Trace, "2"),
+    ]),
+    ("mono", &[
+        (Role::User, "1"),
+        (Role::Heading, "1"),
+        (Role::Disabled, "2"),
+        (Role::Failure, "1"),
+        (Role::Muted, "2"),
+        (Role::Error, "1"),
+        (Role::Warn, "1"),
+        (Role::Debug, "2"),
+        (Role::Trace, "2"),
+    ]),
+];
+
+/// `[theme]` section of the configuration file.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct ThemeConfig {
+    /// One of the built-in themes, `default` when unset.
+    pub name: Option<String>,
+    /// Per-role overrides, e.g. `user = "1;36"`.
+    pub colors: HashMap<String, String>,
+}
+
+pub struct Theme {
+    name: String,
+    colors: HashMap<Role, String>,
+}
+
+impl Theme {
+    /// The built-in theme called `name`.
+    pub fn builtin(name: &str) -> Option<Theme> {
+        let (name, colors) = THEMES.iter().find(|(theme, _)| *theme == name)?;
+        Some(Theme {
+            name: name.to_string(),
+            colors: colors.iter().map(|&(role, code)| (role, code.to_string())).collect(),
+        })
+    }
+
+    fn from_config(config: &ThemeConfig) -> Theme {
+        let name = config.name.as_deref().unwrap_or("default");
+        let mut theme = Theme::builtin(name).unwrap_or_else(|| {
+            warn!("unknown theme '{}', using 'default'", name);
+            Theme::builtin("default").expect("the default theme exists")
+        });
+
+        for (key, code) in &config.colors {
+            match Role::from_name(key) {
+                Some(role) => _ = theme.colors.insert(role, code.clone()),
+                None => warn!("unknown theme color '{}'", key),
+            }
+        }
+
+        theme
+    }
+
+    /// Wraps `text` in the escape sequence for `role`, ignoring whether colors are enabled.
+    pub fn paint_always(&self, role: Role, text: &str) -> String {
+        match self.colors.get(&role) {
+            Some(code) if !code.is_empty() => format!("\x1b[{}m{}\x1b[0m", code, text),
+            _ => text.to_string(),
+        }
+    }
+}
+
+static THEME: RwLock<Option<Theme>> = RwLock::new(None);
+
+/// Loads the theme from the configuration and disables colors for `NO_COLOR` or `--no-color`.
+pub fn init(config: &ThemeConfig, no_color: bool) {
+    if no_color || env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        colored::control::set_override(false);
+    }
+
+    *THEME.write().unwrap_or_else(|e| e.into_inner()) = Some(Theme::from_config(config));
+}
+
+pub fn names() -> impl Iterator<Item = &'static str> {
+    THEMES.iter().map(|(name, _)| *name)
+}
+
+/// Name of the active theme.
+pub fn current() -> String {
+    THEME.read()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map_or_else(|| "default".to_string(), |theme| theme.name.clone())
+}
+
+/// Switches to the built-in theme `name`.
+pub fn set(name: &str) -> Result<(), CommandError> {
+    let theme = Theme::builtin(name).ok_or_else(|| CommandError::InvalidArguments(format!(
+        "Unknown theme '{}', expected one of: {}",
+        name,
+        names().collect::<Vec<_>>().join(", ")
+    )))?;
+
+    *THEME.write().unwrap_or_else(|e| e.into_inner()) = Some(theme);
+    Ok(())
+}
+
+pub fn colors_enabled() -> bool {
+    colored::control::SHOULD_COLORIZE.should_colorize()
+}
+
+/// `text` colored for `role` in the active theme, or plain when colors are disabled.
+pub fn paint(role: Role, text: &str) -> String {
+    if !colors_enabled() {
+        return text.to_string();
+    }
+
+    match THEME.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        Some(theme) => theme.paint_always(role, text),
+        None => text.to_string(),
+    }
+}
@@ -0,0 +1,255 @@
+use std::{
+    io::{self, IsTerminal, Write},
+    time::{Duration, Instant},
+};
+
+use command_core::{output, Align, CommandError, CommandOutput, OutputFormat, Table};
+use command_macro::command;
+use crossterm::{
+    cursor, event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, Clear, ClearType},
+};
+use humansize::{format_size, DECIMAL};
+use serde::Serialize;
+
+use crate::{pager::FullScreen, processes::{ProcessInfo, Processes}};
+
+const REFRESH: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SortBy {
+    Cpu,
+    Memory,
+    Pid,
+    Name,
+}
+
+fn sort(processes: &mut [ProcessInfo], by: SortBy) {
+    match by {
+        SortBy::Cpu => processes.sort_by(|a, b| b.cpu.total_cmp(&a.cpu).then(a.pid.cmp(&b.pid))),
+        SortBy::Memory => processes.sort_by(|a, b| b.memory.cmp(&a.memory).then(a.pid.cmp(&b.pid))),
+        SortBy::Pid => processes.sort_by_key(|process| process.pid),
+        SortBy::Name => processes.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()).then(a.pid.cmp(&b.pid))),
+    }
+}
+
+struct Top {
+    processes: Processes,
+    list: Vec<ProcessInfo>,
+    sort_by: SortBy,
+    /// Pid under the cursor, so it stays selected while the list reorders.
+    selected: Option<u32>,
+    /// Pid waiting for `y` to be killed.
+    confirm_kill: Option<u32>,
+    message: Option<String>,
+}
+
+impl Top {
+    fn new() -> Self {
+        let mut top = Self {
+            processes: Processes::new(),
+            list: Vec::new(),
+            sort_by: SortBy::Cpu,
+            selected: None,
+            confirm_kill: None,
+            message: None,
+        };
+        top.refresh();
+        top
+    }
+
+    fn refresh(&mut self) {
+        self.processes.refresh();
+        self.list = self.processes.list();
+        sort(&mut self.list, self.sort_by);
+        if self.selected.is_none_or(|pid| !self.list.iter().any(|process| process.pid == pid)) {
+            self.selected = self.list.first().map(|process| process.pid);
+        }
+    }
+
+    fn selected_index(&self) -> usize {
+        self.selected
+            .and_then(|pid| self.list.iter().position(|process| process.pid == pid))
+            .unwrap_or(0)
+    }
+
+    fn move_by(&mut self, offset: isize) {
+        let index = self.selected_index().saturating_add_signed(offset).min(self.list.len().saturating_sub(1));
+        self.selected = self.list.get(index).map(|process| process.pid);
+    }
+
+    fn run(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let mut refreshed = Instant::now();
+        loop {
+            let (_, rows) = terminal::size()?;
+            // Two lines of summary and a header above the list, a status line below it.
+            let page = (rows as usize).saturating_sub(4).max(1);
+            self.draw(out, page)?;
+
+            if event::poll(REFRESH.saturating_sub(refreshed.elapsed()))? {
+                if let Event::Key(KeyEvent { code, modifiers, kind: KeyEventKind::Press, .. }) = event::read()? {
+                    if !self.key(code, modifiers, page) {
+                        return Ok(());
+                    }
+                }
+            }
+            if refreshed.elapsed() >= REFRESH {
+                self.refresh();
+                refreshed = Instant::now();
+            }
+        }
+    }
+
+    /// Handles a key press, returning `false` to quit.
+    fn key(&mut self, code: KeyCode, modifiers: KeyModifiers, page: usize) -> bool {
+        if let Some(pid) = self.confirm_kill.take() {
+            self.message = Some(match code {
+                KeyCode::Char('y') if self.processes.kill(pid) => format!("Sent a termination signal to {}", pid),
+                KeyCode::Char('y') => format!("Could not kill {}", pid),
+                _ => "Not killed".to_string(),
+            });
+            return true;
+        }
+        self.message = None;
+
+        match code {
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Down | KeyCode::Char('j') => self.move_by(1),
+            KeyCode::Up => self.move_by(-1),
+            KeyCode::PageDown => self.move_by(page as isize),
+            KeyCode::PageUp => self.move_by(-(page as isize)),
+            KeyCode::Home => self.selected = self.list.first().map(|process| process.pid),
+            KeyCode::End => self.selected = self.list.last().map(|process| process.pid),
+            KeyCode::Char(key @ ('c' | 'm' | 'p' | 'n')) => {
+                self.sort_by = match key {
+                    'c' => SortBy::Cpu,
+                    'm' => SortBy::Memory,
+                    'p' => SortBy::Pid,
+                    _ => SortBy::Name,
+                };
+                sort(&mut self.list, self.sort_by);
+            }
+            KeyCode::Char('k') => self.confirm_kill = self.selected,
+            _ => {}
+        }
+        true
+    }
+
+    fn draw(&self, out: &mut impl Write, page: usize) -> io::Result<()> {
+        queue!(out, Clear(ClearType::All))?;
+
+        let (used, total) = self.processes.memory();
+        let summary = format!(
+            "CPU {:5.1}%   memory {} of {}   {} processes",
+            self.processes.cpu_usage(),
+            format_size(used, DECIMAL),
+            format_size(total, DECIMAL),
+            self.list.len()
+        );
+        let marker = |by: SortBy| if self.sort_by == by { "*" } else { " " };
+        let header = format!(
+            "{:>8}{} {:>6}{} {:>10}{} {}{}",
+            "PID", marker(SortBy::Pid), "CPU%", marker(SortBy::Cpu), "MEM", marker(SortBy::Memory), "NAME", marker(SortBy::Name)
+        );
+        queue!(
+            out,
+            cursor::MoveTo(0, 0),
+            Print(summary),
+            cursor::MoveTo(0, 2),
+            SetAttribute(Attribute::Bold),
+            Print(header),
+            SetAttribute(Attribute::Reset)
+        )?;
+
+        let selected = self.selected_index();
+        let first = selected.saturating_sub(page - 1);
+        for (row, (index, process)) in self.list.iter().enumerate().skip(first).take(page).enumerate() {
+            let line = format!(
+                "{:>8}  {:>6.1}  {:>10}  {}",
+                process.pid,
+                process.cpu,
+                format_size(process.memory, DECIMAL),
+                process.name
+            );
+            queue!(out, cursor::MoveTo(0, (row + 3) as u16))?;
+            if index == selected {
+                queue!(out, SetAttribute(Attribute::Reverse), Print(line), SetAttribute(Attribute::Reset))?;
+            } else {
+                queue!(out, Print(line))?;
+            }
+        }
+
+        let status = match (&self.message, self.confirm_kill) {
+            (_, Some(pid)) => format!("Kill {}? [y/N]", pid),
+            (Some(message), None) => message.clone(),
+            (None, None) => "q quit, c/m/p/n sort by CPU, memory, pid or name, k kill".to_string(),
+        };
+        queue!(
+            out,
+            cursor::MoveTo(0, (page + 3) as u16),
+            SetAttribute(Attribute::Reverse),
+            Print(status),
+            SetAttribute(Attribute::Reset)
+        )?;
+        out.flush()
+    }
+}
+
+#[derive(Serialize)]
+struct ProcessEntry {
+    pid: u32,
+    name: String,
+    cpu: f32,
+    memory: u64,
+}
+
+#[derive(Serialize)]
+struct ProcessTable(Vec<ProcessEntry>);
+
+impl CommandOutput for ProcessTable {
+    fn to_text(&self) -> String {
+        let mut table = Table::new()
+            .headers(["PID", "CPU%", "MEM", "NAME"])
+            .align(0, Align::Right)
+            .align(1, Align::Right)
+            .align(2, Align::Right);
+        for process in &self.0 {
+            table.push_row([
+                process.pid.to_string(),
+                format!("{:.1}", process.cpu),
+                format_size(process.memory, DECIMAL),
+                process.name.clone(),
+            ]);
+        }
+        table.render()
+    }
+}
+
+#[command(
+    name = "top",
+    description = "Show a live process table; sort it and kill the process under the cursor",
+    usage = "top",
+    examples = ["top", "top --json"],
+    json
+)]
+pub fn cmd_top() -> Result<(), CommandError> {
+    // Without a terminal to draw on, one snapshot is printed instead, busiest first.
+    if !io::stdout().is_terminal() || !io::stdin().is_terminal() || output::format() == OutputFormat::Json {
+        let mut processes = Processes::new();
+        std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+        processes.refresh();
+        let mut list = processes.list();
+        sort(&mut list, SortBy::Cpu);
+        let entries = list.into_iter()
+            .map(|process| ProcessEntry { pid: process.pid, name: process.name, cpu: process.cpu, memory: process.memory })
+            .collect();
+        return output::emit(&ProcessTable(entries));
+    }
+
+    let mut stdout = io::stdout();
+    let _screen = FullScreen::enter(&mut stdout)?;
+    Ok(Top::new().run(&mut stdout)?)
+}
@@ -0,0 +1,195 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use command_core::{output, table::strip_ansi, CommandError};
+use log::info;
+
+use crate::config;
+
+/// The log a session is written to while `set transcript on` is in effect.
+struct Transcript {
+    path: PathBuf,
+    file: BufWriter<File>,
+    /// Whether the next text starts a line, which then gets a timestamp.
+    at_line_start: bool,
+    /// The end of the last output written, when it cut a character or an escape sequence in two.
+    pending: Vec<u8>,
+}
+
+/// How much of an unfinished escape sequence is held back before it is written as it is.
+const MAX_PENDING: usize = 64;
+
+impl Transcript {
+    /// Writes `text` without colors, every line starting with the time it was shown at.
+    fn write(&mut self, text: &str) -> std::io::Result<()> {
+        let timestamp = chrono::Local::now().format("[%Y-%m-%d %H:%M:%S] ").to_string();
+        for line in strip_ansi(text).split_inclusive('\n') {
+            if self.at_line_start {
+                self.file.write_all(timestamp.as_bytes())?;
+            }
+            self.file.write_all(line.as_bytes())?;
+            self.at_line_start = line.ends_with('\n');
+        }
+        self.file.flush()
+    }
+
+    /// Writes output as it is printed, holding back a trailing partial character or escape sequence
+    /// until the rest of it arrives.
+    fn record(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.pending.extend_from_slice(data);
+        let complete = complete_len(&self.pending);
+        let rest = if self.pending.len() - complete > MAX_PENDING {
+            Vec::new()
+        } else {
+            self.pending.split_off(complete)
+        };
+        let text = String::from_utf8_lossy(&std::mem::replace(&mut self.pending, rest)).into_owned();
+        self.write(&text)
+    }
+
+    /// Writes output held back by [`Transcript::record`], which won't be completed anymore.
+    fn flush_pending(&mut self) -> std::io::Result<()> {
+        let pending = std::mem::take(&mut self.pending);
+        if pending.is_empty() {
+            return Ok(());
+        }
+        self.write(&String::from_utf8_lossy(&pending))
+    }
+}
+
+/// The length of `bytes` without a UTF-8 character or CSI escape sequence cut off at the end.
+fn complete_len(bytes: &[u8]) -> usize {
+    let mut end = bytes.len();
+    let tail = end.saturating_sub(3);
+    if let Some(lead) = bytes[tail..].iter().rposition(|&byte| byte & 0xC0 != 0x80).map(|i| tail + i) {
+        let needed = match bytes[lead] {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 1,
+        };
+        if end - lead < needed {
+            end = lead;
+        }
+    }
+
+    // Matches what `strip_ansi` removes: ESC and one char, or ESC '[' up to a final byte.
+    if let Some(escape) = bytes[..end].iter().rposition(|&byte| byte == 0x1b) {
+        let complete = match bytes[escape + 1..end].split_first() {
+            None => false,
+            Some((b'[', parameters)) => parameters.iter().any(|byte| (b'@'..=b'~').contains(byte)),
+            Some(_) => true,
+        };
+        if !complete {
+            end = escape;
+        }
+    }
+    end
+}
+
+static TRANSCRIPT: Mutex<Option<Transcript>> = Mutex::new(None);
+
+/// Applies `f` to the transcript, if one is being written. A transcript that can't be written
+/// anymore is closed; nothing is logged, since that would go to the transcript as well.
+fn with_transcript(f: impl FnOnce(&mut Transcript) -> std::io::Result<()>) {
+    let mut transcript = TRANSCRIPT.lock().unwrap_or_else(|e| e.into_inner());
+    let failed = transcript.as_mut().is_some_and(|active| f(active).is_err());
+    if failed {
+        *transcript = None;
+        drop(transcript);
+        output::set_tee("transcript", None);
+    }
+}
+
+pub fn is_active() -> bool {
+    TRANSCRIPT.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+/// `~/.shell/transcripts/<date>-<time>.log`, for `set transcript on` without a file.
+fn default_path() -> Option<PathBuf> {
+    let name = chrono::Local::now().format("%Y%m%d-%H%M%S.log").to_string();
+    config::data_dir().map(|dir| dir.join("transcripts").join(name))
+}
+
+/// Starts appending the session to `path`.
+fn start(path: &Path) -> Result<(), CommandError> {
+    if let Some(active) = TRANSCRIPT.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return Err(CommandError::CommandFailed(format!("Already writing a transcript to '{}'", active.path.display())));
+    }
+
+    let file = path.parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| OpenOptions::new().create(true).append(true).open(path))
+        .map_err(|e| CommandError::CommandFailed(format!("Could not open '{}': {}", path.display(), e)))?;
+    let mut transcript = Transcript {
+        path: path.to_path_buf(),
+        file: BufWriter::new(file),
+        at_line_start: true,
+        pending: Vec::new(),
+    };
+    transcript.write("--- transcript started ---\n")?;
+
+    // Logged before starting, so the transcript doesn't open with it.
+    info!("Writing a transcript to '{}', run 'set transcript off' to stop", path.display());
+    *TRANSCRIPT.lock().unwrap_or_else(|e| e.into_inner()) = Some(transcript);
+    output::set_tee("transcript", Some(record_output));
+    Ok(())
+}
+
+fn stop() -> Option<PathBuf> {
+    output::set_tee("transcript", None);
+    let mut transcript = TRANSCRIPT.lock().unwrap_or_else(|e| e.into_inner()).take()?;
+    _ = transcript.flush_pending();
+    _ = transcript.write("--- transcript ended ---\n");
+    Some(transcript.path)
+}
+
+/// Writes the prompt and the line entered at it. Of a prompt with a right side, only the left one
+/// the line follows is kept.
+pub fn command_entered(prompt: &str, line: &str) {
+    let left = prompt.rsplit("\x1b8").next().unwrap_or(prompt);
+    with_transcript(|transcript| {
+        transcript.flush_pending()?;
+        transcript.write(&format!("{}{}\n", left, line))
+    });
+}
+
+/// Writes what was printed; installed as an output tee while writing a transcript.
+pub fn record_output(data: &[u8]) {
+    with_transcript(|transcript| transcript.record(data));
+}
+
+/// `set transcript [on [file]|off]`: shows whether a transcript is written, or starts or stops one.
+pub fn set_option(value: Option<&str>, path: Option<&Path>) -> Result<(), CommandError> {
+    match (value, path) {
+        (None, _) => {
+            let path = TRANSCRIPT.lock().unwrap_or_else(|e| e.into_inner()).as_ref().map(|active| active.path.clone());
+            match path {
+                Some(path) => output::print(&format!("on {}\n", path.display())),
+                None => output::print("off\n"),
+            }
+        }
+        (Some("on"), path) => {
+            let path = path.map(Path::to_path_buf).or_else(default_path).ok_or_else(|| {
+                CommandError::CommandFailed("Could not determine the home directory, give a file".to_string())
+            })?;
+            start(&path)
+        }
+        (Some("off"), _) => match stop() {
+            Some(path) => {
+                info!("Transcript saved to '{}'", path.display());
+                Ok(())
+            }
+            None => Err(CommandError::CommandFailed("Not writing a transcript".to_string())),
+        },
+        (Some(other), _) => Err(CommandError::InvalidArguments(format!(
+            "Expected 'on [file]' or 'off', got '{}'",
+            other
+        ))),
+    }
+}
@@ -0,0 +1,174 @@
+use std::{fs, io, path::{Path, PathBuf}};
+
+use chrono::Local;
+use command_core::CommandError;
+use serde::{Deserialize, Serialize};
+
+use crate::{config, restrict};
+
+/// `[trash]` section of the configuration file.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct TrashConfig {
+    /// Make `rm` move items to the trash as if `--trash` was given.
+    pub rm: bool,
+}
+
+/// Where an item in the trash came from.
+#[derive(Serialize, Deserialize)]
+pub struct TrashInfo {
+    pub original: PathBuf,
+    pub deleted: String,
+}
+
+pub struct TrashEntry {
+    pub id: String,
+    pub info: TrashInfo,
+}
+
+/// `~/.shell/trash`, holding the items in `files/<id>` and where they came from in `info/<id>.toml`.
+fn trash_dir() -> Result<PathBuf, CommandError> {
+    config::data_dir()
+        .map(|dir| dir.join("trash"))
+        .ok_or_else(|| CommandError::CommandFailed("Could not determine the home directory for the trash".to_string()))
+}
+
+fn files_dir() -> Result<PathBuf, CommandError> {
+    trash_dir().map(|dir| dir.join("files"))
+}
+
+fn info_dir() -> Result<PathBuf, CommandError> {
+    trash_dir().map(|dir| dir.join("info"))
+}
+
+/// Copies `from` to `to` recursively, for moves across filesystems.
+fn copy_tree(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(from)?.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_tree(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+/// Renames `from` to `to`, copying and deleting when they are on different filesystems.
+fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    copy_tree(from, to)?;
+    if fs::symlink_metadata(from)?.is_dir() {
+        fs::remove_dir_all(from)
+    } else {
+        fs::remove_file(from)
+    }
+}
+
+/// `path` made absolute, resolving links in the directories above it but not `path` itself, so a
+/// trashed link is restored as the link.
+fn absolute_path(path: &Path) -> io::Result<PathBuf> {
+    fs::symlink_metadata(path)?;
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+            Ok(parent.canonicalize()?.join(name))
+        }
+        _ => path.canonicalize(),
+    }
+}
+
+/// Moves `path` into the trash, returning the id it can be restored with.
+pub fn move_to_trash(path: &Path) -> Result<String, CommandError> {
+    let original = absolute_path(path)?;
+    let (files, info) = (files_dir()?, info_dir()?);
+    fs::create_dir_all(&files)?;
+    fs::create_dir_all(&info)?;
+
+    let deleted = Local::now();
+    let stamp = deleted.format("%Y%m%d-%H%M%S").to_string();
+    let id = (0..)
+        .map(|n| if n == 0 { stamp.clone() } else { format!("{}-{}", stamp, n) })
+        .find(|id| !files.join(id).exists() && !info.join(format!("{}.toml", id)).exists())
+        .expect("an unused id exists");
+
+    let record = TrashInfo {
+        original,
+        deleted: deleted.to_rfc3339(),
+    };
+    let record = toml::to_string(&record)
+        .map_err(|e| CommandError::CommandFailed(format!("Could not record trash entry: {}", e)))?;
+    fs::write(info.join(format!("{}.toml", id)), record)?;
+
+    if let Err(e) = move_path(path, &files.join(&id)) {
+        _ = fs::remove_file(info.join(format!("{}.toml", id)));
+        return Err(CommandError::CommandFailed(format!("Could not move '{}' to the trash: {}", path.display(), e)));
+    }
+
+    Ok(id)
+}
+
+/// Items in the trash, oldest first.
+pub fn list() -> Result<Vec<TrashEntry>, CommandError> {
+    let info = info_dir()?;
+    let Ok(entries) = fs::read_dir(&info) else {
+        return Ok(Vec::new());
+    };
+
+    let mut items: Vec<TrashEntry> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            let id = path.file_stem()?.to_string_lossy().into_owned();
+            let info = toml::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+            Some(TrashEntry { id, info })
+        })
+        .collect();
+
+    items.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(items)
+}
+
+/// Moves the item `id` back to where it was deleted from.
+pub fn restore(id: &str) -> Result<PathBuf, CommandError> {
+    // Ids are file names in the trash, so one can't reach outside it.
+    if id.is_empty() || id.contains(['/', '\\']) || id.starts_with('.') {
+        return Err(CommandError::InvalidArguments(format!("'{}' is not a trash id", id)));
+    }
+    let info_path = info_dir()?.join(format!("{}.toml", id));
+    let record: TrashInfo = fs::read_to_string(&info_path)
+        .ok()
+        .and_then(|contents| toml::from_str(&contents).ok())
+        .ok_or_else(|| CommandError::InvalidArguments(format!("No item '{}' in the trash", id)))?;
+
+    restrict::check(&record.original)?;
+    if record.original.exists() {
+        return Err(CommandError::CommandFailed(format!(
+            "Cannot restore '{}': the path already exists",
+            record.original.display()
+        )));
+    }
+    if let Some(parent) = record.original.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    move_path(&files_dir()?.join(id), &record.original)
+        .map_err(|e| CommandError::CommandFailed(format!("Could not restore '{}': {}", record.original.display(), e)))?;
+    fs::remove_file(info_path)?;
+
+    Ok(record.original)
+}
+
+/// Permanently deletes everything in the trash, returning how many items there were.
+pub fn empty() -> Result<usize, CommandError> {
+    let count = list()?.len();
+    let dir = trash_dir()?;
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    Ok(count)
+}
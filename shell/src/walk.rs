@@ -0,0 +1,156 @@
+use std::{fs::{self, Metadata}, path::{Path, PathBuf}, sync::Mutex};
+
+use command_core::{CancellationToken, CommandError};
+use ignore::{WalkBuilder, WalkState};
+use log::warn;
+
+use crate::metadata::Dereference;
+
+/// An entry found while walking.
+pub struct Entry {
+    pub path: PathBuf,
+    pub metadata: Metadata,
+}
+
+/// How commands that recurse walk a directory tree: how deep, which entries are skipped, whether
+/// links are followed and whether directories are read on several threads.
+#[derive(Clone, Copy)]
+pub struct Walk {
+    max_depth: Option<usize>,
+    hidden: bool,
+    ignore_files: bool,
+    dereference: Dereference,
+    parallel: bool,
+    sorted: bool,
+}
+
+impl Default for Walk {
+    fn default() -> Self {
+        Self {
+            max_depth: None,
+            hidden: true,
+            ignore_files: false,
+            dereference: Dereference::Never,
+            parallel: false,
+            sorted: false,
+        }
+    }
+}
+
+impl Walk {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many levels below the root are visited; `Some(0)` only visits the root.
+    pub fn max_depth(mut self, depth: Option<usize>) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Whether entries starting with `.` are visited.
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Whether `.gitignore`, `.ignore` and the global git excludes are honored.
+    pub fn ignore_files(mut self, ignore_files: bool) -> Self {
+        self.ignore_files = ignore_files;
+        self
+    }
+
+    /// Links below the root are followed with [`Dereference::Always`], unless the shell is
+    /// restricted; the root itself always is.
+    pub fn dereference(mut self, dereference: Dereference) -> Self {
+        self.dereference = dereference;
+        self
+    }
+
+    /// Read directories on several threads; entries then arrive in no particular order.
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Visit the entries of each directory by name. Only applies to walks that aren't parallel.
+    pub fn sorted(mut self, sorted: bool) -> Self {
+        self.sorted = sorted;
+        self
+    }
+
+    fn builder(&self, root: &Path) -> WalkBuilder {
+        let mut builder = WalkBuilder::new(root);
+        builder
+            .max_depth(self.max_depth)
+            .hidden(!self.hidden)
+            .ignore(self.ignore_files)
+            .git_ignore(self.ignore_files)
+            .git_global(self.ignore_files)
+            .git_exclude(self.ignore_files)
+            .parents(self.ignore_files)
+            .require_git(false)
+            .follow_links(self.dereference.follows(false));
+        if self.sorted {
+            builder.sort_by_file_name(|a, b| a.cmp(b));
+        }
+        builder
+    }
+
+    /// Calls `visit` with `root` and everything below it. Entries that cannot be read are reported
+    /// and skipped; the walk stops at the first error `visit` returns or when the command is interrupted.
+    pub fn run(
+        &self,
+        root: &Path,
+        visit: impl Fn(Entry) -> Result<(), CommandError> + Sync,
+    ) -> Result<(), CommandError> {
+        // A trailing separator makes the OS resolve a link given as the root, so it is walked as a directory.
+        let root = if fs::symlink_metadata(root).is_ok_and(|metadata| metadata.is_symlink()) {
+            root.join("")
+        } else {
+            root.to_path_buf()
+        };
+
+        let token = CancellationToken::current();
+        let failure = Mutex::new(None);
+
+        let handle = |result: Result<ignore::DirEntry, ignore::Error>| {
+            if token.is_cancelled() {
+                return WalkState::Quit;
+            }
+
+            let entry = match result.and_then(|entry| entry.metadata().map(|metadata| (entry, metadata))) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn!("{}", e);
+                    return WalkState::Continue;
+                }
+            };
+            let (entry, metadata) = entry;
+
+            match visit(Entry { path: entry.path().to_path_buf(), metadata }) {
+                Ok(()) => WalkState::Continue,
+                Err(e) => {
+                    failure.lock().unwrap_or_else(|e| e.into_inner()).get_or_insert(e);
+                    WalkState::Quit
+                }
+            }
+        };
+
+        if self.parallel {
+            self.builder(&root).build_parallel().run(|| Box::new(&handle));
+        } else {
+            for result in self.builder(&root).build() {
+                if matches!(handle(result), WalkState::Quit) {
+                    break;
+                }
+            }
+        }
+
+        token.check()?;
+        match failure.into_inner().unwrap_or_else(|e| e.into_inner()) {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
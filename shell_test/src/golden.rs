@@ -0,0 +1,136 @@
+//! Golden-file tests: a `.shtest` script holds commands, each on a line starting with `$ `, followed
+//! by the output it is expected to produce. Lines before the first command are kept as a header.
+//!
+//! ```text
+//! # Removing a file
+//! $ touch notes.txt
+//! $ rm notes.txt
+//! $ cat notes.txt
+//! warn: file 'notes.txt' does not exist
+//! ```
+//!
+//! Log records show up as `<level>: <message>` and a failed command adds `error: <message>`.
+//! Each script runs in its own temporary directory.
+
+use std::{fs, io, path::Path};
+
+use crate::TestShell;
+
+const COMMAND_PREFIX: &str = "$ ";
+
+/// One command of a script and the output following it.
+struct Step {
+    line: String,
+    expected: String,
+}
+
+struct Script {
+    header: String,
+    steps: Vec<Step>,
+}
+
+fn parse(contents: &str) -> Script {
+    let mut header = String::new();
+    let mut steps: Vec<Step> = Vec::new();
+    for line in contents.split_inclusive('\n') {
+        match (line.strip_prefix(COMMAND_PREFIX), steps.last_mut()) {
+            (Some(command), _) => steps.push(Step { line: command.trim_end().to_string(), expected: String::new() }),
+            (None, Some(step)) => step.expected.push_str(line),
+            (None, None) => header.push_str(line),
+        }
+    }
+    Script { header, steps }
+}
+
+/// What a command produced, in the form it is written to a script.
+fn render(shell: &mut TestShell, line: &str) -> String {
+    let run = shell.run(line);
+    let mut text = run.stdout;
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str(&run.stderr);
+    if let Err(e) = run.result {
+        text.push_str(&format!("error: {}\n", e));
+    }
+    text
+}
+
+/// How running one script went.
+pub struct Outcome {
+    pub steps: usize,
+    /// Commands whose output differed, with a diff for each.
+    pub failures: Vec<String>,
+}
+
+/// Runs the script at `path`. With `update`, the script is rewritten with the actual output
+/// instead of being compared against it.
+pub fn run_file(path: &Path, update: bool) -> io::Result<Outcome> {
+    let script = parse(&fs::read_to_string(path)?);
+    let mut shell = TestShell::new();
+
+    let mut failures = Vec::new();
+    let mut updated = script.header.clone();
+    for step in &script.steps {
+        let actual = render(&mut shell, &step.line);
+        if actual != step.expected {
+            failures.push(diff(&step.line, &step.expected, &actual));
+        }
+        updated.push_str(&format!("{}{}\n{}", COMMAND_PREFIX, step.line, actual));
+    }
+    drop(shell);
+
+    if update && !failures.is_empty() {
+        fs::write(path, updated)?;
+    }
+    Ok(Outcome { steps: script.steps.len(), failures })
+}
+
+/// The expected and actual lines of `line`, marked with `-` and `+`, with the shared lines in between left out.
+fn diff(line: &str, expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    let prefix = expected.iter().zip(&actual).take_while(|(a, b)| a == b).count();
+    let suffix = expected[prefix..].iter().rev()
+        .zip(actual[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut text = format!("{}{}\n", COMMAND_PREFIX, line);
+    for removed in &expected[prefix..expected.len() - suffix] {
+        text.push_str(&format!("-{}\n", removed));
+    }
+    for added in &actual[prefix..actual.len() - suffix] {
+        text.push_str(&format!("+{}\n", added));
+    }
+    if expected.len() == actual.len() && prefix == expected.len() {
+        text.push_str(" (differs in trailing whitespace or line endings)\n");
+    }
+    text
+}
+
+/// Runs every script in `paths`, printing a report, and returns whether all of them passed.
+/// With `update`, failing scripts are rewritten instead and count as passed.
+pub fn run(paths: &[&Path], update: bool) -> bool {
+    let mut failed = 0;
+    for path in paths {
+        match run_file(path, update) {
+            Ok(outcome) if outcome.failures.is_empty() => println!("ok      {} ({} commands)", path.display(), outcome.steps),
+            Ok(outcome) if update => println!("updated {} ({} changed)", path.display(), outcome.failures.len()),
+            Ok(outcome) => {
+                failed += 1;
+                println!("FAILED  {} ({} of {} commands)", path.display(), outcome.failures.len(), outcome.steps);
+                for failure in outcome.failures {
+                    println!("{}", failure);
+                }
+            }
+            Err(e) => {
+                failed += 1;
+                println!("FAILED  {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    println!("{} of {} scripts passed", paths.len() - failed, paths.len());
+    failed == 0
+}
@@ -0,0 +1,194 @@
+//! End-to-end tests for builtins: command lines go through the same tokenizer, registry and
+//! middleware as typed ones, inside a temporary working directory.
+//!
+//! ```ignore
+//! let mut shell = shell_test::TestShell::new();
+//! shell.write("notes.txt", "hello");
+//! shell.run("rm notes.txt").assert_ok();
+//! assert!(!shell.exists("notes.txt"));
+//! ```
+//!
+//! Only the commands linked into the test binary are registered, so tests live in the crate defining them.
+//! The shell itself runs golden-file scripts with `--test`, see [`golden`].
+
+pub mod golden;
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, Once},
+};
+
+use command_core::{output, vfs::{self, Fs}, CommandError, Shell};
+use log::{LevelFilter, Log, Metadata, Record};
+use tempfile::TempDir;
+
+/// Held by the [`TestShell`] alive, as the working directory is process-wide.
+static LOCK: Mutex<()> = Mutex::new(());
+static INSTALL: Once = Once::new();
+
+/// Writes log records, which is how builtins report warnings and progress, to the captured stderr.
+struct CaptureLogger;
+
+impl Log for CaptureLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        _ = output::print_error(&format!("{}: {}\n", record.level().as_str().to_lowercase(), record.args()));
+    }
+
+    fn flush(&self) {}
+}
+
+/// What running one command line produced.
+#[derive(Debug)]
+pub struct Run {
+    pub stdout: String,
+    pub stderr: String,
+    pub result: Result<(), CommandError>,
+}
+
+impl Run {
+    #[track_caller]
+    pub fn assert_ok(&self) -> &Self {
+        if let Err(e) = &self.result {
+            panic!("command failed: {}\nstderr:\n{}", e, self.stderr);
+        }
+        self
+    }
+
+    /// Asserts the command failed, with an error whose message contains `text`.
+    #[track_caller]
+    pub fn assert_err(&self, text: &str) -> &Self {
+        match &self.result {
+            Ok(()) => panic!("command succeeded, expected an error containing '{}'", text),
+            Err(e) => assert!(e.to_string().contains(text), "error '{}' does not contain '{}'", e, text),
+        }
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_stdout(&self, expected: &str) -> &Self {
+        assert_eq!(self.stdout, expected, "unexpected stdout");
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_stdout_contains(&self, text: &str) -> &Self {
+        assert!(self.stdout.contains(text), "stdout does not contain '{}':\n{}", text, self.stdout);
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_stderr_contains(&self, text: &str) -> &Self {
+        assert!(self.stderr.contains(text), "stderr does not contain '{}':\n{}", text, self.stderr);
+        self
+    }
+}
+
+/// A shell running in a fresh temporary directory, removed again on drop along with the working
+/// directory change. Tests using one run one at a time.
+pub struct TestShell {
+    shell: Shell,
+    dir: TempDir,
+    previous_dir: Option<PathBuf>,
+    previous_fs: Option<Option<Arc<dyn Fs>>>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Default for TestShell {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestShell {
+    pub fn new() -> Self {
+        // A test that panicked while holding the lock leaves nothing behind that matters here.
+        let lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        INSTALL.call_once(|| {
+            if log::set_logger(&CaptureLogger).is_ok() {
+                log::set_max_level(LevelFilter::Info);
+            }
+        });
+
+        let dir = tempfile::tempdir().expect("creating the test directory");
+        let previous_dir = env::current_dir().ok();
+        env::set_current_dir(dir.path()).expect("entering the test directory");
+
+        Self {
+            shell: Shell::builder().build(),
+            dir,
+            previous_dir,
+            previous_fs: None,
+            _lock: lock,
+        }
+    }
+
+    /// Runs the file commands against `fs`, e.g. a [`vfs::MemoryFs`], until the shell is dropped.
+    pub fn use_fs(&mut self, fs: Arc<dyn Fs>) -> &mut Self {
+        let previous = vfs::set(fs);
+        self.previous_fs.get_or_insert(previous);
+        self
+    }
+
+    /// The temporary working directory.
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Runs `line` as if it was typed, returning what it wrote and returned.
+    pub fn run(&mut self, line: &str) -> Run {
+        let (result, captured) = output::capture(|| self.shell.eval(line));
+        Run {
+            stdout: captured.stdout_text().into_owned(),
+            stderr: captured.stderr_text().into_owned(),
+            result,
+        }
+    }
+
+    /// Creates `path` below the working directory with `contents`, and any missing parent directories.
+    pub fn write(&self, path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> &Self {
+        let path = self.path().join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("creating parent directories");
+        }
+        fs::write(&path, contents).expect("writing a test file");
+        self
+    }
+
+    pub fn mkdir(&self, path: impl AsRef<Path>) -> &Self {
+        fs::create_dir_all(self.path().join(path)).expect("creating a test directory");
+        self
+    }
+
+    #[track_caller]
+    pub fn read(&self, path: impl AsRef<Path>) -> String {
+        let path = self.path().join(path);
+        fs::read_to_string(&path).unwrap_or_else(|e| panic!("reading '{}': {}", path.display(), e))
+    }
+
+    pub fn exists(&self, path: impl AsRef<Path>) -> bool {
+        fs::symlink_metadata(self.path().join(path)).is_ok()
+    }
+
+    pub fn is_dir(&self, path: impl AsRef<Path>) -> bool {
+        self.path().join(path).is_dir()
+    }
+}
+
+impl Drop for TestShell {
+    fn drop(&mut self) {
+        match self.previous_fs.take() {
+            Some(Some(previous)) => _ = vfs::set(previous),
+            Some(None) => vfs::reset(),
+            None => {}
+        }
+        // Leave the directory before it is removed; Windows can't remove a current directory.
+        if let Some(previous) = &self.previous_dir {
+            _ = env::set_current_dir(previous);
+        }
+    }
+}